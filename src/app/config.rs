@@ -3,17 +3,31 @@ use std::{
     fs::File,
     io::{Read, Write},
     path::PathBuf,
+    sync::OnceLock,
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::internal::library::path_hash;
 
+/// Set by the `--config` option, in place of the default location.
+static FILE_PATH: OnceLock<PathBuf> = OnceLock::new();
+
 #[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Config {
     pub library: Library,
     pub playlists: Playlists,
+    #[serde(default)]
+    pub ratings: Ratings,
+    #[serde(default)]
+    pub shuffle: Shuffle,
+    #[serde(default)]
+    pub radio: Radio,
+    #[serde(default)]
+    pub output: Output,
     pub misc: Misc,
+    #[serde(default)]
+    pub keys: super::keys::Keys,
 }
 
 impl Config {
@@ -21,15 +35,39 @@ impl Config {
         let mut file = File::open(path)?;
         let mut s = String::new();
         file.read_to_string(&mut s)?;
-        Ok(toml::from_str(&s)?)
+        Self::parse(&s)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut config: Self = toml::from_str(s)?;
+        if let Some(path) = config.library.path.take()
+            && config.library.roots.is_empty()
+        {
+            config.library.roots.push(LibraryRoot::new(path));
+        }
+        if let Some(volume) = config.misc.default_volume.take() {
+            config.misc.startup_volume =
+                crate::internal::audio::volume_position(volume);
+        }
+        config.keys.check();
+        Ok(config)
     }
 
     pub fn file_path() -> Result<PathBuf, Box<dyn Error>> {
-        let mut path = crate::exe_path()?;
+        if let Some(path) = FILE_PATH.get() {
+            return Ok(path.clone());
+        }
+        let mut path = crate::paths::config_dir()?;
         path.push("config.toml");
         Ok(path)
     }
 
+    /// Overrides the path returned by `file_path`. Only the first call has any
+    /// effect.
+    pub fn set_file_path(path: PathBuf) {
+        let _ = FILE_PATH.set(path);
+    }
+
     pub fn verify_pins(self, lib: &crate::internal::Library) -> Self {
         Self {
             library: Library {
@@ -47,11 +85,7 @@ impl Config {
                     .pins
                     .into_iter()
                     .filter(|p| {
-                        crate::exe_path()
-                            .unwrap()
-                            .join("playlists/")
-                            .join(p)
-                            .exists()
+                        crate::paths::playlist_dir().unwrap().join(p).exists()
                     })
                     .collect(),
             },
@@ -59,44 +93,190 @@ impl Config {
         }
     }
 
+    pub fn serialize(&self) -> Result<String, Box<dyn Error>> {
+        Ok(toml::to_string_pretty(&self)?)
+    }
+
     pub fn write_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let toml = toml::to_string_pretty(&self)?;
+        let toml = self.serialize()?;
         let mut file = File::create(path)?;
         Ok(file.write_all(toml.as_bytes())?)
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Library {
-    pub path: PathBuf,
+    /// The folders scanned into the library.
+    #[serde(default)]
+    pub roots: Vec<LibraryRoot>,
+    /// The single library folder of older configs, moved into `roots` when
+    /// they're read.
+    #[serde(default, skip_serializing)]
+    path: Option<PathBuf>,
     pub full_rescan_on_start: bool,
     pub pins: Vec<PathBuf>,
+    /// A folder given with `--library`, scanned in place of `roots` for this
+    /// run only. It's never saved.
+    #[serde(skip)]
+    pub session_root: Option<PathBuf>,
 }
 
-impl Default for Library {
-    fn default() -> Self {
+impl Library {
+    pub fn enabled_roots(&self) -> Vec<PathBuf> {
+        if let Some(root) = &self.session_root {
+            return vec![root.clone()];
+        }
+        self.roots
+            .iter()
+            .filter(|root| root.enabled)
+            .map(|root| root.path.clone())
+            .collect()
+    }
+
+    /// Adds `path` as a library folder, in place of the one at `replacing`
+    /// and any that it overlaps with, keeping the rest.
+    pub fn replace_root(&mut self, replacing: Option<usize>, path: PathBuf) {
+        if let Some(i) = replacing
+            && i < self.roots.len()
+        {
+            self.roots.remove(i);
+        }
+        self.roots.retain(|root| {
+            !path.starts_with(&root.path) && !root.path.starts_with(&path)
+        });
+        self.roots.push(LibraryRoot::new(path));
+        self.session_root = None;
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LibraryRoot {
+    pub path:    PathBuf,
+    /// Disabled folders are left out of the library without being forgotten.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl LibraryRoot {
+    pub fn new(path: PathBuf) -> Self {
         Self {
-            path: "/".into(),
-            full_rescan_on_start: false,
-            pins: vec![],
+            path,
+            enabled: true,
         }
     }
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_startup_volume() -> f32 {
+    // the slider's on a decibel scale, so this is about -15 dB:
+    0.75
+}
+
 #[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Playlists {
     pub pins: Vec<PathBuf>,
 }
 
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Ratings {
+    /// Also save ratings into the tags of the rated files.
+    pub write_to_files:   bool,
+    /// Favor higher-rated tracks when shuffling.
+    pub weighted_shuffle: bool,
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Shuffle {
+    /// How the queue is shuffled while shuffle is on.
+    pub strategy: super::shuffle::Strategy,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Radio {
+    /// How tracks queued by radio mode should be related to the last track.
+    pub relations: Vec<super::radio::Relation>,
+}
+
+impl Default for Radio {
+    fn default() -> Self {
+        Self {
+            relations: super::radio::Relation::ALL.to_vec(),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct Output {
+    /// The name of the device to play on, or `None` for the system's default.
+    pub device: Option<String>,
+    /// Open the device in each track's own format, and leave the volume alone,
+    /// so that samples reach it unchanged.
+    #[serde(default)]
+    pub bit_perfect: bool,
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Misc {
-    pub default_volume: f32,
+    /// The volume to start at, as a position on the volume slider, when the
+    /// last one isn't remembered.
+    #[serde(default = "default_startup_volume")]
+    pub startup_volume: f32,
+    /// The startup volume of older configs, as an amplitude rather than a
+    /// position on the slider, converted into `startup_volume` when they're
+    /// read.
+    #[serde(default, skip_serializing)]
+    default_volume: Option<f32>,
+    /// Start at the volume and mute setting the app was closed with.
+    #[serde(default = "default_enabled")]
+    pub remember_volume: bool,
+    /// The volume the app was closed with, if it's remembered.
+    #[serde(default)]
+    pub last_volume: Option<f32>,
+    #[serde(default)]
+    pub last_mute: bool,
 }
 
 impl Default for Misc {
     fn default() -> Self {
         Self {
-            default_volume: 0.5,
+            startup_volume: default_startup_volume(),
+            default_volume: None,
+            remember_volume: true,
+            last_volume: None,
+            last_mute: false,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn older_startup_volumes_are_converted() {
+        let config = Config::parse(
+            "[library]\nfull_rescan_on_start = false\npins = \
+             []\n[playlists]\npins = []\n[misc]\ndefault_volume = 0.5\n",
+        )
+        .unwrap();
+        // half the amplitude is about 6 dB down, a tenth of the slider:
+        assert!((config.misc.startup_volume - 0.9).abs() < 0.001);
+        assert!(!config.serialize().unwrap().contains("default_volume"));
+    }
+
+    #[test]
+    fn an_empty_radio_section_is_the_default() {
+        let config = Config::parse(
+            "[library]\nfull_rescan_on_start = false\npins = \
+             []\n[playlists]\npins = []\n[radio]\n# nothing yet\n[misc]\n",
+        )
+        .unwrap();
+        assert_eq!(config.radio.relations, super::super::radio::Relation::ALL);
+    }
+}
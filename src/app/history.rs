@@ -0,0 +1,275 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufReader, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::internal::{Library, Track};
+
+/// Fraction of a track that has to be listened to for a playback to count
+/// towards a track's play count.
+pub const PLAYED_THRESHOLD: f32 = 0.5;
+
+/// Held while writing to the history file, so that records are written in
+/// the order they were added.
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Number of entries in each of the most played lists.
+const TOP_LEN: usize = 10;
+
+/// The statistics shown in the history view. These go through the whole
+/// history and library, so they're only worked out while the view is open.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub listening_time: Duration,
+    pub tracks: Vec<(u64, usize)>,
+    pub albums: Vec<(String, usize)>,
+    pub artists: Vec<(String, usize)>,
+    pub never_played: Vec<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PlayRecord {
+    pub track: u64,
+    /// Seconds since the Unix epoch.
+    pub started: u64,
+    pub listened: Duration,
+    /// Fraction of the track that was played before it finished or was
+    /// skipped, from 0.0 - 1.0.
+    pub completion: f32,
+}
+
+impl PlayRecord {
+    pub fn played(&self) -> bool {
+        self.completion >= PLAYED_THRESHOLD
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+    #[default]
+    AllTime,
+}
+
+impl Period {
+    pub const ALL: [Period; 5] = [
+        Period::Day,
+        Period::Week,
+        Period::Month,
+        Period::Year,
+        Period::AllTime,
+    ];
+
+    fn secs(&self) -> Option<u64> {
+        const DAY: u64 = 60 * 60 * 24;
+        match self {
+            Period::Day => Some(DAY),
+            Period::Week => Some(DAY * 7),
+            Period::Month => Some(DAY * 30),
+            Period::Year => Some(DAY * 365),
+            Period::AllTime => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Period {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Period::Day => "Today",
+            Period::Week => "Week",
+            Period::Month => "Month",
+            Period::Year => "Year",
+            Period::AllTime => "All time",
+        })
+    }
+}
+
+/// An append-only log of every track that finished playing or was skipped.
+#[derive(Default)]
+pub struct History {
+    records:   Vec<PlayRecord>,
+    /// Records that are yet to be written to the history file, encoded.
+    unwritten: Arc<Mutex<Vec<u8>>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file_path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = crate::paths::data_dir()?;
+        path.push("history");
+        Ok(path)
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut records = vec![];
+        // a record cut off by a crash mid-write is dropped along with
+        // anything after it:
+        while let Ok(record) = bincode::serde::decode_from_std_read(
+            &mut reader,
+            bincode::config::standard(),
+        ) {
+            records.push(record);
+        }
+        Ok(Self {
+            records,
+            unwritten: Arc::default(),
+        })
+    }
+
+    /// Adds a record to the history, and appends it to the history file in
+    /// the background.
+    pub fn append(&mut self, record: PlayRecord) -> Result<(), Box<dyn Error>> {
+        let data = bincode::serde::encode_to_vec(
+            &record,
+            bincode::config::standard(),
+        )?;
+        self.records.push(record);
+        self.unwritten.lock().unwrap().extend_from_slice(&data);
+        let unwritten = self.unwritten.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = write_unwritten(&unwritten)
+                .inspect_err(|e| eprintln!("Couldn't write play history: {e}"));
+        });
+        Ok(())
+    }
+
+    /// Writes any records that are still waiting to be, for when the app is
+    /// about to exit.
+    pub fn flush(&self) -> Result<(), Box<dyn Error>> {
+        write_unwritten(&self.unwritten)
+    }
+
+    pub fn stats(&self, lib: &Library, period: Period) -> Stats {
+        Stats {
+            listening_time: self.listening_time(period),
+            tracks: self.most_played_tracks(period, TOP_LEN),
+            albums: self.most_played_albums(lib, period, TOP_LEN),
+            artists: self.most_played_artists(lib, period, TOP_LEN),
+            never_played: self.never_played(lib),
+        }
+    }
+
+    pub fn records(&self) -> &[PlayRecord] {
+        &self.records
+    }
+
+    fn since(&self, period: Period) -> impl Iterator<Item = &PlayRecord> {
+        let cutoff = period.secs().map_or(0, |secs| now().saturating_sub(secs));
+        self.records.iter().filter(move |r| r.started >= cutoff)
+    }
+
+    pub fn listening_time(&self, period: Period) -> Duration {
+        self.since(period).map(|r| r.listened).sum()
+    }
+
+    /// Returns up to `n` tracks with the most plays in the given period, along
+    /// with their play counts.
+    pub fn most_played_tracks(
+        &self,
+        period: Period,
+        n: usize,
+    ) -> Vec<(u64, usize)> {
+        let mut counts = HashMap::new();
+        self.since(period)
+            .filter(|r| r.played())
+            .for_each(|r| *counts.entry(r.track).or_insert(0) += 1);
+        top_n(counts, n)
+    }
+
+    /// Returns up to `n` albums with the most plays in the given period.
+    pub fn most_played_albums(
+        &self,
+        lib: &Library,
+        period: Period,
+        n: usize,
+    ) -> Vec<(String, usize)> {
+        self.most_played_by(lib, period, n, |track| {
+            track.metadata.album.iter().cloned().collect()
+        })
+    }
+
+    /// Returns up to `n` artists with the most plays in the given period.
+    pub fn most_played_artists(
+        &self,
+        lib: &Library,
+        period: Period,
+        n: usize,
+    ) -> Vec<(String, usize)> {
+        self.most_played_by(lib, period, n, |track| {
+            track.metadata.artists.clone()
+        })
+    }
+
+    fn most_played_by(
+        &self,
+        lib: &Library,
+        period: Period,
+        n: usize,
+        keys: impl Fn(&Track) -> Vec<String>,
+    ) -> Vec<(String, usize)> {
+        let mut counts = HashMap::new();
+        self.since(period)
+            .filter(|r| r.played())
+            .filter_map(|r| lib.get_track(r.track))
+            .flat_map(keys)
+            .for_each(|key| *counts.entry(key).or_insert(0) += 1);
+        top_n(counts, n)
+    }
+
+    /// Returns every track in the library that has never counted as played.
+    pub fn never_played(&self, lib: &Library) -> Vec<u64> {
+        let played = self
+            .records
+            .iter()
+            .filter(|r| r.played())
+            .map(|r| r.track)
+            .collect::<HashSet<_>>();
+        let mut tracks = lib
+            .tracks()
+            .filter(|(id, _)| !played.contains(id))
+            .map(|(id, track)| (*id, track))
+            .collect::<Vec<_>>();
+        tracks.sort_unstable_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+        tracks.into_iter().map(|(id, _)| id).collect()
+    }
+}
+
+fn write_unwritten(unwritten: &Mutex<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    let _writing = WRITING.lock().unwrap();
+    let data = std::mem::take(&mut *unwritten.lock().unwrap());
+    if data.is_empty() {
+        return Ok(());
+    }
+    let mut f = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(History::file_path()?)?;
+    Ok(f.write_all(&data)?)
+}
+
+fn top_n<K: Ord>(counts: HashMap<K, usize>, n: usize) -> Vec<(K, usize)> {
+    let mut counts = counts.into_iter().collect::<Vec<_>>();
+    counts.sort_unstable_by(|(ka, a), (kb, b)| b.cmp(a).then(ka.cmp(kb)));
+    counts.truncate(n);
+    counts
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,225 @@
+//! Keyboard shortcuts, and the `[keys]` section of the config which rebinds
+//! them.
+//!
+//! A binding is a key, optionally preceded by modifiers: `space`, `n`,
+//! `ctrl+right`, or `shift+delete`. An empty binding disables the shortcut.
+
+use iced::keyboard::{Key, Modifiers, key::Named};
+use serde::{Deserialize, Serialize};
+
+use super::Message;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    PlayPause,
+    SeekForward,
+    SeekBack,
+    VolumeUp,
+    VolumeDown,
+    VolumeUpFine,
+    VolumeDownFine,
+    Next,
+    Previous,
+    Search,
+    RemoveFromQueue,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Keys {
+    pub play_pause: String,
+    pub seek_forward: String,
+    pub seek_back: String,
+    pub volume_up: String,
+    pub volume_down: String,
+    /// Turns the volume up or down by a smaller step.
+    pub volume_up_fine: String,
+    pub volume_down_fine: String,
+    pub next: String,
+    pub previous: String,
+    pub search: String,
+    /// Removes the selected tracks from the queue, or else the next one.
+    pub remove_from_queue: String,
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self {
+            play_pause: String::from("space"),
+            seek_forward: String::from("right"),
+            seek_back: String::from("left"),
+            volume_up: String::from("up"),
+            volume_down: String::from("down"),
+            volume_up_fine: String::from("shift+up"),
+            volume_down_fine: String::from("shift+down"),
+            next: String::from("n"),
+            previous: String::from("p"),
+            search: String::from("/"),
+            remove_from_queue: String::from("delete"),
+        }
+    }
+}
+
+impl Keys {
+    fn bindings(&self) -> [(&'static str, &str, Action); 11] {
+        [
+            ("play_pause", &self.play_pause, Action::PlayPause),
+            ("seek_forward", &self.seek_forward, Action::SeekForward),
+            ("seek_back", &self.seek_back, Action::SeekBack),
+            ("volume_up", &self.volume_up, Action::VolumeUp),
+            ("volume_down", &self.volume_down, Action::VolumeDown),
+            ("volume_up_fine", &self.volume_up_fine, Action::VolumeUpFine),
+            (
+                "volume_down_fine",
+                &self.volume_down_fine,
+                Action::VolumeDownFine,
+            ),
+            ("next", &self.next, Action::Next),
+            ("previous", &self.previous, Action::Previous),
+            ("search", &self.search, Action::Search),
+            (
+                "remove_from_queue",
+                &self.remove_from_queue,
+                Action::RemoveFromQueue,
+            ),
+        ]
+    }
+
+    /// The action bound to the given key press, if any.
+    pub fn action(&self, key: &Key, modifiers: Modifiers) -> Option<Action> {
+        self.bindings()
+            .into_iter()
+            .find_map(|(_, binding, action)| {
+                Binding::parse(binding)
+                    .is_some_and(|binding| binding.matches(key, modifiers))
+                    .then_some(action)
+            })
+    }
+
+    /// Reports any bindings which couldn't be understood.
+    pub fn check(&self) {
+        for (name, binding, _) in self.bindings() {
+            if !binding.is_empty() && Binding::parse(binding).is_none() {
+                eprintln!("Unknown key `{binding}` for keys.{name}");
+            }
+        }
+    }
+}
+
+struct Binding {
+    key: Key,
+    modifiers: Modifiers,
+}
+
+impl Binding {
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_lowercase();
+        // `+` can be bound too, as in `ctrl++`:
+        let (modifiers, key) = match s.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None if s == "+" => ("", "+"),
+            None => s.rsplit_once('+').unwrap_or(("", &s)),
+        };
+
+        let mut parsed = Modifiers::empty();
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            parsed |= match modifier {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" => Modifiers::ALT,
+                "super" | "logo" | "cmd" => Modifiers::LOGO,
+                _ => return None,
+            };
+        }
+
+        let key = match key {
+            "space" => Key::Named(Named::Space),
+            "left" => Key::Named(Named::ArrowLeft),
+            "right" => Key::Named(Named::ArrowRight),
+            "up" => Key::Named(Named::ArrowUp),
+            "down" => Key::Named(Named::ArrowDown),
+            "delete" | "del" => Key::Named(Named::Delete),
+            "backspace" => Key::Named(Named::Backspace),
+            "enter" | "return" => Key::Named(Named::Enter),
+            "escape" | "esc" => Key::Named(Named::Escape),
+            "tab" => Key::Named(Named::Tab),
+            "home" => Key::Named(Named::Home),
+            "end" => Key::Named(Named::End),
+            "pageup" => Key::Named(Named::PageUp),
+            "pagedown" => Key::Named(Named::PageDown),
+            "insert" => Key::Named(Named::Insert),
+            key if key.chars().count() == 1 => Key::Character(key.into()),
+            _ => return None,
+        };
+        Some(Self {
+            key,
+            modifiers: parsed,
+        })
+    }
+
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        match (&self.key, key) {
+            // shift is already part of a typed character, as with `?`, so it
+            // only has to match if it's asked for:
+            (Key::Character(bound), Key::Character(pressed)) => {
+                bound.as_str() == pressed.to_lowercase()
+                    && (self.modifiers == modifiers
+                        || (!self.modifiers.shift()
+                            && self.modifiers == modifiers - Modifiers::SHIFT))
+            }
+            (bound, pressed) => bound == pressed && self.modifiers == modifiers,
+        }
+    }
+}
+
+/// Sends every key press which isn't taken by a widget, such as a text input
+/// being typed in, as a `Message::KeyPressed`.
+pub fn subscription() -> iced::Subscription<Message> {
+    iced::keyboard::on_key_press(|key, modifiers| {
+        Some(Message::KeyPressed(key, modifiers))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bindings_match_key_presses() {
+        let keys = Keys {
+            next: String::from("ctrl+right"),
+            search: String::from("?"),
+            volume_up: String::from("ctrl++"),
+            previous: String::new(),
+            ..Keys::default()
+        };
+        keys.check();
+
+        let space = Key::Named(Named::Space);
+        assert_eq!(
+            keys.action(&space, Modifiers::empty()),
+            Some(Action::PlayPause)
+        );
+        assert_eq!(keys.action(&space, Modifiers::CTRL), None);
+
+        let right = Key::Named(Named::ArrowRight);
+        assert_eq!(keys.action(&right, Modifiers::CTRL), Some(Action::Next));
+        assert_eq!(
+            keys.action(&right, Modifiers::empty()),
+            Some(Action::SeekForward)
+        );
+
+        let question = Key::Character("?".into());
+        assert_eq!(
+            keys.action(&question, Modifiers::SHIFT),
+            Some(Action::Search)
+        );
+        let plus = Key::Character("+".into());
+        assert_eq!(keys.action(&plus, Modifiers::CTRL), Some(Action::VolumeUp));
+        let p = Key::Character("p".into());
+        assert_eq!(keys.action(&p, Modifiers::empty()), None);
+
+        assert!(Binding::parse("hyper+x").is_none());
+        assert!(Binding::parse("nonsense").is_none());
+    }
+}
@@ -1,29 +1,71 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
 use config::Config;
+use history::{History, Period, PlayRecord};
 use iced::task::Task;
 use playlist::{Playlist, PlaylistMap, PlaylistTrack};
+use session::{Session, SessionView};
+use user_data::{GainTarget, UserData};
 pub use view::ICON_FONT_BYTES;
-use view::{queue, sidebar, start_screen};
+use view::{queue, settings, sidebar, start_screen};
+use watch::FileStamps;
 
 use super::*;
 use crate::internal::library::path_hash;
 
-mod config;
-mod playlist;
+pub mod config;
+mod history;
+mod keys;
+#[cfg(target_os = "linux")]
+mod mpris;
+#[cfg(unix)]
+mod output;
+pub mod playlist;
+mod radio;
+mod remote;
+mod session;
+mod shuffle;
+#[cfg(test)]
+mod test;
+mod user_data;
 mod view;
+mod watch;
+
+/// How many previously played tracks are kept for Skip Back.
+const MAX_PREVIOUS: usize = 100;
+
+/// How long a notice, such as about a track that couldn't be played, is shown
+/// for.
+const NOTICE_TIME: Duration = Duration::from_secs(8);
 
 #[derive(Debug, Clone)]
 pub enum Message {
     CancelCreatePlaylist,
+    /// Checks that playback is going to the right device.
+    CheckOutput,
     CloseAddToPlaylist,
+    CloseRequested(iced::window::Id),
+    ConfigChanged,
     CreatePlaylist,
     DeletePlaylist(u64),
+    HistoryPeriod(Period),
     ImgPathChanged(String),
     ImgSelected(Option<rfd::FileHandle>),
+    KeyPressed(iced::keyboard::Key, iced::keyboard::Modifiers),
+    #[cfg(target_os = "linux")]
+    Mpris(mpris::Event),
     None,
     OpenImgDialog,
     OpenNewPlaylist,
+    /// The devices listed in the background for `check_output`.
+    OutputDevicesListed(Vec<String>),
     PinAdd(PinKind, PathBuf),
     PlaylistPathChanged(String),
+    PlaylistsChanged,
     PlaylistRemove(usize),
     PlaylistSelected(u64),
     PlaylistSwap(usize, usize),
@@ -32,18 +74,54 @@ pub enum Message {
     PlayheadMoved(f32),
     PlayheadReleased,
     Queue(queue::QueueMessage),
+    #[cfg(unix)]
+    RemoteStatus(Option<crate::ipc::Status>),
+    Resume(u64, Duration),
+    SaveSession,
     ScanDone,
+    SearchChanged(String),
     SelectPlaylist(u64),
+    /// Sets a gain offset, in decibels.
+    SetGain(GainTarget, f32),
+    SetRating(u64, u8),
+    Settings(settings::SettingsMessage),
     SidebarMessage(sidebar::SidebarMessage),
+    SortTracks(SortColumn),
     StartScreen(start_screen::Message),
+    ToggleFavorite(u64),
     ToggleMute,
     TogglePlay,
+    ToggleRadio,
     ToggleRepeat,
     UpdateProgress,
+    ViewHistory,
     ViewLibrary(u64),
     ViewLibraryRoot,
     ViewPlaylist(Option<u64>),
+    ViewSettings,
     VolumeChanged(f32),
+    /// The mouse wheel was turned over the volume controls, by this many
+    /// lines.
+    VolumeScrolled(f32),
+}
+
+#[cfg(unix)]
+impl Message {
+    /// The message a request forwarded from another launch is handled as (see
+    /// `crate::instance`), if it's one the app takes.
+    pub fn from_request(request: crate::ipc::Request) -> Option<Self> {
+        use crate::ipc::Request;
+
+        match request {
+            Request::Enqueue { path } => {
+                Some(queue::QueueMessage::Enqueue(vec![path]).into())
+            }
+            Request::Next => Some(queue::QueueMessage::SkipForward.into()),
+            Request::Stop => Some(queue::QueueMessage::Stop.into()),
+            Request::Toggle => Some(Message::TogglePlay),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -52,7 +130,9 @@ pub enum PinKind {
     Playlist,
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize,
+)]
 pub enum PlayStatus {
     #[default]
     Pause,
@@ -60,7 +140,9 @@ pub enum PlayStatus {
     Stopped,
 }
 
-#[derive(Copy, Clone, Default, PartialEq)]
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize,
+)]
 pub enum RepeatStatus {
     #[default]
     None,
@@ -68,34 +150,105 @@ pub enum RepeatStatus {
     All,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SortColumn {
+    Number,
+    Title,
+    Album,
+    Rating,
+    Duration,
+}
+
+/// Options given on the command line which affect how the app starts.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptions {
+    /// A library to use in place of the configured one.
+    pub library: Option<PathBuf>,
+    /// Overrides `full_rescan_on_start` in the config.
+    pub full_rescan: Option<bool>,
+    /// Tracks or folders to play, replacing the queue.
+    pub play: Vec<PathBuf>,
+    /// Plays into nothing at this many times real time, instead of on a
+    /// device.
+    pub null_output: Option<f32>,
+}
+
 pub enum Viewing {
+    History,
     Library,
     Playlist(Option<u64>),
+    Settings,
 }
 
 pub struct App {
     codec_registry: &'static CodecRegistry,
     probe: &'static Probe,
 
-    config:    Config,
-    library:   Library,
+    config: Config,
+    /// The config file as it was last read or written, shared with the
+    /// writes made in the background.
+    config_stamp: Arc<Mutex<ConfigStamp>>,
+    /// How many times the config's been written.
+    config_writes: u64,
+    library: Library,
     playlists: PlaylistMap,
-    viewing:   Viewing,
+    user_data: UserData,
+    viewing: Viewing,
+    /// Column the track list is sorted by, and whether it's descending.
+    track_sort: Option<(SortColumn, bool)>,
+    /// Only tracks matching this are listed, if it isn't empty.
+    search: String,
 
+    /// Where outputs are opened.
+    backend: Arc<dyn internal::audio::Backend>,
+    /// Whether the devices are being listed for `check_output`.
+    listing_devices: bool,
+    /// The device being played on, if there is one.
+    output: Option<Box<dyn internal::audio::Output>>,
+    /// The devices offered in the settings, as of when they were opened.
+    output_devices: Vec<String>,
     sink: rodio::Sink,
+    /// How far the current track had played when the output was last checked.
+    last_position: Duration,
+    /// How far the current track has played.
+    position: internal::audio::Position,
+    /// Why the current track stopped early, if it did.
+    failure: internal::audio::Failure,
     playing: Option<Track>,
+    /// Something which went wrong with playback, with when it happened.
+    notice: Option<(String, std::time::Instant)>,
     queue: Vec<u64>,
+    queue_state: queue::QueueState,
+    /// Tracks played before the current one, most recent last, for skipping
+    /// back to.
+    previous: VecDeque<u64>,
+    /// While shuffle is on, the queue in the order it was filled, to be put
+    /// back when shuffle is turned off.
+    unshuffled: Option<Vec<u64>>,
     play_status: PlayStatus,
     repeat: RepeatStatus,
+    /// Whether related tracks are queued once the queue runs out.
+    radio: bool,
     playhead_position: f32,
     seeking: bool,
     /// If a track is playing, this stores the current timestamp as well as the
-    /// total duration of the track.
-    track_duration: Option<(Duration, Duration)>,
+    /// total duration of the track, if that's known.
+    track_duration: Option<(Duration, Option<Duration>)>,
+    /// Seconds since the Unix epoch at which the current track started.
+    play_started: u64,
+
+    history: History,
+    history_period: Period,
+    history_stats: history::Stats,
 
     mute: bool,
     volume: f32,
+    #[cfg(target_os = "linux")]
+    mpris: Option<mpris::Mpris>,
+    /// Whether playback is controlled by a daemon rather than this app.
+    remote: bool,
     start_screen: Option<start_screen::StartScreen>,
+    settings: settings::Settings,
     sidebar: sidebar::Sidebar,
 
     selecting_playlist: Option<u64>,
@@ -106,34 +259,77 @@ pub struct App {
     new_playlist_img:   String,
 }
 
-impl App {
-    pub fn new(stream_handle: rodio::OutputStreamHandle) -> Self {
+/// The config file's stamp, along with which write it's from, so that one
+/// which runs late is skipped rather than putting an older config back.
+#[derive(Default)]
+struct ConfigStamp {
+    stamps:  FileStamps,
+    written: u64,
+}
+
+/// What the app starts from, as it was left in its files.
+struct Saved {
+    config: Config,
+    /// The config file as it was read.
+    config_stamp: FileStamps,
+    library: Library,
+    start_screen: Option<start_screen::StartScreen>,
+    history: History,
+    user_data: UserData,
+    playlists: PlaylistMap,
+    session: Option<Session>,
+}
+
+impl Saved {
+    /// Fails if there's a config which can't be read, rather than starting
+    /// from the defaults and overwriting it.
+    fn load(
+        remote: bool,
+        options: &LaunchOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut config_stamp = FileStamps::default();
+        let path = Config::file_path()?;
+        let config = match std::fs::read_to_string(&path) {
+            Ok(s) => {
+                let config = Config::parse(&s)
+                    .map_err(|e| format!("{}: {e}", path.display()))?;
+                config_stamp.record(&path, s.as_bytes());
+                Some(config)
+            }
+            // there's none until one's written:
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
         let (config, library, start_screen) =
-            match Config::from_file(Config::file_path().unwrap()) {
-                Ok(config) => {
-                    let lib_cache_path = Library::file_path();
-                    if let Ok(path) = lib_cache_path
-                        && path.exists()
-                    {
-                        let lib = {
-                            let config_lib_path = config.library.path.clone();
-                            Library::from_file(&path).map_or_else(
-                                |_| internal::scan(&config_lib_path),
-                                |lib| {
-                                    if lib.root_directory().path
-                                        == config_lib_path
-                                        && !config.library.full_rescan_on_start
-                                    {
-                                        internal::partial_scan(
-                                            &config_lib_path,
-                                            lib,
-                                        )
-                                    } else {
-                                        internal::scan(&config_lib_path)
-                                    }
-                                },
-                            )
-                        };
+            match (config, options.library.clone()) {
+                // a library given on the command line is used in place of the
+                // configured one, just this once:
+                (config, Some(path)) => {
+                    let mut config = config.unwrap_or_default();
+                    config.library.session_root = Some(path);
+                    let lib = load_library(
+                        &config.library.enabled_roots(),
+                        options
+                            .full_rescan
+                            .unwrap_or(config.library.full_rescan_on_start),
+                    );
+                    let _ = lib.write_to_file().inspect_err(|e| {
+                        eprintln!("Problem caching library data: {e}")
+                    });
+                    (config, lib, None)
+                }
+                (Some(config), None) => {
+                    // a library whose cache has gone, or was left behind by an
+                    // older version, is scanned again rather than chosen again:
+                    let cached =
+                        Library::file_path().is_ok_and(|path| path.exists());
+                    if cached || !config.library.enabled_roots().is_empty() {
+                        let lib = load_library(
+                            &config.library.enabled_roots(),
+                            options
+                                .full_rescan
+                                .unwrap_or(config.library.full_rescan_on_start),
+                        );
                         (config, lib, None)
                     } else {
                         (
@@ -143,65 +339,229 @@ impl App {
                         )
                     }
                 }
-                Err(e) => {
-                    eprintln!("Couldn't read config: {e}");
-                    (
-                        Config::default(),
-                        Library::new(),
-                        Some(start_screen::StartScreen::new()),
-                    )
-                }
+                (None, None) => (
+                    Config::default(),
+                    Library::new(),
+                    Some(start_screen::StartScreen::new()),
+                ),
             };
 
         let config = config.verify_pins(&library);
 
-        let mut playlists = PlaylistMap::new();
-        playlists.scan_playlists();
+        let history = History::file_path()
+            .ok()
+            .filter(|path| path.exists())
+            .map_or_else(History::new, |path| {
+                History::from_file(&path).unwrap_or_else(|e| {
+                    eprintln!("Couldn't read play history: {e}");
+                    History::new()
+                })
+            });
 
-        let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-        let volume = config.misc.default_volume.clamp(0.0, 1.0);
-        sink.set_volume(volume);
+        let user_data = UserData::load().unwrap_or_else(|e| {
+            eprintln!("Couldn't read user data: {e}");
+            UserData::new()
+        });
 
-        let sidebar = sidebar::Sidebar::new(
-            config
-                .library
-                .pins
-                .iter()
-                .map(|path| {
-                    (
-                        path_hash(path),
-                        path.file_stem().unwrap().to_str().unwrap().to_owned(),
-                    )
+        let mut playlists = PlaylistMap::new();
+        let _ = playlists
+            .scan_playlists()
+            .inspect_err(|e| eprintln!("Couldn't read playlists: {e}"));
+
+        // an attached app leaves the session to the daemon:
+        let session = if start_screen.is_none() && !remote {
+            Session::file_path()
+                .ok()
+                .filter(|path| path.exists())
+                .and_then(|path| {
+                    Session::from_file(path)
+                        .inspect_err(|e| {
+                            eprintln!("Couldn't read session: {e}")
+                        })
+                        .ok()
                 })
-                .collect(),
-            config
-                .playlists
-                .pins
+        } else {
+            None
+        };
+
+        Ok(Saved {
+            config,
+            config_stamp,
+            library,
+            start_screen,
+            history,
+            user_data,
+            playlists,
+            session,
+        })
+    }
+}
+
+impl App {
+    pub fn new(options: LaunchOptions) -> (Self, Task<Message>) {
+        Self::init(false, options)
+    }
+
+    /// Creates the app as a remote control for a running daemon, rather than
+    /// playing audio itself.
+    pub fn attach(options: LaunchOptions) -> (Self, Task<Message>) {
+        let (app, task) = Self::init(true, options);
+        (app, Task::done(Message::UpdateProgress).chain(task))
+    }
+
+    fn init(remote: bool, options: LaunchOptions) -> (Self, Task<Message>) {
+        let saved = Saved::load(remote, &options).unwrap_or_else(|e| {
+            eprintln!("Couldn't read config: {e}");
+            std::process::exit(1)
+        });
+        Self::from_saved(remote, options, saved)
+    }
+
+    fn from_saved(
+        remote: bool,
+        options: LaunchOptions,
+        saved: Saved,
+    ) -> (Self, Task<Message>) {
+        let Saved {
+            config,
+            config_stamp,
+            mut library,
+            start_screen,
+            history,
+            user_data,
+            playlists,
+            session,
+        } = saved;
+
+        let (volume, mute) = match config.misc.last_volume {
+            Some(volume) if config.misc.remember_volume => {
+                (volume, config.misc.last_mute)
+            }
+            _ => (config.misc.startup_volume, false),
+        };
+        let volume = volume.clamp(0.0, 1.0);
+        let backend = internal::audio::backend(options.null_output);
+        // an attached app doesn't play anything itself:
+        let (output, notice) = if remote {
+            (None, None)
+        } else {
+            output::open(&*backend, config.output.device.as_deref())
+        };
+        let (output, sink) = output::sink(output);
+        sink.set_volume(match (mute, config.output.bit_perfect) {
+            (true, _) => 0.0,
+            (false, true) => 1.0,
+            (false, false) => internal::audio::volume_gain(volume),
+        });
+
+        let sidebar = sidebar::Sidebar::from_config(&config, &playlists);
+
+        let mut queue = vec![];
+        let mut unshuffled = None;
+        let mut repeat = RepeatStatus::None;
+        let mut radio = false;
+        let mut viewing = Viewing::Library;
+        let mut task = Task::none();
+
+        if let Some(session) = session {
+            queue = session
+                .queue
                 .iter()
-                .map(|path| {
-                    let id = path_hash(path);
-                    (id, playlists.get_playlist(id).unwrap().title.to_owned())
-                })
-                .collect(),
-        );
+                .map(path_hash)
+                .filter(|id| library.get_track(*id).is_some())
+                .collect();
+            unshuffled = session.unshuffled.map(|original| {
+                original
+                    .iter()
+                    .map(path_hash)
+                    .filter(|id| library.get_track(*id).is_some())
+                    .collect()
+            });
+            repeat = session.repeat;
+            radio = session.radio;
+            viewing = match session.viewing {
+                SessionView::History => Viewing::History,
+                SessionView::LibraryRoot => Viewing::Library,
+                SessionView::Settings => Viewing::Settings,
+                SessionView::Library(path) => {
+                    let id = path_hash(&path);
+                    if library.get_directory(id).is_some() {
+                        library.set_current(id);
+                    }
+                    Viewing::Library
+                }
+                SessionView::Playlist(None) => Viewing::Playlist(None),
+                SessionView::Playlist(Some(filename)) => {
+                    let id = path_hash(&PathBuf::from(filename));
+                    if playlists.get_playlist(id).is_some() {
+                        Viewing::Playlist(Some(id))
+                    } else {
+                        Viewing::Playlist(None)
+                    }
+                }
+            };
+            if let Some(playing) = session.playing {
+                let id = path_hash(&playing);
+                if library.get_track(id).is_some() {
+                    task = Task::done(Message::Resume(
+                        id,
+                        Duration::from_secs_f64(session.position.max(0.0)),
+                    ));
+                }
+            }
+        }
+
+        let play = resolve_paths(&library, &options.play);
+        if !play.is_empty() {
+            queue = play;
+            task = Task::done(Message::PlayNext);
+        }
 
-        Self {
+        let mut app = Self {
             codec_registry: symphonia::default::get_codecs(),
             probe: symphonia::default::get_probe(),
             config,
+            config_stamp: Arc::new(Mutex::new(ConfigStamp {
+                stamps:  config_stamp,
+                written: 0,
+            })),
+            config_writes: 0,
             library,
             playlists,
-            viewing: Viewing::Library,
+            user_data,
+            viewing,
+            track_sort: None,
+            search: String::new(),
+            backend: backend.into(),
+            listing_devices: false,
+            output,
+            output_devices: vec![],
             sink,
+            last_position: Duration::ZERO,
+            position: internal::audio::Position::default(),
+            failure: internal::audio::Failure::default(),
+            notice: notice.map(|notice| (notice, std::time::Instant::now())),
             playing: None,
-            queue: vec![],
+            queue,
+            queue_state: queue::QueueState::default(),
+            previous: VecDeque::new(),
+            unshuffled,
             playhead_position: 0.0,
             seeking: false,
             play_status: PlayStatus::Stopped,
-            repeat: RepeatStatus::None,
+            repeat,
+            radio,
             track_duration: None,
-            mute: false,
+            play_started: 0,
+            history,
+            history_period: Period::default(),
+            history_stats: history::Stats::default(),
+            mute,
             volume,
+            #[cfg(target_os = "linux")]
+            mpris: None,
+            remote,
+            settings: settings::Settings::default(),
             sidebar,
             start_screen,
             selecting_playlist: None,
@@ -209,34 +569,520 @@ impl App {
             new_playlist_title: String::new(),
             new_playlist_path: String::new(),
             new_playlist_img: String::new(),
+        };
+        app.refresh_history_stats();
+
+        // the daemon has to be sent the new queue before it's told to play:
+        #[cfg(unix)]
+        if remote && !app.queue.is_empty() {
+            task = app.send_remote_queue().chain(task);
         }
+
+        (app, task)
     }
 
     fn stop(&mut self) {
+        self.record_play(false);
         self.sink.stop();
         self.playing = None;
         self.track_duration = None;
         self.play_status = PlayStatus::Stopped;
     }
 
-    fn write_config(&self) -> Task<Message> {
-        let config = self.config.clone();
+    /// Sets up the sink to play the given track from the beginning, without
+    /// changing whether playback is paused.
+    fn load_track(&mut self, id: u64) {
+        self.record_play(false);
+        self.play_started = history::now();
+        let Some(track) = self.library.get_track(id) else {
+            // it's gone from the library since it was queued, so the empty sink
+            // moves the queue on:
+            eprintln!(
+                "Couldn't play track {id}: it's no longer in the library"
+            );
+            self.playing = None;
+            self.track_duration = None;
+            self.sink.stop();
+            return;
+        };
+        self.playing = Some(track.clone());
+        self.playhead_position = 0.0;
+        self.track_duration = Some((Duration::ZERO, track.metadata.duration));
+        self.sink.stop();
+        // each track may have its own gain offset:
+        self.sink.set_volume(self.output_volume());
+        self.position = internal::audio::Position::default();
+        // the sink's left empty, so the queue moves on:
+        if let Err(e) = self.append_stream(&track.clone()) {
+            self.pass_over(e);
+        }
+    }
+
+    /// Starts decoding `track` into the sink, unless there's no device to play
+    /// it on, in which case it waits for one.
+    fn append_stream(&mut self, track: &Track) -> Result<(), Box<dyn Error>> {
+        if self.output.is_none() {
+            return Ok(());
+        }
+        let bit_perfect = self.config.output.bit_perfect;
+        let stream = internal::audio::AudioStream::new(
+            &track.path,
+            self.codec_registry,
+            self.probe,
+            track.metadata.duration,
+            bit_perfect,
+        )?;
+        if bit_perfect {
+            self.match_output(stream.format());
+        }
+        self.position = stream.position_handle();
+        self.failure = stream.failure_handle();
+        self.sink.append(stream);
+        Ok(())
+    }
+
+    /// Gives up on the current track after it couldn't be played, noting
+    /// which one it was.
+    fn pass_over(&mut self, error: impl std::fmt::Display) {
+        let Some(track) = self.playing.take() else {
+            return;
+        };
+        eprintln!("Couldn't play {}: {error}", track.path.display());
+        self.track_duration = None;
+        let title = match &track.metadata.title {
+            Some(title) => title.clone(),
+            None => track.path.file_name().unwrap().to_string_lossy().into(),
+        };
+        self.notify(format!("Couldn't play {title}"));
+    }
+
+    /// Shows `notice` for a few seconds.
+    fn notify(&mut self, notice: String) {
+        self.notice = Some((notice, std::time::Instant::now()));
+    }
+
+    /// Seeks within the current track, moving the playhead to match.
+    fn seek_to(&mut self, pos: Duration) -> Task<Message> {
+        let Some((current, Some(total))) = &mut self.track_duration else {
+            return Task::none();
+        };
+        if pos >= *total {
+            return Task::none();
+        }
+        *current = pos;
+        self.playhead_position = pos.as_secs_f32() / total.as_secs_f32();
+        #[cfg(unix)]
+        if self.remote {
+            return self.send_remote(crate::ipc::Request::Seek {
+                position: pos.as_secs_f64(),
+            });
+        }
+        if let Err(e) = self.sink.try_seek(pos) {
+            eprintln!("Couldn't seek: {e}");
+            self.notify(String::from("Couldn't seek"));
+        }
+        Task::none()
+    }
+
+    fn set_repeat(&mut self, repeat: RepeatStatus) -> Task<Message> {
+        self.repeat = repeat;
+        #[cfg(unix)]
+        if self.remote {
+            return self.send_remote(crate::ipc::Request::Repeat { repeat });
+        }
+        Task::none()
+    }
+
+    /// Applies the volume and mute setting to the output.
+    fn apply_volume(&self) -> Task<Message> {
+        #[cfg(unix)]
+        if self.remote {
+            let volume = if self.mute { 0.0 } else { self.volume };
+            return self.send_remote(crate::ipc::Request::Volume { volume });
+        }
+        self.sink.set_volume(self.output_volume());
+        Task::none()
+    }
+
+    /// The volume the sink plays at, which is left alone in bit-perfect mode
+    /// so as not to change the samples.
+    fn output_volume(&self) -> f32 {
+        match (self.mute, self.config.output.bit_perfect) {
+            (true, _) => 0.0,
+            (false, true) => 1.0,
+            (false, false) => {
+                internal::audio::volume_gain(self.volume) * self.track_gain()
+            }
+        }
+    }
+
+    /// The gain offset set for the playing track and its album, as an
+    /// amplitude.
+    fn track_gain(&self) -> f32 {
+        self.playing.as_ref().map_or(1.0, |track| {
+            internal::audio::db_to_gain(self.user_data.gain(track))
+        })
+    }
+
+    /// Notes the volume in the config, to start at next time, if it's to be
+    /// remembered and has changed. It's saved along with the session, so
+    /// it's kept even if the app doesn't get to close properly.
+    fn remember_volume(&mut self) -> Task<Message> {
+        let misc = &self.config.misc;
+        if self.remote
+            || !misc.remember_volume
+            || (misc.last_volume == Some(self.volume)
+                && misc.last_mute == self.mute)
+        {
+            return Task::none();
+        }
+        self.config.misc.last_volume = Some(self.volume);
+        self.config.misc.last_mute = self.mute;
+        self.write_config()
+    }
+
+    /// Saves the session and volume, for when the app is about to exit.
+    fn save_before_exit(&mut self) -> Task<Message> {
+        self.write_session_now();
+        let _ = self
+            .history
+            .flush()
+            .inspect_err(|e| eprintln!("Couldn't write play history: {e}"));
+        self.remember_volume()
+    }
+
+    /// Adds the current track, if any, to the play history. `finished` should
+    /// be set if the track played through to the end rather than being skipped
+    /// or stopped.
+    fn record_play(&mut self, finished: bool) {
+        let Some(playing) = &self.playing else {
+            return;
+        };
+        let (listened, completion) = match self.track_duration {
+            Some((_, Some(total))) if finished => (total, 1.0),
+            Some((current, Some(total))) if !total.is_zero() => (
+                current,
+                (current.as_secs_f32() / total.as_secs_f32()).clamp(0.0, 1.0),
+            ),
+            Some((current, Some(_))) => (current, 1.0),
+            // without the length, all that's known is whether it finished:
+            Some((current, None)) => {
+                (current, if finished { 1.0 } else { 0.0 })
+            }
+            None => return,
+        };
+        let record = PlayRecord {
+            track: track_hash(playing),
+            started: self.play_started,
+            listened,
+            completion,
+        };
+        let _ = self
+            .history
+            .append(record)
+            .inspect_err(|e| eprintln!("Couldn't write play history: {e}"));
+        self.refresh_history_stats();
+    }
+
+    /// Works out the history view's statistics again, if it's open. They're
+    /// worked out afresh whenever it's opened.
+    fn refresh_history_stats(&mut self) {
+        if matches!(self.viewing, Viewing::History) {
+            self.history_stats =
+                self.history.stats(&self.library, self.history_period);
+        }
+    }
+
+    /// Remembers a track that's stopped playing, so Skip Back can return to
+    /// it.
+    fn push_previous(&mut self, id: u64) {
+        if self.previous.len() == MAX_PREVIOUS {
+            self.previous.pop_front();
+        }
+        self.previous.push_back(id);
+    }
+
+    /// In radio mode, queues tracks related to `seed` if the queue has run
+    /// out.
+    fn continue_radio(&mut self, seed: u64) {
+        if !self.radio || !self.queue.is_empty() {
+            return;
+        }
+        let tracks = radio::pick(
+            &self.library,
+            seed,
+            &self.config.radio.relations,
+            |id| self.previous.contains(&id),
+        );
+        if let Some(original) = &mut self.unshuffled {
+            original.extend_from_slice(&tracks);
+        }
+        self.queue.extend(tracks);
+    }
+
+    fn session(&self) -> Session {
+        Session {
+            queue: self
+                .queue
+                .iter()
+                .filter_map(|id| self.library.get_track(*id))
+                .map(|track| track.path.clone())
+                .collect(),
+            playing: self.playing.as_ref().map(|track| track.path.clone()),
+            position: self
+                .track_duration
+                .map_or(0.0, |(current, _)| current.as_secs_f64()),
+            unshuffled: self.unshuffled.as_ref().map(|original| {
+                original
+                    .iter()
+                    .filter_map(|id| self.library.get_track(*id))
+                    .map(|track| track.path.clone())
+                    .collect()
+            }),
+            repeat: self.repeat,
+            radio: self.radio,
+            viewing: match self.viewing {
+                Viewing::History => SessionView::History,
+                Viewing::Library => {
+                    if self.library.curr_dir == self.library.root_dir {
+                        SessionView::LibraryRoot
+                    } else {
+                        SessionView::Library(
+                            self.library.current_directory().path.clone(),
+                        )
+                    }
+                }
+                Viewing::Playlist(None) => SessionView::Playlist(None),
+                Viewing::Playlist(Some(id)) => SessionView::Playlist(
+                    self.playlists
+                        .get_playlist(id)
+                        .map(|pl| pl.filename.clone()),
+                ),
+                Viewing::Settings => SessionView::Settings,
+            },
+        }
+    }
+
+    fn write_session(&self) -> Task<Message> {
+        if self.start_screen.is_some() || self.remote {
+            return Task::none();
+        }
+        let session = self.session();
+        Task::future(tokio::spawn(async move {
+            let _ = Session::file_path()
+                .and_then(|path| session.write_to_file(&path))
+                .inspect_err(|e| eprintln!("Couldn't save session: {e}"));
+        }))
+        .map(|_| Message::None)
+    }
+
+    /// Writes the session synchronously, for when the app is about to exit.
+    fn write_session_now(&self) {
+        if self.start_screen.is_none() && !self.remote {
+            let _ = Session::file_path()
+                .and_then(|path| self.session().write_to_file(&path))
+                .inspect_err(|e| eprintln!("Couldn't save session: {e}"));
+        }
+    }
+
+    fn write_user_data(&self) -> Task<Message> {
+        let user_data = self.user_data.clone();
         Task::future(tokio::spawn(async move {
-            config.write_to_file(&Config::file_path().unwrap());
+            let _ = user_data
+                .write_to_file()
+                .inspect_err(|e| eprintln!("Couldn't save user data: {e}"));
         }))
         .map(|_| Message::None)
     }
 
+    /// Swaps in a newly scanned library, dropping anything that's no longer in
+    /// it from the queue, the tracks Skip Back returns to, and the pins.
+    fn replace_library(&mut self, library: Library) -> Task<Message> {
+        let curr = self.library.curr_dir;
+        self.library = library;
+        if self.library.get_directory(curr).is_some() {
+            self.library.set_current(curr);
+        }
+        let library = &self.library;
+        self.queue.retain(|id| library.get_track(*id).is_some());
+        self.previous.retain(|id| library.get_track(*id).is_some());
+        if let Some(original) = &mut self.unshuffled {
+            original.retain(|id| library.get_track(*id).is_some());
+        }
+        if self
+            .selecting_playlist
+            .is_some_and(|id| library.get_track(id).is_none())
+        {
+            self.selecting_playlist = None;
+        }
+        self.refresh_history_stats();
+        let _ = self
+            .library
+            .write_to_file()
+            .inspect_err(|e| eprintln!("Problem caching library data: {e}"));
+        self.config = self.config.clone().verify_pins(&self.library);
+        self.sidebar =
+            sidebar::Sidebar::from_config(&self.config, &self.playlists);
+        self.write_config()
+    }
+
+    /// Saves the config, unless another program has changed it since it was
+    /// read, in which case their version is loaded instead.
+    fn write_config(&mut self) -> Task<Message> {
+        let contents = match self.config.serialize() {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Couldn't write config: {e}");
+                return Task::none();
+            }
+        };
+        self.config_writes += 1;
+        let number = self.config_writes;
+        let stamp = self.config_stamp.clone();
+        let write = move || {
+            let mut stamp = stamp.lock().unwrap();
+            if stamp.written > number {
+                return Ok(());
+            }
+            stamp.written = number;
+            Config::file_path()
+                .and_then(|path| stamp.stamps.write(&path, &contents))
+                .map_err(|e| e.to_string())
+        };
+        Task::perform(
+            async { tokio::task::spawn_blocking(write).await },
+            |result| match result {
+                Ok(Ok(())) => Message::None,
+                Ok(Err(e)) => {
+                    eprintln!("Couldn't write config: {e}");
+                    Message::ConfigChanged
+                }
+                Err(e) => {
+                    eprintln!("Couldn't write config: {e}");
+                    Message::None
+                }
+            },
+        )
+    }
+
+    /// Saves a playlist, unless another program has changed it since it was
+    /// read, in which case their version is loaded instead.
+    fn write_playlist(&mut self, id: u64) -> Task<Message> {
+        match self.playlists.write_playlist(id) {
+            Ok(()) => Task::none(),
+            Err(e) => {
+                eprintln!("Couldn't write playlist: {e}");
+                Task::done(Message::PlaylistsChanged)
+            }
+        }
+    }
+
+    /// Loads the config if another program has changed it, rescanning the
+    /// library if its folders are different.
+    fn reload_config(&mut self) -> Task<Message> {
+        let Ok(path) = Config::file_path() else {
+            return Task::none();
+        };
+        if !self.config_stamp.lock().unwrap().stamps.changed(&path) {
+            return Task::none();
+        }
+        let s = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            // it's written again the next time anything is changed here:
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.config_stamp.lock().unwrap().stamps.forget(&path);
+                return Task::none();
+            }
+            Err(e) => {
+                eprintln!("Couldn't reload config: {e}");
+                return Task::none();
+            }
+        };
+        // a half-written or mistyped config is left alone, and isn't
+        // overwritten either, since it still differs from the recorded one:
+        let mut config = match Config::parse(&s) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Couldn't reload config: {e}");
+                return Task::none();
+            }
+        };
+        self.config_stamp
+            .lock()
+            .unwrap()
+            .stamps
+            .record(&path, s.as_bytes());
+        // a `--library` override lasts for the whole run:
+        config.library.session_root = self.config.library.session_root.clone();
+        let rescan = config.library.enabled_roots()
+            != self.config.library.enabled_roots();
+        let switch = config.output.device != self.config.output.device
+            || config.output.bit_perfect != self.config.output.bit_perfect;
+        self.config = config.verify_pins(&self.library);
+        if switch && !self.remote {
+            self.switch_output(self.config.output.device.clone().as_deref());
+        }
+        self.sidebar =
+            sidebar::Sidebar::from_config(&self.config, &self.playlists);
+        if rescan && self.start_screen.is_none() {
+            self.scan_library(false)
+        } else {
+            Task::none()
+        }
+    }
+
+    /// Loads the playlists again if another program has changed any of them.
+    fn reload_playlists(&mut self) -> Task<Message> {
+        match self.playlists.reload() {
+            Ok(true) => (),
+            Ok(false) => return Task::none(),
+            Err(e) => eprintln!("Couldn't reload playlists: {e}"),
+        }
+        if let Viewing::Playlist(Some(id)) = self.viewing
+            && self.playlists.get_playlist(id).is_none()
+        {
+            self.viewing = Viewing::Playlist(None);
+        }
+        if self.playlists.playlists().next().is_none() {
+            self.selecting_playlist = None;
+        }
+        let pins = self.config.playlists.pins.len();
+        self.config = self.config.clone().verify_pins(&self.library);
+        self.sidebar =
+            sidebar::Sidebar::from_config(&self.config, &self.playlists);
+        if self.config.playlists.pins.len() != pins {
+            self.write_config()
+        } else {
+            Task::none()
+        }
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
+        #[cfg(unix)]
+        let message = if self.remote {
+            match self.update_remote(message) {
+                Ok(task) => return task,
+                Err(message) => message,
+            }
+        } else {
+            message
+        };
+
         match message {
             Message::CancelCreatePlaylist => {
                 self.new_playlist_menu = false;
                 Task::none()
             }
+            Message::CheckOutput => self.check_output(),
             Message::CloseAddToPlaylist => {
                 self.selecting_playlist = None;
                 Task::none()
             }
+            Message::CloseRequested(id) => self
+                .save_before_exit()
+                .chain(iced::window::close(id))
+                .chain(iced::exit()),
+            Message::ConfigChanged => self.reload_config(),
             Message::CreatePlaylist => {
                 let img = {
                     let path = PathBuf::from(&self.new_playlist_img);
@@ -248,10 +1094,9 @@ impl App {
                     img,
                     vec![],
                 );
-                pl.write_to_file();
-                self.playlists.scan_playlists();
+                let id = self.playlists.add_playlist(pl);
                 self.new_playlist_menu = false;
-                Task::none()
+                self.write_playlist(id)
             }
             Message::DeletePlaylist(id) => {
                 let Some(pl) = self.playlists.remove_playlist(id) else {
@@ -267,8 +1112,22 @@ impl App {
                     .filter(|p| p.to_str().unwrap() != pl.filename)
                     .map(|p| p.to_owned())
                     .collect();
+                if let Viewing::Playlist(Some(viewing)) = self.viewing
+                    && viewing == id
+                {
+                    self.viewing = Viewing::Playlist(None);
+                }
+                self.sidebar = sidebar::Sidebar::from_config(
+                    &self.config,
+                    &self.playlists,
+                );
                 self.write_config()
             }
+            Message::HistoryPeriod(period) => {
+                self.history_period = period;
+                self.refresh_history_stats();
+                Task::none()
+            }
             Message::ImgPathChanged(s) => {
                 self.new_playlist_img = s;
                 Task::none()
@@ -280,6 +1139,16 @@ impl App {
                 }
                 Task::none()
             }
+            Message::KeyPressed(key, modifiers) => {
+                match self.config.keys.action(&key, modifiers) {
+                    Some(action) if self.start_screen.is_none() => {
+                        self.key_action(action)
+                    }
+                    _ => Task::none(),
+                }
+            }
+            #[cfg(target_os = "linux")]
+            Message::Mpris(event) => self.update_mpris(event),
             Message::None => Task::none(),
             Message::OpenImgDialog => Task::perform(
                 rfd::AsyncFileDialog::new()
@@ -294,6 +1163,11 @@ impl App {
                 self.new_playlist_menu = true;
                 Task::none()
             }
+            Message::OutputDevicesListed(names) => {
+                self.listing_devices = false;
+                self.return_to_chosen_output(&names);
+                Task::none()
+            }
             Message::PinAdd(kind, path) => match kind {
                 PinKind::Library => {
                     self.config.library.pins.push(path.clone());
@@ -310,16 +1184,15 @@ impl App {
                     )
                 }
                 PinKind::Playlist => {
-                    self.config.playlists.pins.push(path.clone());
                     let id = path_hash(&path);
+                    let Some(pl) = self.playlists.get_playlist(id) else {
+                        return Task::none();
+                    };
+                    self.config.playlists.pins.push(path);
                     Task::done(
                         sidebar::SidebarMessage::PlaylistAppend(
-                            path_hash(&path),
-                            self.playlists
-                                .get_playlist(id)
-                                .unwrap()
-                                .title
-                                .clone(),
+                            id,
+                            pl.title.clone(),
                         )
                         .into(),
                     )
@@ -329,42 +1202,46 @@ impl App {
                 self.new_playlist_path = s;
                 Task::none()
             }
-            Message::PlaylistRemove(index) => unsafe {
+            Message::PlaylistsChanged => self.reload_playlists(),
+            Message::PlaylistRemove(index) => {
                 let Viewing::Playlist(Some(id)) = self.viewing else {
                     return Task::none();
                 };
-                let pl = self.playlists.get_playlist_mut(id).unwrap_unchecked();
-                pl.tracks.remove(index);
-                pl.write_to_file();
-                Task::none()
-            },
-            Message::PlaylistSelected(pl_id) => unsafe {
-                let track_id = self.selecting_playlist.unwrap_unchecked();
-                let pl =
-                    self.playlists.get_playlist_mut(pl_id).unwrap_unchecked();
-                pl.tracks.push(PlaylistTrack::Track(
-                    track_id,
-                    self.library
-                        .get_track(track_id)
-                        .unwrap_unchecked()
-                        .path
-                        .clone(),
-                ));
-                self.selecting_playlist = None;
-                pl.write_to_file();
-                Task::none()
-            },
-            Message::PlaylistSwap(a, b) => unsafe {
+                let Some(pl) = self.playlists.get_playlist_mut(id) else {
+                    return Task::none();
+                };
+                if index < pl.tracks.len() {
+                    pl.tracks.remove(index);
+                }
+                self.write_playlist(id)
+            }
+            Message::PlaylistSelected(pl_id) => {
+                // the track or playlist may have gone since the menu opened:
+                let Some(track_id) = self.selecting_playlist.take() else {
+                    return Task::none();
+                };
+                let Some(track) = self.library.get_track(track_id) else {
+                    return Task::none();
+                };
+                let Some(pl) = self.playlists.get_playlist_mut(pl_id) else {
+                    return Task::none();
+                };
+                pl.tracks
+                    .push(PlaylistTrack::Track(track_id, track.path.clone()));
+                self.write_playlist(pl_id)
+            }
+            Message::PlaylistSwap(a, b) => {
                 let Viewing::Playlist(Some(id)) = self.viewing else {
                     return Task::none();
                 };
-                let pl = self.playlists.get_playlist_mut(id).unwrap_unchecked();
-                if b < pl.tracks.len() {
+                let Some(pl) = self.playlists.get_playlist_mut(id) else {
+                    return Task::none();
+                };
+                if a < pl.tracks.len() && b < pl.tracks.len() {
                     pl.tracks.swap(a, b);
                 }
-                pl.write_to_file();
-                Task::none()
-            },
+                self.write_playlist(id)
+            }
             Message::PlaylistTitleChanged(s) => {
                 self.new_playlist_title = s;
                 Task::none()
@@ -372,66 +1249,117 @@ impl App {
             Message::PlayheadMoved(val) => {
                 self.playhead_position = val;
                 self.seeking = true;
-
-                let Some(_playing) = &self.playing else {
-                    return Task::none();
-                };
-
-                let Some((_, duration)) = &self.track_duration else {
+                let Some((_, Some(duration))) = self.track_duration else {
                     return Task::none();
                 };
-
-                let seek_pos =
-                    Duration::from_secs((val * duration.as_secs_f32()) as u64);
-                self.sink.try_seek(seek_pos);
-                Task::none()
+                self.seek_to(duration.mul_f64(val as f64))
             }
             Message::PlayheadReleased => {
                 self.seeking = false;
                 Task::none()
             }
             Message::PlayNext => {
+                if let Some(playing) = &self.playing {
+                    self.continue_radio(track_hash(playing));
+                }
                 if self.queue.is_empty() {
                     return Task::none();
                 }
+                if let Some(playing) = &self.playing {
+                    let id = track_hash(playing);
+                    self.push_previous(id);
+                }
                 let track = self.queue.remove(0);
-                let track = self.library.get_track(track).unwrap();
-                self.playing = Some(track.clone());
-                self.playhead_position = 0.0;
-                self.track_duration = track
-                    .metadata
-                    .duration
-                    .as_ref()
-                    .map(|total| (Duration::from_secs(0), *total));
-                self.sink.stop();
-                self.sink.append(internal::audio::AudioStream::new(
-                    &track.path,
-                    self.codec_registry,
-                    self.probe,
-                    track.metadata.duration.unwrap(),
-                ));
+                self.queue_state.clear();
+                self.load_track(track);
                 self.sink.play();
                 self.play_status = PlayStatus::Play;
                 Task::none()
             }
             Message::Queue(msg) => self.update_queue(msg),
-            Message::ScanDone => unsafe {
-                let start = self.start_screen.take().unwrap_unchecked();
-                self.library = start.lib.unwrap_unchecked();
-                let _ = self.library.write_to_file().inspect_err(|e| {
-                    eprintln!("Problem caching library data: {e}")
-                });
-                self.config.library.path = start.path.into();
-                self.write_config()
-            },
+            // only sent while attached to a daemon:
+            #[cfg(unix)]
+            Message::RemoteStatus(_) => Task::none(),
+            Message::Resume(id, pos) => {
+                self.load_track(id);
+                self.sink.pause();
+                self.play_status = PlayStatus::Pause;
+                self.seek_to(pos)
+            }
+            Message::SaveSession => {
+                Task::batch([self.write_session(), self.remember_volume()])
+            }
+            Message::ScanDone => {
+                let Some(start) = self.start_screen.take() else {
+                    return Task::none();
+                };
+                let Some(lib) = start.lib else {
+                    return Task::none();
+                };
+                self.config
+                    .library
+                    .replace_root(start.replacing, start.path.into());
+                // only the chosen folder was scanned, so any others are
+                // scanned in alongside it:
+                if self.config.library.enabled_roots().len() > 1 {
+                    Task::batch([self.write_config(), self.scan_library(false)])
+                } else {
+                    self.replace_library(lib)
+                }
+            }
+            Message::SearchChanged(s) => {
+                self.search = s;
+                Task::none()
+            }
             Message::SelectPlaylist(track_id) => {
                 self.selecting_playlist = Some(track_id);
                 Task::none()
             }
+            Message::SetGain(target, gain) => {
+                self.user_data.set_gain(target, gain);
+                Task::batch([self.apply_volume(), self.write_user_data()])
+            }
+            Message::SetRating(id, rating) => {
+                let rating = rating.min(5);
+                self.user_data.track_mut(id).rating = rating;
+                let write_tags = if self.config.ratings.write_to_files
+                    && let Some(track) = self.library.get_track(id)
+                {
+                    let track = track.clone();
+                    Task::future(tokio::task::spawn_blocking(move || {
+                        let _ = internal::tags::write_rating(&track, rating)
+                            .inspect_err(|e| {
+                                eprintln!(
+                                    "Couldn't write rating to {}: {e}",
+                                    track.path.display()
+                                )
+                            });
+                    }))
+                    .map(|_| Message::None)
+                } else {
+                    Task::none()
+                };
+                Task::batch([self.write_user_data(), write_tags])
+            }
+            Message::Settings(msg) => self.update_settings(msg),
             Message::SidebarMessage(msg) => {
                 self.sidebar.update(msg, &mut self.config);
                 self.write_config()
             }
+            Message::SortTracks(column) => {
+                self.track_sort = match self.track_sort {
+                    Some((current, false)) if current == column => {
+                        Some((column, true))
+                    }
+                    Some((current, true)) if current == column => None,
+                    _ => Some((column, false)),
+                };
+                Task::none()
+            }
+            Message::StartScreen(start_screen::Message::Cancel) => {
+                self.start_screen = None;
+                Task::none()
+            }
             Message::StartScreen(msg) => {
                 if let Some(start) = &mut self.start_screen {
                     if start.lib.is_some() {
@@ -443,14 +1371,14 @@ impl App {
                     Task::none()
                 }
             }
+            Message::ToggleFavorite(id) => {
+                let data = self.user_data.track_mut(id);
+                data.favorite = !data.favorite;
+                self.write_user_data()
+            }
             Message::ToggleMute => {
                 self.mute = !self.mute;
-                if self.mute {
-                    self.sink.set_volume(0.0);
-                } else {
-                    self.sink.set_volume(self.volume);
-                }
-                Task::none()
+                self.apply_volume()
             }
             Message::TogglePlay => {
                 self.play_status = match self.play_status {
@@ -465,20 +1393,42 @@ impl App {
                 };
                 Task::none()
             }
-            Message::ToggleRepeat => {
-                self.repeat = match self.repeat {
-                    RepeatStatus::None => RepeatStatus::One,
-                    RepeatStatus::One => RepeatStatus::All,
-                    RepeatStatus::All => RepeatStatus::None,
-                };
+            Message::ToggleRadio => {
+                self.radio = !self.radio;
+                if let Some(playing) = &self.playing {
+                    self.continue_radio(track_hash(playing));
+                }
                 Task::none()
             }
-            Message::UpdateProgress => self.update_progress(),
+            Message::ToggleRepeat => self.set_repeat(match self.repeat {
+                RepeatStatus::None => RepeatStatus::One,
+                RepeatStatus::One => RepeatStatus::All,
+                RepeatStatus::All => RepeatStatus::None,
+            }),
+            Message::UpdateProgress => {
+                let task = self.update_progress();
+                #[cfg(target_os = "linux")]
+                self.sync_mpris();
+                task
+            }
+            Message::ViewHistory => {
+                use iced::widget::scrollable;
+
+                self.viewing = Viewing::History;
+                self.refresh_history_stats();
+                self.new_playlist_menu = false;
+                self.selecting_playlist = None;
+                scrollable::scroll_to(
+                    scrollable::Id::new("history"),
+                    scrollable::AbsoluteOffset { x: 0.0, y: 0.0 },
+                )
+            }
             Message::ViewLibrary(id) => {
                 use iced::widget::scrollable;
 
                 self.library.set_current(id);
                 self.viewing = Viewing::Library;
+                self.search.clear();
                 self.new_playlist_menu = false;
                 self.selecting_playlist = None;
                 scrollable::scroll_to(
@@ -493,6 +1443,7 @@ impl App {
                 use iced::widget::scrollable;
 
                 self.viewing = Viewing::Playlist(val);
+                self.search.clear();
                 self.new_playlist_menu = false;
                 self.selecting_playlist = None;
                 if val.is_some() {
@@ -507,16 +1458,108 @@ impl App {
                     )
                 }
             }
+            Message::ViewSettings => {
+                use iced::widget::scrollable;
+
+                self.viewing = Viewing::Settings;
+                self.new_playlist_menu = false;
+                self.selecting_playlist = None;
+                Task::batch([
+                    self.list_devices(|names| {
+                        settings::SettingsMessage::DevicesListed(names).into()
+                    }),
+                    scrollable::scroll_to(
+                        scrollable::Id::new("settings"),
+                        scrollable::AbsoluteOffset { x: 0.0, y: 0.0 },
+                    ),
+                ])
+            }
             Message::VolumeChanged(val) => {
                 self.volume = val;
-                self.sink.set_volume(val);
-                Task::none()
+                self.mute = false;
+                self.apply_volume()
+            }
+            Message::VolumeScrolled(lines) => {
+                const SCROLL_STEP: f32 = 0.02;
+                self.step_volume(lines * SCROLL_STEP)
             }
         }
     }
 
+    /// Turns the volume up, or down if `step` is negative, as a fraction of
+    /// the slider.
+    fn step_volume(&self, step: f32) -> Task<Message> {
+        Task::done(Message::VolumeChanged((self.volume + step).clamp(0.0, 1.0)))
+    }
+
+    fn key_action(&mut self, action: keys::Action) -> Task<Message> {
+        use keys::Action;
+
+        const SEEK_STEP: Duration = Duration::from_secs(5);
+        const VOLUME_STEP: f32 = 0.05;
+        const FINE_VOLUME_STEP: f32 = 0.01;
+
+        match action {
+            Action::PlayPause => Task::done(Message::TogglePlay),
+            Action::SeekForward | Action::SeekBack => {
+                let Some((current, _)) = self.track_duration else {
+                    return Task::none();
+                };
+                self.seek_to(if action == Action::SeekForward {
+                    current + SEEK_STEP
+                } else {
+                    current.saturating_sub(SEEK_STEP)
+                })
+            }
+            Action::VolumeUp => self.step_volume(VOLUME_STEP),
+            Action::VolumeDown => self.step_volume(-VOLUME_STEP),
+            Action::VolumeUpFine => self.step_volume(FINE_VOLUME_STEP),
+            Action::VolumeDownFine => self.step_volume(-FINE_VOLUME_STEP),
+            Action::Next => Task::done(queue::QueueMessage::SkipForward.into()),
+            Action::Previous => {
+                Task::done(queue::QueueMessage::SkipBack.into())
+            }
+            Action::Search => {
+                use iced::widget::text_input;
+
+                // only the library and playlists can be searched:
+                if !matches!(
+                    self.viewing,
+                    Viewing::Library | Viewing::Playlist(Some(_))
+                ) {
+                    self.viewing = Viewing::Library;
+                }
+                text_input::focus(text_input::Id::new("search"))
+            }
+            Action::RemoveFromQueue if self.queue_state.has_selection() => {
+                Task::done(queue::QueueMessage::RemoveSelected.into())
+            }
+            Action::RemoveFromQueue if !self.queue.is_empty() => {
+                Task::done(queue::QueueMessage::Remove(0).into())
+            }
+            Action::RemoveFromQueue => Task::none(),
+        }
+    }
+
     fn update_progress(&mut self) -> Task<Message> {
+        if self
+            .notice
+            .as_ref()
+            .is_some_and(|(_, at)| at.elapsed() > NOTICE_TIME)
+        {
+            self.notice = None;
+        }
+        // playback's held until there's somewhere to play:
+        if self.output.is_none() {
+            return Task::none();
+        }
         if self.sink.empty() {
+            // a track which broke off isn't repeated or counted as finished:
+            if let Some(e) = self.failure.take() {
+                self.record_play(false);
+                self.pass_over(e);
+            }
+            self.record_play(true);
             if let Some(playing) = &self.playing {
                 let last = track_hash(playing);
                 match self.repeat {
@@ -524,6 +1567,10 @@ impl App {
                     RepeatStatus::All => self.queue.push(last),
                     RepeatStatus::None => (),
                 }
+                if self.repeat != RepeatStatus::One {
+                    self.push_previous(last);
+                }
+                self.continue_radio(last);
             }
             self.playing = None;
             if !self.queue.is_empty() {
@@ -537,10 +1584,13 @@ impl App {
             let Some(playing) = &self.playing else {
                 return Task::none(); // prevent race conditions
             };
-            let sink_pos = self.sink.get_pos();
-            let duration = playing.metadata.duration.unwrap();
+            let sink_pos = self.position.get();
+            let duration = playing.metadata.duration;
             self.track_duration = Some((sink_pos, duration));
-            if !self.seeking {
+            // a track of unknown length has no playhead to move:
+            if let Some(duration) = duration
+                && !self.seeking
+            {
                 self.playhead_position =
                     sink_pos.as_secs_f32() / duration.as_secs_f32();
             }
@@ -548,8 +1598,74 @@ impl App {
         }
     }
 
+    pub fn subscription(&self) -> iced::Subscription<Message> {
+        iced::Subscription::batch([
+            self.progress_subscription(),
+            iced::time::every(Duration::from_secs(30))
+                .map(|_| Message::SaveSession),
+            iced::window::close_requests().map(Message::CloseRequested),
+            watch::subscription(),
+            keys::subscription(),
+            queue::subscription(),
+            #[cfg(target_os = "linux")]
+            mpris::subscription(),
+            #[cfg(unix)]
+            if self.remote {
+                iced::Subscription::none()
+            } else {
+                crate::instance::subscription()
+            },
+            if self.remote {
+                iced::Subscription::none()
+            } else {
+                iced::time::every(output::CHECK_INTERVAL)
+                    .map(|_| Message::CheckOutput)
+            },
+        ])
+    }
+
     pub fn progress_subscription(&self) -> iced::Subscription<Message> {
+        #[cfg(unix)]
+        if self.remote {
+            return iced::time::every(remote::POLL_INTERVAL)
+                .map(|_| Message::UpdateProgress);
+        }
         iced::time::every(Duration::from_millis(10))
             .map(|_| Message::UpdateProgress)
     }
 }
+
+/// Loads the library from its cache with a partial rescan of `roots`, or scans
+/// them from scratch if there's no usable cache or `full_rescan` is set.
+pub fn load_library(roots: &[PathBuf], full_rescan: bool) -> Library {
+    let cached = Library::file_path()
+        .ok()
+        .and_then(|cache| Library::from_file(&cache).ok());
+    match cached {
+        Some(lib) if !full_rescan => internal::partial_scan(roots, lib),
+        _ => internal::scan(roots),
+    }
+}
+
+/// Finds the tracks at or under the given paths in the library, in order,
+/// reporting any paths that aren't in the library.
+pub fn resolve_paths(library: &Library, paths: &[PathBuf]) -> Vec<u64> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            let tracks = std::path::absolute(path)
+                .ok()
+                .and_then(|path| library.tracks_under(&path))
+                .or_else(|| {
+                    path.canonicalize()
+                        .ok()
+                        .and_then(|path| library.tracks_under(&path))
+                });
+            if tracks.is_none() {
+                eprintln!("{} isn't in the library", path.display());
+            }
+            tracks
+        })
+        .flatten()
+        .collect()
+}
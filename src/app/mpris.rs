@@ -0,0 +1,721 @@
+//! An `org.mpris.MediaPlayer2` D-Bus service, so that desktop media keys,
+//! lock-screen widgets and tools like `playerctl` can control the player.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use iced::futures::SinkExt;
+use tokio::sync::mpsc;
+use zbus::{
+    Connection, interface, object_server::SignalEmitter, zvariant::Value,
+};
+
+use super::*;
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.polypody";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const TRACK_PATH: &str = "/org/polypody/track";
+const NO_TRACK_PATH: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// How far the position may drift from where it's expected to be before it's
+/// treated as a seek.
+const SEEK_TOLERANCE: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    Ready(Mpris),
+    Next,
+    Pause,
+    Play,
+    PlayPause,
+    Previous,
+    Quit,
+    Raise,
+    /// Offset from the current position, in microseconds.
+    Seek(i64),
+    /// Position in microseconds, for the track with the given hash.
+    SetPosition(u64, i64),
+    SetRepeat(RepeatStatus),
+    SetShuffle(bool),
+    SetVolume(f64),
+    Stop,
+}
+
+impl From<Event> for Message {
+    fn from(value: Event) -> Message {
+        Message::Mpris(value)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TrackInfo {
+    pub id: u64,
+    pub title: Option<String>,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub discnum: Option<usize>,
+    pub num: Option<usize>,
+    pub length: Option<Duration>,
+    pub path: PathBuf,
+    pub art: Option<PathBuf>,
+}
+
+/// The parts of the player's state which are signaled when they change.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerState {
+    pub status: PlayStatus,
+    pub repeat: RepeatStatus,
+    pub shuffle: bool,
+    pub volume: f64,
+    pub track: Option<TrackInfo>,
+    pub can_go_next: bool,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: PlayerState,
+    position: Duration,
+    position_updated: Instant,
+}
+
+/// Handle to the running service, used to keep it up to date with the player.
+#[derive(Clone, Debug)]
+pub struct Mpris {
+    conn:   Connection,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Mpris {
+    /// Updates the state reported over D-Bus, signaling any properties that
+    /// changed.
+    pub fn update(&self, state: PlayerState, position: Duration) {
+        let mut shared = self.shared.lock().unwrap();
+
+        let track_changed = shared.state.track != state.track;
+        let expected = if shared.state.status == PlayStatus::Play {
+            shared.position + shared.position_updated.elapsed()
+        } else {
+            shared.position
+        };
+        let seeked =
+            !track_changed && expected.abs_diff(position) > SEEK_TOLERANCE;
+        shared.position = position;
+        shared.position_updated = Instant::now();
+
+        if shared.state == state && !seeked {
+            return;
+        }
+        let old = std::mem::replace(&mut shared.state, state.clone());
+        drop(shared);
+
+        let conn = self.conn.clone();
+        tokio::spawn(async move {
+            let Ok(iface) = conn
+                .object_server()
+                .interface::<_, Player>(OBJECT_PATH)
+                .await
+            else {
+                return;
+            };
+            let emitter = iface.signal_emitter();
+            let player = iface.get().await;
+            let _ = async {
+                if old.status != state.status {
+                    player.playback_status_changed(emitter).await?;
+                }
+                if old.repeat != state.repeat {
+                    player.loop_status_changed(emitter).await?;
+                }
+                if old.shuffle != state.shuffle {
+                    player.shuffle_changed(emitter).await?;
+                }
+                if old.volume != state.volume {
+                    player.volume_changed(emitter).await?;
+                }
+                if old.track != state.track {
+                    player.metadata_changed(emitter).await?;
+                    player.can_play_changed(emitter).await?;
+                    player.can_seek_changed(emitter).await?;
+                }
+                if old.can_go_next != state.can_go_next {
+                    player.can_go_next_changed(emitter).await?;
+                }
+                if seeked {
+                    Player::seeked(emitter, position.as_micros() as i64)
+                        .await?;
+                }
+                zbus::Result::Ok(())
+            }
+            .await
+            .inspect_err(|e| eprintln!("Couldn't send MPRIS signal: {e}"));
+        });
+    }
+}
+
+/// Starts the service, and produces a message for every request made to it.
+pub fn subscription() -> iced::Subscription<Message> {
+    iced::Subscription::run(|| {
+        iced::stream::channel(16, |mut output| async move {
+            let (tcx, mut rcx) = mpsc::channel(16);
+            let shared = Arc::new(Mutex::new(Shared {
+                state: PlayerState::default(),
+                position: Duration::ZERO,
+                position_updated: Instant::now(),
+            }));
+            match serve(tcx, shared.clone()).await {
+                Ok(conn) => {
+                    let _ = output
+                        .send(Event::Ready(Mpris { conn, shared }).into())
+                        .await;
+                    while let Some(event) = rcx.recv().await {
+                        if output.send(event.into()).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Couldn't start MPRIS service: {e}"),
+            }
+        })
+    })
+}
+
+async fn serve(
+    tcx: mpsc::Sender<Event>,
+    shared: Arc<Mutex<Shared>>,
+) -> zbus::Result<Connection> {
+    zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Root { tcx: tcx.clone() })?
+        .serve_at(OBJECT_PATH, Player { tcx, shared })?
+        .build()
+        .await
+}
+
+struct Root {
+    tcx: mpsc::Sender<Event>,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    async fn raise(&self) {
+        let _ = self.tcx.send(Event::Raise).await;
+    }
+
+    async fn quit(&self) {
+        let _ = self.tcx.send(Event::Quit).await;
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        "Polypody"
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> &str {
+        "polypody"
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+struct Player {
+    tcx:    mpsc::Sender<Event>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Player {
+    fn state(&self) -> PlayerState {
+        self.shared.lock().unwrap().state.clone()
+    }
+
+    async fn send(&self, event: Event) {
+        let _ = self.tcx.send(event).await;
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    async fn next(&self) {
+        self.send(Event::Next).await;
+    }
+
+    async fn previous(&self) {
+        self.send(Event::Previous).await;
+    }
+
+    async fn pause(&self) {
+        self.send(Event::Pause).await;
+    }
+
+    async fn play_pause(&self) {
+        self.send(Event::PlayPause).await;
+    }
+
+    async fn stop(&self) {
+        self.send(Event::Stop).await;
+    }
+
+    async fn play(&self) {
+        self.send(Event::Play).await;
+    }
+
+    async fn seek(&self, offset: i64) {
+        self.send(Event::Seek(offset)).await;
+    }
+
+    async fn set_position(
+        &self,
+        track_id: zbus::zvariant::ObjectPath<'_>,
+        position: i64,
+    ) {
+        let Some(track) = self.state().track else {
+            return;
+        };
+        // requests for any track but the current one are to be ignored:
+        if track_id.as_str() == track_path(track.id) {
+            self.send(Event::SetPosition(track.id, position)).await;
+        }
+    }
+
+    async fn open_uri(&self, _uri: &str) -> zbus::fdo::Result<()> {
+        Err(zbus::fdo::Error::NotSupported(String::from(
+            "Opening URIs isn't supported",
+        )))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(
+        emitter: &SignalEmitter<'_>,
+        position: i64,
+    ) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match self.state().status {
+            PlayStatus::Play => "Playing",
+            PlayStatus::Pause => "Paused",
+            PlayStatus::Stopped => "Stopped",
+        }
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        match self.state().repeat {
+            RepeatStatus::None => "None",
+            RepeatStatus::One => "Track",
+            RepeatStatus::All => "Playlist",
+        }
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&self, value: &str) -> zbus::Result<()> {
+        let repeat = match value {
+            "None" => RepeatStatus::None,
+            "Track" => RepeatStatus::One,
+            "Playlist" => RepeatStatus::All,
+            _ => {
+                return Err(zbus::fdo::Error::InvalidArgs(format!(
+                    "Unknown loop status: {value}"
+                ))
+                .into());
+            }
+        };
+        let _ = self.tcx.try_send(Event::SetRepeat(repeat));
+        Ok(())
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn set_rate(&self, _value: f64) {}
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.state().shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&self, value: bool) {
+        let _ = self.tcx.try_send(Event::SetShuffle(value));
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<&'static str, Value<'static>> {
+        let mut map = HashMap::new();
+        let Some(track) = self.state().track else {
+            map.insert(
+                "mpris:trackid",
+                Value::from(
+                    zbus::zvariant::ObjectPath::from_static_str_unchecked(
+                        NO_TRACK_PATH,
+                    ),
+                ),
+            );
+            return map;
+        };
+        if let Ok(path) =
+            zbus::zvariant::ObjectPath::try_from(track_path(track.id))
+        {
+            map.insert("mpris:trackid", Value::from(path));
+        }
+        if let Some(length) = track.length {
+            map.insert("mpris:length", Value::from(length.as_micros() as i64));
+        }
+        if let Some(art) = &track.art {
+            map.insert("mpris:artUrl", Value::from(file_url(art)));
+        }
+        map.insert("xesam:url", Value::from(file_url(&track.path)));
+        map.insert(
+            "xesam:title",
+            Value::from(track.title.unwrap_or_else(|| {
+                track
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })),
+        );
+        if !track.artists.is_empty() {
+            map.insert("xesam:artist", Value::from(track.artists));
+        }
+        if let Some(album) = track.album {
+            map.insert("xesam:album", Value::from(album));
+        }
+        if let Some(num) = track.num {
+            map.insert("xesam:trackNumber", Value::from(num as i32));
+        }
+        if let Some(discnum) = track.discnum {
+            map.insert("xesam:discNumber", Value::from(discnum as i32));
+        }
+        map
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state().volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, value: f64) {
+        let _ = self.tcx.try_send(Event::SetVolume(value));
+    }
+
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        let shared = self.shared.lock().unwrap();
+        let position = if shared.state.status == PlayStatus::Play {
+            shared.position + shared.position_updated.elapsed()
+        } else {
+            shared.position
+        };
+        position.as_micros() as i64
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        self.state().can_go_next
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+fn track_path(id: u64) -> String {
+    format!("{TRACK_PATH}/{id:016X}")
+}
+
+/// Converts a path to a `file://` URL, percent-encoding anything but
+/// unreserved characters and separators.
+fn file_url(path: &std::path::Path) -> String {
+    let mut url = String::from("file://");
+    for byte in path.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'/' => url.push(*byte as char),
+            _ => url.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    url
+}
+
+impl App {
+    /// Builds the state reported to the MPRIS service from the player's
+    /// current state.
+    pub(super) fn mpris_state(&self) -> PlayerState {
+        PlayerState {
+            status: self.play_status,
+            repeat: self.repeat,
+            shuffle: self.unshuffled.is_some(),
+            volume: if self.mute { 0.0 } else { self.volume as f64 },
+            track: self.playing.as_ref().map(|track| TrackInfo {
+                id: track_hash(track),
+                title: track.metadata.title.clone(),
+                artists: track.metadata.artists.clone(),
+                album: track.metadata.album.clone(),
+                discnum: track.metadata.discnum,
+                num: track.metadata.num,
+                length: track.metadata.duration,
+                path: track.path.clone(),
+                art: track.path.parent().and_then(|dir| {
+                    self.library
+                        .get_directory(path_hash(&dir.to_path_buf()))
+                        .and_then(|dir| dir.img.clone())
+                }),
+            }),
+            can_go_next: !self.queue.is_empty(),
+        }
+    }
+
+    pub(super) fn sync_mpris(&self) {
+        if let Some(mpris) = &self.mpris {
+            mpris.update(
+                self.mpris_state(),
+                self.track_duration
+                    .map_or(Duration::ZERO, |(current, _)| current),
+            );
+        }
+    }
+
+    pub(super) fn update_mpris(&mut self, event: Event) -> Task<Message> {
+        match event {
+            Event::Ready(mpris) => {
+                self.mpris = Some(mpris);
+                self.sync_mpris();
+                Task::none()
+            }
+            Event::Next => Task::done(queue::QueueMessage::SkipForward.into()),
+            Event::Pause => {
+                if self.play_status == PlayStatus::Play {
+                    Task::done(Message::TogglePlay)
+                } else {
+                    Task::none()
+                }
+            }
+            Event::Play => {
+                if self.play_status != PlayStatus::Play {
+                    Task::done(Message::TogglePlay)
+                } else {
+                    Task::none()
+                }
+            }
+            Event::PlayPause => Task::done(Message::TogglePlay),
+            Event::Previous => Task::done(queue::QueueMessage::SkipBack.into()),
+            Event::Quit => self.save_before_exit().chain(iced::exit()),
+            Event::Raise => {
+                iced::window::get_latest().and_then(iced::window::gain_focus)
+            }
+            Event::Seek(offset) => {
+                let Some((current, Some(total))) = self.track_duration else {
+                    return Task::none();
+                };
+                let target = current.as_micros() as i64 + offset;
+                if target >= total.as_micros() as i64 {
+                    Task::done(queue::QueueMessage::SkipForward.into())
+                } else {
+                    self.seek_to(Duration::from_micros(target.max(0) as u64))
+                }
+            }
+            Event::SetPosition(id, position) => {
+                let Some((_, Some(total))) = self.track_duration else {
+                    return Task::none();
+                };
+                let is_current =
+                    self.playing.as_ref().is_some_and(|t| track_hash(t) == id);
+                if is_current
+                    && position >= 0
+                    && (position as u128) <= total.as_micros()
+                {
+                    self.seek_to(Duration::from_micros(position as u64))
+                } else {
+                    Task::none()
+                }
+            }
+            Event::SetRepeat(repeat) => self.set_repeat(repeat),
+            Event::SetVolume(volume) => Task::done(Message::VolumeChanged(
+                volume.clamp(0.0, 1.0) as f32,
+            )),
+            Event::SetShuffle(on) => {
+                Task::done(queue::QueueMessage::SetShuffle(on).into())
+            }
+            Event::Stop => Task::done(queue::QueueMessage::Stop.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use zbus::zvariant::ObjectPath;
+
+    use super::*;
+
+    fn player(state: PlayerState) -> (Player, mpsc::Receiver<Event>) {
+        let (tcx, rcx) = mpsc::channel(16);
+        let shared = Arc::new(Mutex::new(Shared {
+            state,
+            position: Duration::ZERO,
+            position_updated: Instant::now(),
+        }));
+        (Player { tcx, shared }, rcx)
+    }
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            id: 0xAB,
+            artists: vec![String::from("A"), String::from("B")],
+            album: Some(String::from("Album")),
+            num: Some(3),
+            length: Some(Duration::from_secs(90)),
+            path: PathBuf::from("/music/a b.flac"),
+            ..TrackInfo::default()
+        }
+    }
+
+    #[test]
+    fn the_state_is_reported_in_mpris_terms() {
+        let (player, _) = player(PlayerState {
+            status: PlayStatus::Pause,
+            repeat: RepeatStatus::One,
+            track: Some(track()),
+            ..PlayerState::default()
+        });
+        assert_eq!(player.playback_status(), "Paused");
+        assert_eq!(player.loop_status(), "Track");
+        assert!(player.can_play() && player.can_seek());
+
+        let metadata = player.metadata();
+        assert_eq!(
+            metadata["mpris:trackid"],
+            Value::from(ObjectPath::try_from(track_path(0xAB)).unwrap())
+        );
+        assert_eq!(metadata["mpris:length"], Value::from(90_000_000_i64));
+        // a track without a title goes by its file name:
+        assert_eq!(metadata["xesam:title"], Value::from("a b.flac"));
+        assert_eq!(
+            metadata["xesam:url"],
+            Value::from("file:///music/a%20b.flac")
+        );
+        assert_eq!(metadata["xesam:album"], Value::from("Album"));
+        assert_eq!(metadata["xesam:trackNumber"], Value::from(3));
+        assert!(!metadata.contains_key("xesam:discNumber"));
+    }
+
+    #[test]
+    fn nothing_playing_is_reported_as_no_track() {
+        let (player, _) = player(PlayerState {
+            status: PlayStatus::Stopped,
+            ..PlayerState::default()
+        });
+        assert_eq!(player.playback_status(), "Stopped");
+        assert_eq!(player.loop_status(), "None");
+        assert!(!player.can_play());
+
+        let metadata = player.metadata();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(
+            metadata["mpris:trackid"],
+            Value::from(ObjectPath::from_static_str_unchecked(NO_TRACK_PATH))
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_passed_on_as_events() {
+        let (player, mut rcx) = player(PlayerState {
+            track: Some(track()),
+            ..PlayerState::default()
+        });
+        player.next().await;
+        assert!(matches!(rcx.recv().await, Some(Event::Next)));
+        player.play_pause().await;
+        assert!(matches!(rcx.recv().await, Some(Event::PlayPause)));
+        player.seek(-5_000_000).await;
+        assert!(matches!(rcx.recv().await, Some(Event::Seek(-5_000_000))));
+
+        // only the current track can be sought in:
+        let other = ObjectPath::try_from(track_path(0xCD)).unwrap();
+        player.set_position(other, 10).await;
+        let current = ObjectPath::try_from(track_path(0xAB)).unwrap();
+        player.set_position(current, 10).await;
+        assert!(matches!(
+            rcx.recv().await,
+            Some(Event::SetPosition(0xAB, 10))
+        ));
+
+        player.set_loop_status("Playlist").unwrap();
+        assert!(matches!(
+            rcx.recv().await,
+            Some(Event::SetRepeat(RepeatStatus::All))
+        ));
+        assert!(player.set_loop_status("Sometimes").is_err());
+        player.set_volume(0.5);
+        assert!(matches!(rcx.recv().await, Some(Event::SetVolume(0.5))));
+        assert!(rcx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! Choosing the output device, and moving playback between devices when the
+//! choice changes or a device goes away.
+//!
+//! A device is assumed to have gone away when playback stops moving while it
+//! should be playing, since a device in use may not be listed as available.
+
+use std::time::Duration;
+
+use super::*;
+use crate::internal::audio::{Backend, Output, StreamFormat};
+
+/// How often the output is checked on.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// An output device as offered in the settings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Device {
+    Default,
+    Named(String),
+}
+
+impl Device {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Default => None,
+            Self::Named(name) => Some(name),
+        }
+    }
+}
+
+impl std::fmt::Display for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name().unwrap_or("System default"))
+    }
+}
+
+/// Opens the device called `name`, or the default device if `name` is `None`
+/// or can't be opened, along with a notice if it fell back to the default.
+pub fn open(
+    backend: &dyn Backend,
+    name: Option<&str>,
+) -> (Option<Box<dyn Output>>, Option<String>) {
+    let output = backend.open(name).inspect_err(|e| {
+        eprintln!(
+            "Couldn't open {}: {e}",
+            name.unwrap_or("the default output")
+        )
+    });
+    match (output, name) {
+        (Ok(output), _) => (Some(output), None),
+        (Err(_), Some(name)) => {
+            let output = backend
+                .open(None)
+                .inspect_err(|e| {
+                    eprintln!("Couldn't open the default output: {e}")
+                })
+                .ok();
+            let notice = output.as_ref().map(|_| {
+                format!("{name} isn't available, so the default is used")
+            });
+            (output, notice)
+        }
+        (Err(_), None) => (None, None),
+    }
+}
+
+/// A sink playing on `output`, along with the output. If there isn't one, or
+/// it can't be played on, the sink plays nowhere and there's no output, so
+/// playback's held until one is opened.
+pub fn sink(
+    output: Option<Box<dyn Output>>,
+) -> (Option<Box<dyn Output>>, rodio::Sink) {
+    let sink = output.as_ref().and_then(|output| {
+        output
+            .sink()
+            .inspect_err(|e| eprintln!("Couldn't start playback: {e}"))
+            .ok()
+    });
+    match sink {
+        Some(sink) => (output, sink),
+        None => (None, rodio::Sink::new_idle().0),
+    }
+}
+
+impl App {
+    /// The devices to offer in the settings, always including the chosen one.
+    pub fn device_choices(&self) -> Vec<Device> {
+        let mut devices = vec![Device::Default];
+        devices.extend(self.output_devices.iter().cloned().map(Device::Named));
+        if let Some(chosen) = &self.config.output.device
+            && !self.output_devices.contains(chosen)
+        {
+            devices.push(Device::Named(chosen.clone()));
+        }
+        devices
+    }
+
+    /// Moves playback onto the device called `name`, or the default device,
+    /// carrying on from the same point in the current track.
+    pub fn switch_output(&mut self, name: Option<&str>) {
+        let position = self.position.get();
+        self.sink.stop();
+        // a hardware device can only be opened once at a time, so the old one
+        // is closed first in case it's the same:
+        self.output = None;
+        let (output, notice) = open(&*self.backend, name);
+        if let Some(notice) = notice {
+            self.notify(notice);
+        }
+        (self.output, self.sink) = sink(output);
+        self.sink.set_volume(self.output_volume());
+        if self.play_status != PlayStatus::Play {
+            self.sink.pause();
+        }
+        let Some(track) = self.playing.clone() else {
+            return;
+        };
+        match self.append_stream(&track) {
+            Ok(()) => {
+                let _ = self.sink.try_seek(position);
+            }
+            Err(e) => self.pass_over(e),
+        }
+        self.last_position = position;
+    }
+
+    /// Reopens the device in `format`, unless it's open in it already, so
+    /// that a track in that format plays bit-perfectly. The device's left as
+    /// it is if it can't play the format.
+    pub fn match_output(&mut self, format: StreamFormat) {
+        let Some(current) = &self.output else {
+            return;
+        };
+        if current
+            .format()
+            .is_some_and(|current| current.plays_natively(format))
+        {
+            return;
+        }
+        let name = current.name().map(String::from);
+        self.sink.stop();
+        // the device has to be let go of before it's opened again:
+        self.output = None;
+        let output = match self.backend.open_native(name.as_deref(), format) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                let device = name.as_deref().unwrap_or("The default output");
+                eprintln!(
+                    "Couldn't open {device} for bit-perfect playback: {e}"
+                );
+                self.notify(format!("{device} can't play this track natively"));
+                let (output, notice) = open(&*self.backend, name.as_deref());
+                if let Some(notice) = notice {
+                    self.notify(notice);
+                }
+                output
+            }
+        };
+        (self.output, self.sink) = sink(output);
+        self.sink.set_volume(self.output_volume());
+        if self.play_status != PlayStatus::Play {
+            self.sink.pause();
+        }
+    }
+
+    /// Falls back to the default device if the current one has stopped
+    /// playing, and returns to the chosen device once it's available again.
+    /// Listing the devices can be slow, so it's done in the background.
+    pub fn check_output(&mut self) -> Task<Message> {
+        let position = self.position.get();
+        let stalled = self.output.is_some()
+            && self.playing.is_some()
+            && self.play_status == PlayStatus::Play
+            && !self.sink.empty()
+            && position == self.last_position;
+        self.last_position = position;
+
+        if stalled {
+            let current = self.output.as_ref().and_then(|output| output.name());
+            eprintln!(
+                "{} stopped playing",
+                current.unwrap_or("The default output")
+            );
+            if let Some(current) = current {
+                self.notify(format!(
+                    "{current} stopped playing, so the default is used"
+                ));
+            }
+            self.switch_output(None);
+            return Task::none();
+        }
+        if self.listing_devices || !self.off_chosen_output() {
+            return Task::none();
+        }
+        self.listing_devices = true;
+        self.list_devices(Message::OutputDevicesListed)
+    }
+
+    /// Lists the devices in the background, since a device that's stuck can
+    /// hold the listing up.
+    pub fn list_devices(
+        &self,
+        done: impl Fn(Vec<String>) -> Message + Send + 'static,
+    ) -> Task<Message> {
+        let backend = self.backend.clone();
+        Task::perform(
+            tokio::task::spawn_blocking(move || backend.device_names()),
+            move |names| done(names.unwrap_or_default()),
+        )
+    }
+
+    /// Whether nothing's open, or a device other than the chosen one.
+    fn off_chosen_output(&self) -> bool {
+        match (&self.config.output.device, &self.output) {
+            (_, None) => true,
+            (Some(chosen), Some(output)) => output.name() != Some(chosen),
+            (None, Some(_)) => false,
+        }
+    }
+
+    /// Opens the chosen device if it's among `names`, or any device if
+    /// nothing's open.
+    pub fn return_to_chosen_output(&mut self, names: &[String]) {
+        let chosen = self.config.output.device.clone();
+        match (chosen, self.output.as_ref().map(|output| output.name())) {
+            // nothing's open, so any device will do:
+            (chosen, None) if !names.is_empty() => {
+                self.switch_output(chosen.as_deref());
+            }
+            (Some(chosen), Some(current))
+                if current != Some(&chosen) && names.contains(&chosen) =>
+            {
+                self.switch_output(Some(&chosen));
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use super::*;
+    use crate::internal::audio::device::DeviceFormat;
+
+    /// A device that's open but can't be played on.
+    struct Broken;
+
+    impl Output for Broken {
+        fn sink(&self) -> Result<rodio::Sink, Box<dyn Error>> {
+            Err("the device has gone".into())
+        }
+
+        fn name(&self) -> Option<&str> {
+            None
+        }
+
+        fn format(&self) -> Option<DeviceFormat> {
+            None
+        }
+    }
+
+    #[test]
+    fn an_output_that_cant_be_played_on_is_dropped() {
+        let (output, sink) = sink(Some(Box::new(Broken)));
+        assert!(output.is_none());
+        assert!(sink.empty());
+    }
+}
@@ -1,21 +1,24 @@
 use std::{
     collections::{HashMap, hash_map},
     error::Error,
-    fs::File,
-    io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 
+use super::watch::FileStamps;
+
+#[derive(Default)]
 pub struct PlaylistMap {
-    map: HashMap<u64, Playlist>,
+    map:    HashMap<u64, Playlist>,
+    stamps: FileStamps,
 }
 
 impl PlaylistMap {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            map:    HashMap::new(),
+            stamps: FileStamps::default(),
         }
     }
 
@@ -38,40 +41,70 @@ impl PlaylistMap {
     }
 
     pub fn remove_playlist(&mut self, id: u64) -> Option<Playlist> {
-        self.map.remove(&id)
+        self.map.remove(&id).inspect(|pl| {
+            if let Ok(path) = pl.file_path() {
+                self.stamps.forget(&path);
+            }
+        })
     }
 
-    pub fn scan_playlists(&mut self) -> Result<(), Box<dyn Error>> {
-        let mut path = crate::exe_path()?;
-        path.push("playlists/");
-        if !path.exists() {
-            return Ok(());
+    /// Saves the given playlist, unless its file was changed by another
+    /// program since it was read (see `FileStamps::write`).
+    pub fn write_playlist(&mut self, id: u64) -> Result<(), Box<dyn Error>> {
+        let pl = self.map.get(&id).ok_or("no such playlist")?;
+        let path = pl.file_path()?;
+        let toml = pl.serialize()?;
+        self.stamps.write(&path, &toml)
+    }
+
+    /// Rereads the playlists if any were added, changed or removed by another
+    /// program, returning whether they were.
+    pub fn reload(&mut self) -> Result<bool, Box<dyn Error>> {
+        let files = crate::paths::playlist_dir()?
+            .read_dir()?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect::<Vec<_>>();
+        if self.stamps.unchanged(&files) {
+            return Ok(false);
         }
-        for entry in path.read_dir().unwrap() {
-            if let Err(e) = entry {
-                eprintln!("Error reading entry: {e}");
-            } else if let Ok(entry) = entry {
-                let path = entry.path();
-                let Some(extension) = path.extension() else {
+        *self = Self::new();
+        self.scan_playlists()?;
+        Ok(true)
+    }
+
+    /// Reads the playlists in the playlist directory. A file that can't be
+    /// read, such as one that's half written, is reported and left out.
+    pub fn scan_playlists(&mut self) -> Result<(), Box<dyn Error>> {
+        for entry in crate::paths::playlist_dir()?.read_dir()? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    eprintln!("Error reading entry: {e}");
                     continue;
-                };
-                if extension.to_str().unwrap() == "toml" {
-                    let mut file = File::open(&path)?;
-                    let mut s = String::new();
-                    file.read_to_string(&mut s)?;
-                    let Ok(toml) = toml::from_str(&s) else {
-                        continue;
-                    };
-                    let pl = Playlist::from_toml(
-                        toml,
-                        path.file_name().unwrap().to_str().unwrap().to_owned(),
-                    );
-                    self.add_playlist(pl);
                 }
+            };
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            if let Err(e) = self.read_playlist(&path) {
+                eprintln!("Couldn't read {}: {e}", path.display());
             }
         }
         Ok(())
     }
+
+    fn read_playlist(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let s = std::fs::read_to_string(path)?;
+        self.stamps.record(path, s.as_bytes());
+        let pl = Playlist::from_toml(
+            toml::from_str(&s)?,
+            path.file_name().unwrap().to_string_lossy().into_owned(),
+        );
+        self.add_playlist(pl);
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -133,10 +166,19 @@ impl Playlist {
         }
     }
 
+    /// The tracks in the playlist which could be found, in order.
+    pub fn track_ids(&self) -> Vec<u64> {
+        self.tracks
+            .iter()
+            .filter_map(|track| match track {
+                PlaylistTrack::Track(id, _) => Some(*id),
+                PlaylistTrack::Unresolved(_) => None,
+            })
+            .collect()
+    }
+
     pub fn file_path(&self) -> Result<PathBuf, Box<dyn Error>> {
-        let mut path = crate::exe_path()?;
-        path.push("playlists/");
-        std::fs::create_dir_all(&path)?;
+        let mut path = crate::paths::playlist_dir()?;
         path.push(self.filename.clone());
         Ok(path)
     }
@@ -164,12 +206,6 @@ impl Playlist {
         };
         Ok(toml::to_string_pretty(&playlist)?)
     }
-
-    pub fn write_to_file(&self) -> Result<(), Box<dyn Error>> {
-        let mut f = File::create(self.file_path()?)?;
-        let toml = self.serialize()?;
-        Ok(f.write_all(toml.as_bytes())?)
-    }
 }
 
 impl std::fmt::Display for Playlist {
@@ -0,0 +1,164 @@
+//! Radio mode: once the queue runs out, tracks related to the last one played
+//! are queued, so playback carries on.
+//!
+//! Which tracks count as related is chosen in the `[radio]` section of the
+//! config, from the [`Relation`]s below.
+
+use serde::{Deserialize, Serialize};
+
+use crate::internal::{Library, Track};
+
+/// How many tracks are queued each time the queue runs out.
+const BATCH: usize = 5;
+
+/// A way two tracks can be related.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relation {
+    /// In the same folder.
+    Folder,
+    /// Sharing an artist.
+    Artist,
+    /// By the same album artist, or the same first artist for tracks without
+    /// one.
+    AlbumArtist,
+    /// Sharing a genre.
+    Genre,
+}
+
+impl Relation {
+    pub const ALL: [Self; 4] =
+        [Self::Folder, Self::Artist, Self::AlbumArtist, Self::Genre];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Folder => "Tracks from the same folder",
+            Self::Artist => "Tracks by the same artist",
+            Self::AlbumArtist => "Tracks by the same album artist",
+            Self::Genre => "Tracks of the same genre",
+        }
+    }
+
+    fn relates(self, a: &Track, b: &Track) -> bool {
+        fn shared(a: &[String], b: &[String]) -> bool {
+            a.iter()
+                .any(|a| b.iter().any(|b| a.eq_ignore_ascii_case(b)))
+        }
+        fn album_artist(track: &Track) -> Option<&String> {
+            let metadata = &track.metadata;
+            metadata.album_artist.as_ref().or(metadata.artists.first())
+        }
+
+        match self {
+            Self::Folder => a.path.parent() == b.path.parent(),
+            Self::Artist => shared(&a.metadata.artists, &b.metadata.artists),
+            Self::AlbumArtist => album_artist(a)
+                .zip(album_artist(b))
+                .is_some_and(|(a, b)| a.eq_ignore_ascii_case(b)),
+            Self::Genre => shared(&a.metadata.genres, &b.metadata.genres),
+        }
+    }
+}
+
+/// Picks tracks to carry on from `seed` with, favoring those related to it in
+/// more of the given ways, and avoiding tracks that were `played` recently
+/// unless there's nothing else.
+pub fn pick(
+    library: &Library,
+    seed: u64,
+    relations: &[Relation],
+    played: impl Fn(u64) -> bool,
+) -> Vec<u64> {
+    use rand::Rng;
+
+    let Some(seed_track) = library.get_track(seed) else {
+        return vec![];
+    };
+    let candidates = library
+        .tracks()
+        .filter(|(id, _)| **id != seed)
+        .map(|(id, track)| {
+            let related = relations
+                .iter()
+                .filter(|relation| relation.relates(seed_track, track))
+                .count();
+            (*id, related, played(*id))
+        })
+        .collect::<Vec<_>>();
+
+    // related tracks that weren't played recently, else any that weren't,
+    // else anything at all:
+    let fallbacks: [fn(usize, bool) -> bool; 3] = [
+        |related, played| related > 0 && !played,
+        |_, played| !played,
+        |_, _| true,
+    ];
+    let pool = fallbacks
+        .into_iter()
+        .map(|allowed| {
+            candidates
+                .iter()
+                .filter(|(_, related, played)| allowed(*related, *played))
+                .collect::<Vec<_>>()
+        })
+        .find(|pool| !pool.is_empty())
+        .unwrap_or_default();
+
+    // weighted so that tracks related in more ways are much likelier, using
+    // the same method as `UserData::weighted_shuffle`:
+    let mut rng = rand::rng();
+    let mut keyed = pool
+        .into_iter()
+        .map(|(id, related, _)| {
+            let weight = (1 + related * related) as f64;
+            let u: f64 = 1.0 - rng.random::<f64>();
+            (u.powf(1.0 / weight), *id)
+        })
+        .collect::<Vec<_>>();
+    keyed.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+    keyed.into_iter().take(BATCH).map(|(_, id)| id).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::internal::{AudioType, Metadata};
+
+    #[test]
+    fn related_tracks_are_picked() {
+        fn track(path: &str, artist: &str, genre: &str) -> Track {
+            Track {
+                path: PathBuf::from(path),
+                audio_type: AudioType::Flac,
+                metadata: Metadata {
+                    artists: vec![String::from(artist)],
+                    genres: vec![String::from(genre)],
+                    ..Metadata::default()
+                },
+            }
+        }
+
+        let mut library = Library::new();
+        let seed = library.add_track(track("/a/1.flac", "A", "jazz"));
+        let sibling = library.add_track(track("/a/2.flac", "B", "rock"));
+        let same_artist = library.add_track(track("/b/1.flac", "a", "rock"));
+        let same_genre = library.add_track(track("/c/1.flac", "C", "Jazz"));
+        let unrelated = library.add_track(track("/d/1.flac", "D", "pop"));
+
+        let picked = pick(&library, seed, &Relation::ALL, |_| false);
+        assert_eq!(picked.len(), 3);
+        assert!(!picked.contains(&unrelated));
+        assert!(!picked.contains(&seed));
+
+        let picked = pick(&library, seed, &[Relation::Genre], |_| false);
+        assert_eq!(picked, [same_genre]);
+
+        // once every related track has been played, the rest come up:
+        let played = [sibling, same_artist, same_genre];
+        let picked =
+            pick(&library, seed, &Relation::ALL, |id| played.contains(&id));
+        assert_eq!(picked, [unrelated]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Attaching the app to a daemon (see `crate::daemon`) as a remote control.
+//! Playback messages are forwarded to the daemon, and the app's playback state
+//! is mirrored from the daemon's status.
+
+use super::*;
+use crate::ipc::{self, Request, Response, Status};
+
+/// How often an attached app polls the daemon for its status.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+impl App {
+    pub(super) fn send_remote(&self, request: Request) -> Task<Message> {
+        Task::future(async move {
+            match ipc::request(&request).await {
+                Ok(Response {
+                    ok: false, error, ..
+                }) => eprintln!(
+                    "Daemon refused {request:?}: {}",
+                    error.unwrap_or_default()
+                ),
+                Err(e) => eprintln!("Couldn't reach daemon: {e}"),
+                Ok(_) => (),
+            }
+        })
+        .map(|_| Message::None)
+    }
+
+    pub(super) fn send_remote_queue(&self) -> Task<Message> {
+        self.send_remote(Request::SetQueue {
+            paths: self
+                .queue
+                .iter()
+                .filter_map(|id| self.library.get_track(*id))
+                .map(|track| track.path.clone())
+                .collect(),
+        })
+    }
+
+    /// Handles messages which affect playback by sending them to the daemon.
+    /// Any other message is given back, to be handled as usual.
+    pub(super) fn update_remote(
+        &mut self,
+        message: Message,
+    ) -> Result<Task<Message>, Message> {
+        match message {
+            Message::PlayheadMoved(val) => {
+                self.playhead_position = val;
+                self.seeking = true;
+                let Some((_, Some(total))) = self.track_duration else {
+                    return Ok(Task::none());
+                };
+                Ok(self.send_remote(Request::Seek {
+                    position: val as f64 * total.as_secs_f64(),
+                }))
+            }
+            Message::PlayNext => {
+                Ok(self.send_remote(Request::Jump { index: 0 }))
+            }
+            Message::Queue(queue::QueueMessage::SkipForward) => {
+                Ok(self.send_remote(Request::Next))
+            }
+            Message::Queue(queue::QueueMessage::Stop) => {
+                self.queue.clear();
+                Ok(self.send_remote(Request::Stop))
+            }
+            Message::Queue(msg) => {
+                // edit the queue locally, then send the daemon the result:
+                let before = self.queue.clone();
+                let task = self.update_queue(msg);
+                if self.queue != before {
+                    Ok(self.send_remote_queue().chain(task))
+                } else {
+                    Ok(task)
+                }
+            }
+            Message::RemoteStatus(status) => {
+                self.mirror_status(status);
+                Ok(Task::none())
+            }
+            Message::TogglePlay => {
+                self.play_status = match self.play_status {
+                    PlayStatus::Play => PlayStatus::Pause,
+                    PlayStatus::Pause | PlayStatus::Stopped => PlayStatus::Play,
+                };
+                Ok(self.send_remote(Request::Toggle))
+            }
+            Message::UpdateProgress => Ok(Task::perform(
+                async {
+                    ipc::request(&Request::Status)
+                        .await
+                        .ok()
+                        .and_then(|response| response.status)
+                },
+                Message::RemoteStatus,
+            )),
+            message => Err(message),
+        }
+    }
+
+    fn mirror_status(&mut self, status: Option<Status>) {
+        let Some(status) = status else {
+            self.playing = None;
+            self.track_duration = None;
+            self.play_status = PlayStatus::Stopped;
+            return;
+        };
+        self.play_status = status.state;
+        self.repeat = status.repeat;
+        if !self.mute {
+            self.volume = status.volume;
+        }
+        let queue = status
+            .queue
+            .iter()
+            .map(path_hash)
+            .filter(|id| self.library.get_track(*id).is_some())
+            .collect();
+        if queue != self.queue {
+            self.queue = queue;
+            self.queue_state.clear();
+        }
+        let before = self.playing.as_ref().map(track_hash);
+        self.playing = status
+            .track
+            .and_then(|path| self.library.get_track(path_hash(&path)))
+            .cloned();
+        // a track that was skipped back to is taken back out of the previous
+        // tracks, and any other change means the last track was played:
+        let after = self.playing.as_ref().map(track_hash);
+        if let Some(before) = before
+            && after != Some(before)
+        {
+            if after.is_some() && self.previous.back().copied() == after {
+                self.previous.pop_back();
+            } else {
+                self.push_previous(before);
+            }
+        }
+        self.track_duration = self.playing.as_ref().map(|_| {
+            (
+                Duration::from_secs_f64(status.position.max(0.0)),
+                status
+                    .duration
+                    .map(|total| Duration::from_secs_f64(total.max(0.0))),
+            )
+        });
+        if !self.seeking {
+            self.playhead_position = match self.track_duration {
+                Some((current, Some(total))) if !total.is_zero() => {
+                    current.as_secs_f32() / total.as_secs_f32()
+                }
+                _ => 0.0,
+            };
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::RepeatStatus;
+
+/// Playback state saved between runs of the application.
+///
+/// Tracks are stored by path rather than by hash, as TOML can't represent the
+/// full range of a `u64`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Session {
+    pub queue: Vec<PathBuf>,
+    pub playing: Option<PathBuf>,
+    /// Position within the playing track, in seconds.
+    pub position: f64,
+    /// The queue's original order, if shuffle is on.
+    #[serde(default)]
+    pub unshuffled: Option<Vec<PathBuf>>,
+    pub repeat: RepeatStatus,
+    #[serde(default)]
+    pub radio: bool,
+    pub viewing: SessionView,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub enum SessionView {
+    History,
+    #[default]
+    LibraryRoot,
+    Library(PathBuf),
+    Playlist(Option<String>),
+    Settings,
+}
+
+impl Session {
+    pub fn from_file(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut s = String::new();
+        file.read_to_string(&mut s)?;
+        Ok(toml::from_str(&s)?)
+    }
+
+    pub fn file_path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = crate::paths::data_dir()?;
+        path.push("session.toml");
+        Ok(path)
+    }
+
+    pub fn write_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let toml = toml::to_string_pretty(&self)?;
+        let mut file = File::create(path)?;
+        Ok(file.write_all(toml.as_bytes())?)
+    }
+}
@@ -0,0 +1,222 @@
+//! Shuffle mode, and the orders tracks can be shuffled into.
+//!
+//! While shuffle is on, the queue is played in a shuffled order but the order
+//! it was filled in is kept, so turning shuffle off puts the remaining tracks
+//! back where they were.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    path::PathBuf,
+};
+
+use rand::{rng, seq::SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use super::user_data::UserData;
+use crate::internal::Library;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Every track in a random order.
+    #[default]
+    Tracks,
+    /// Albums in a random order, each played through in order.
+    Albums,
+    /// Tracks in a random order, avoiding playing the same artist twice in a
+    /// row.
+    Artists,
+}
+
+impl Strategy {
+    pub const ALL: [Self; 3] = [Self::Tracks, Self::Albums, Self::Artists];
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Tracks => "Tracks",
+            Self::Albums => "Albums",
+            Self::Artists => "Spread out artists",
+        })
+    }
+}
+
+/// Shuffles `tracks` according to `strategy`, favoring higher-rated tracks if
+/// `weights` is given. Tracks in `recent`, most recently played last, are
+/// pushed towards the end.
+pub fn shuffle(
+    tracks: &mut [u64],
+    strategy: Strategy,
+    library: &Library,
+    weights: Option<&UserData>,
+    recent: &VecDeque<u64>,
+) {
+    // 0 for tracks that weren't played recently, so they stay ahead:
+    let recency =
+        |id: &u64| recent.iter().rposition(|r| r == id).map_or(0, |i| i + 1);
+
+    match weights {
+        Some(user_data) => user_data.weighted_shuffle(tracks),
+        None => tracks.shuffle(&mut rng()),
+    }
+    match strategy {
+        Strategy::Tracks => tracks.sort_by_key(recency),
+        Strategy::Albums => {
+            let album = |id: u64| {
+                library.get_track(id).map(|track| {
+                    (
+                        track.path.parent().map(PathBuf::from),
+                        track.metadata.album.clone(),
+                    )
+                })
+            };
+            let position = |id: u64| {
+                library.get_track(id).map(|track| {
+                    (track.metadata.discnum, track.metadata.num, &track.path)
+                })
+            };
+            group_albums(tracks, album, position, recency);
+        }
+        Strategy::Artists => {
+            tracks.sort_by_key(recency);
+            spread(tracks, |id| {
+                library
+                    .get_track(id)
+                    .and_then(|track| track.metadata.artists.first())
+                    .map(|artist| artist.to_lowercase())
+            });
+        }
+    }
+}
+
+/// Gathers already shuffled `tracks` into their albums, in the order each
+/// album first appears, with each album sorted by `position`. Albums holding a
+/// track with a higher `recency` go later.
+fn group_albums<K: Eq + Hash, P: Ord>(
+    tracks: &mut [u64],
+    album: impl Fn(u64) -> Option<K>,
+    position: impl Fn(u64) -> P,
+    recency: impl Fn(&u64) -> usize,
+) {
+    let mut index = HashMap::new();
+    let mut albums: Vec<Vec<u64>> = vec![];
+    for &id in tracks.iter() {
+        match album(id) {
+            Some(key) => {
+                let i = *index.entry(key).or_insert_with(|| {
+                    albums.push(vec![]);
+                    albums.len() - 1
+                });
+                albums[i].push(id);
+            }
+            // a track without an album is an album of its own:
+            None => albums.push(vec![id]),
+        }
+    }
+    for album in albums.iter_mut() {
+        album.sort_by_key(|id| position(*id));
+    }
+    albums.sort_by_key(|album| album.iter().map(&recency).max());
+    tracks
+        .iter_mut()
+        .zip(albums.into_iter().flatten())
+        .for_each(|(track, id)| *track = id);
+}
+
+/// Reorders already shuffled `tracks` so that no two in a row share an
+/// `artist`, where that can be avoided, while otherwise keeping them close to
+/// their shuffled positions. Tracks without an artist can go anywhere.
+fn spread<K: Eq + Hash>(tracks: &mut [u64], artist: impl Fn(u64) -> Option<K>) {
+    let mut index = HashMap::new();
+    let mut artists: Vec<VecDeque<(usize, u64)>> = vec![];
+    for (pos, &id) in tracks.iter().enumerate() {
+        match artist(id) {
+            Some(key) => {
+                let i = *index.entry(key).or_insert_with(|| {
+                    artists.push(VecDeque::new());
+                    artists.len() - 1
+                });
+                artists[i].push_back((pos, id));
+            }
+            None => artists.push(VecDeque::from([(pos, id)])),
+        }
+    }
+
+    let mut last = None;
+    for track in tracks.iter_mut() {
+        // the artist with the most tracks left goes next, so they can't all
+        // end up together at the end, and of those, whoever's next track was
+        // shuffled nearest the front:
+        let next = (0..artists.len())
+            .filter(|&i| Some(i) != last)
+            .filter_map(|i| Some((i, artists[i].front()?.0)))
+            .max_by_key(|&(i, pos)| (artists[i].len(), std::cmp::Reverse(pos)))
+            .map(|(i, _)| i)
+            .or(last)
+            .unwrap();
+        *track = artists[next].pop_front().unwrap().1;
+        last = Some(next);
+    }
+}
+
+/// Puts the tracks still in `queue` back into their `original` order, carrying
+/// on from just after `current` if it's there. Tracks which were queued some
+/// other way are left at the end.
+pub fn unshuffle(
+    queue: &[u64],
+    original: &[u64],
+    current: Option<u64>,
+) -> Vec<u64> {
+    let mut remaining = HashMap::<u64, usize>::new();
+    queue
+        .iter()
+        .for_each(|id| *remaining.entry(*id).or_default() += 1);
+
+    let start = current
+        .and_then(|current| original.iter().position(|id| *id == current))
+        .map_or(0, |i| i + 1);
+    let mut take = |id: &u64| match remaining.get_mut(id) {
+        Some(n) if *n > 0 => {
+            *n -= 1;
+            true
+        }
+        _ => false,
+    };
+    let mut tracks = original[start..]
+        .iter()
+        .chain(&original[..start])
+        .copied()
+        .filter(|id| take(id))
+        .collect::<Vec<_>>();
+    tracks.extend(queue.iter().copied().filter(|id| take(id)));
+    tracks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shuffle_strategies() {
+        // tracks are numbered album * 10 + track, artist = album % 2:
+        let mut tracks = vec![21, 12, 33, 11, 31, 22, 32, 13];
+        group_albums(
+            &mut tracks,
+            |id| Some(id / 10),
+            |id| id,
+            |id| usize::from(*id == 12),
+        );
+        assert_eq!(tracks[..5], [21, 22, 31, 32, 33]);
+        assert_eq!(tracks[5..], [11, 12, 13]);
+
+        let mut tracks = vec![11, 31, 33, 13, 21, 22];
+        spread(&mut tracks, |id| Some(id / 10 % 2));
+        assert_eq!(tracks, [11, 21, 31, 22, 33, 13]);
+
+        let original = [1, 2, 3, 4, 5];
+        assert_eq!(unshuffle(&[5, 3, 1, 9], &original, None), [1, 3, 5, 9]);
+        assert_eq!(unshuffle(&[5, 1, 2], &original, Some(3)), [5, 1, 2]);
+    }
+}
@@ -0,0 +1,657 @@
+//! Drives the app through sequences of messages, without a window or a sound
+//! card, checking the queue, what's playing and what's written to disk.
+
+use std::{
+    fs,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Instant,
+};
+
+use futures::StreamExt;
+use iced_runtime::Action;
+use queue::QueueMessage;
+
+use super::*;
+use crate::internal::{AudioType, Directory, Metadata};
+
+/// How long each of the generated tracks is.
+const TRACK_LENGTH: Duration = Duration::from_millis(300);
+/// How many times faster than real time the null output plays.
+const SPEED: f32 = 20.0;
+/// How long playback's given to get where a test expects it to.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The app's files are kept in one place for the whole process, so only one
+/// harness can run at a time.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// An app with a library of short generated tracks, playing on the null
+/// output, which keeps its files in a temporary directory.
+struct Harness {
+    app:    App,
+    /// The library's tracks, in order.
+    tracks: Vec<u64>,
+    rt:     tokio::runtime::Runtime,
+    dir:    PathBuf,
+    _lock:  MutexGuard<'static, ()>,
+}
+
+impl Harness {
+    fn new(track_count: usize) -> Self {
+        let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let dir = std::env::temp_dir()
+            .join(format!("polypody-app-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        *crate::paths::TEST_DIR.lock().unwrap() = Some(dir.clone());
+
+        let root = dir.join("music");
+        fs::create_dir_all(&root).unwrap();
+        let mut library = Library::new();
+        let mut directory = Directory::new(root.clone());
+        for n in 0..track_count {
+            let path = root.join(format!("{n}.wav"));
+            write_tone(&path, n);
+            directory.tracks.push(library.add_track(Track {
+                path,
+                audio_type: AudioType::Wav,
+                metadata: Metadata {
+                    title: Some(format!("Track {n}")),
+                    num: Some(n + 1),
+                    duration: Some(TRACK_LENGTH),
+                    ..Metadata::default()
+                },
+            }));
+        }
+        let tracks = directory.tracks.clone();
+        let root_id = library.add_directory(directory);
+        library.set_root(root_id);
+
+        let mut config = Config::default();
+        config.library.replace_root(None, root);
+        let saved = Saved {
+            config,
+            config_stamp: FileStamps::default(),
+            library,
+            start_screen: None,
+            history: History::new(),
+            user_data: UserData::new(),
+            playlists: PlaylistMap::new(),
+            session: None,
+        };
+        let options = LaunchOptions {
+            null_output: Some(SPEED),
+            ..LaunchOptions::default()
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (app, task) = {
+            let _guard = rt.enter();
+            App::from_saved(false, options, saved)
+        };
+        let mut harness = Self {
+            app,
+            tracks,
+            rt,
+            dir,
+            _lock: lock,
+        };
+        harness.run(task);
+        harness
+    }
+
+    /// Handles `message` and any messages its tasks produce, as the runtime
+    /// would.
+    fn send(&mut self, message: impl Into<Message>) {
+        let task = {
+            let _guard = self.rt.enter();
+            self.app.update(message.into())
+        };
+        self.run(task);
+    }
+
+    /// Handles the messages `task` produces, ignoring anything it asks of the
+    /// window.
+    fn run(&mut self, task: Task<Message>) {
+        let Some(stream) = iced_runtime::task::into_stream(task) else {
+            return;
+        };
+        let messages = self.rt.block_on(
+            stream
+                .filter_map(|action| {
+                    std::future::ready(match action {
+                        Action::Output(message) => Some(message),
+                        _ => None,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        );
+        messages.into_iter().for_each(|message| self.send(message));
+    }
+
+    /// Keeps playback moving until `done` holds.
+    fn play_until(&mut self, done: impl Fn(&Self) -> bool) {
+        let start = Instant::now();
+        while !done(self) {
+            assert!(start.elapsed() < TIMEOUT, "playback didn't get there");
+            std::thread::sleep(Duration::from_millis(5));
+            self.send(Message::UpdateProgress);
+        }
+    }
+
+    /// The position in the library of the track that's playing.
+    fn playing(&self) -> Option<usize> {
+        let id = track_hash(self.app.playing.as_ref()?);
+        self.tracks.iter().position(|track| *track == id)
+    }
+
+    /// The positions in the library of the queued tracks.
+    fn queue(&self) -> Vec<usize> {
+        self.app
+            .queue
+            .iter()
+            .map(|id| self.tracks.iter().position(|track| track == id).unwrap())
+            .collect()
+    }
+
+    /// Saves a playlist of the tracks at `tracks` in the library.
+    fn add_playlist(&mut self, filename: &str, tracks: &[usize]) -> u64 {
+        let tracks = tracks
+            .iter()
+            .map(|i| {
+                let id = self.tracks[*i];
+                let path = self.app.library.get_track(id).unwrap().path.clone();
+                PlaylistTrack::Track(id, path)
+            })
+            .collect();
+        let pl = Playlist::new(
+            filename.to_uppercase(),
+            filename.to_owned(),
+            None,
+            tracks,
+        );
+        let id = self.app.playlists.add_playlist(pl);
+        self.app.playlists.write_playlist(id).unwrap();
+        id
+    }
+
+    /// The config as it was last written.
+    fn saved_config(&self) -> Config {
+        Config::from_file(Config::file_path().unwrap()).unwrap()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        *crate::paths::TEST_DIR.lock().unwrap() = None;
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Writes a quiet tone, a different pitch for each track.
+fn write_tone(path: &PathBuf, n: usize) {
+    write_tone_at_depth(path, n, 16);
+}
+
+fn write_tone_at_depth(path: &PathBuf, n: usize, bits_per_sample: u16) {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44_100,
+        bits_per_sample,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::create(path, spec).unwrap();
+    let frames = (TRACK_LENGTH.as_secs_f64() * 44_100.0) as usize;
+    let pitch = 220.0 * (n + 1) as f64;
+    for frame in 0..frames {
+        let t = frame as f64 / 44_100.0;
+        let sample =
+            ((t * pitch * std::f64::consts::TAU).sin() * 1000.0) as i32;
+        wav.write_sample(sample).unwrap();
+        wav.write_sample(sample).unwrap();
+    }
+    wav.finalize().unwrap();
+}
+
+#[test]
+fn playing_a_track_queues_the_ones_after_it() {
+    let mut h = Harness::new(4);
+    h.send(QueueMessage::PlayTrack(1));
+    assert_eq!(h.playing(), Some(1));
+    assert_eq!(h.queue(), [2, 3]);
+    assert_eq!(h.app.play_status, PlayStatus::Play);
+
+    // with Repeat All, the tracks before it come round after:
+    h.send(Message::ToggleRepeat);
+    h.send(Message::ToggleRepeat);
+    assert_eq!(h.app.repeat, RepeatStatus::All);
+    h.send(QueueMessage::PlayTrack(2));
+    assert_eq!(h.playing(), Some(2));
+    assert_eq!(h.queue(), [3, 0, 1]);
+
+    h.send(QueueMessage::PlayTrack(4));
+    assert_eq!(h.playing(), Some(2));
+    assert_eq!(h.queue(), [3, 0, 1]);
+}
+
+#[test]
+fn the_queue_plays_through_and_is_recorded() {
+    let mut h = Harness::new(3);
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(Message::ViewHistory);
+    h.play_until(|h| h.playing() == Some(2));
+    assert!(h.queue().is_empty());
+    h.play_until(|h| h.app.playing.is_none());
+    assert_eq!(h.app.play_status, PlayStatus::Stopped);
+
+    h.app.history.flush().unwrap();
+    let history = History::from_file(&History::file_path().unwrap()).unwrap();
+    let played = history
+        .records()
+        .iter()
+        .map(|record| (record.track, record.completion))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        played,
+        h.tracks.iter().map(|id| (*id, 1.0)).collect::<Vec<_>>()
+    );
+    // the statistics follow along while the history's open:
+    assert_eq!(h.app.history_stats.tracks.len(), 3);
+    assert!(h.app.history_stats.never_played.is_empty());
+}
+
+#[test]
+fn repeat_one_plays_the_track_again() {
+    let mut h = Harness::new(3);
+    h.send(Message::ToggleRepeat);
+    h.send(QueueMessage::PlayTrack(0));
+    h.play_until(|h| h.app.history.records().len() == 2);
+    assert_eq!(h.playing(), Some(0));
+    assert_eq!(h.queue(), [1, 2]);
+    assert!(h.app.previous.is_empty());
+}
+
+#[test]
+fn skip_back_without_repeat() {
+    let mut h = Harness::new(4);
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.playing(), Some(1));
+    assert_eq!(h.queue(), [2, 3]);
+
+    h.send(QueueMessage::SkipBack);
+    assert_eq!(h.playing(), Some(0));
+    assert_eq!(h.queue(), [1, 2, 3]);
+    assert!(h.app.previous.is_empty());
+
+    // with nothing before it, the track's started again:
+    h.send(QueueMessage::SkipBack);
+    assert_eq!(h.playing(), Some(0));
+    assert_eq!(h.queue(), [1, 2, 3]);
+}
+
+#[test]
+fn skip_back_with_repeat_one() {
+    let mut h = Harness::new(4);
+    h.send(Message::ToggleRepeat);
+    assert_eq!(h.app.repeat, RepeatStatus::One);
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.playing(), Some(1));
+    assert_eq!(h.queue(), [2, 3]);
+
+    h.send(QueueMessage::SkipBack);
+    assert_eq!(h.playing(), Some(0));
+    assert_eq!(h.queue(), [1, 2, 3]);
+}
+
+#[test]
+fn skip_back_with_repeat_all() {
+    let mut h = Harness::new(4);
+    h.send(Message::ToggleRepeat);
+    h.send(Message::ToggleRepeat);
+    h.send(QueueMessage::PlayTrack(0));
+    assert_eq!(h.queue(), [1, 2, 3]);
+
+    // the end of the queue comes before the first track:
+    h.send(QueueMessage::SkipBack);
+    assert_eq!(h.playing(), Some(3));
+    assert_eq!(h.queue(), [0, 1, 2]);
+
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.playing(), Some(0));
+    assert_eq!(h.queue(), [1, 2, 3]);
+
+    // the track skipped back to isn't left queued at the end as well:
+    h.send(QueueMessage::SkipBack);
+    assert_eq!(h.playing(), Some(3));
+    assert_eq!(h.queue(), [0, 1, 2]);
+}
+
+#[test]
+fn swapping_playlist_tracks_saves_the_playlist() {
+    let mut h = Harness::new(3);
+    let id = h.add_playlist("mix.toml", &[0, 1, 2]);
+    let path = h
+        .app
+        .playlists
+        .get_playlist(id)
+        .unwrap()
+        .file_path()
+        .unwrap();
+    h.send(Message::ViewPlaylist(Some(id)));
+
+    h.send(Message::PlaylistSwap(0, 2));
+    assert_eq!(h.app.playlists.get_playlist(id).unwrap().track_ids(), [
+        h.tracks[2],
+        h.tracks[1],
+        h.tracks[0]
+    ]);
+    let saved = fs::read_to_string(&path).unwrap();
+    let positions =
+        ["2.wav", "1.wav", "0.wav"].map(|name| saved.find(name).unwrap());
+    assert!(positions.is_sorted(), "{saved}");
+
+    // positions past the end are ignored:
+    h.send(Message::PlaylistSwap(0, 3));
+    h.send(Message::PlaylistSwap(3, 0));
+    h.send(Message::PlaylistRemove(3));
+    assert_eq!(
+        h.app.playlists.get_playlist(id).unwrap().track_ids()[0],
+        h.tracks[2]
+    );
+
+    h.send(QueueMessage::PlayList);
+    assert_eq!(h.playing(), Some(2));
+    assert_eq!(h.queue(), [1, 0]);
+}
+
+#[test]
+fn deleting_a_playlist_removes_its_file_and_pin() {
+    let mut h = Harness::new(2);
+    let id = h.add_playlist("mix.toml", &[0, 1]);
+    let path = h
+        .app
+        .playlists
+        .get_playlist(id)
+        .unwrap()
+        .file_path()
+        .unwrap();
+    h.send(Message::PinAdd(PinKind::Playlist, "mix.toml".into()));
+    assert_eq!(h.saved_config().playlists.pins, [PathBuf::from("mix.toml")]);
+    h.send(Message::ViewPlaylist(Some(id)));
+
+    h.send(Message::DeletePlaylist(id));
+    assert!(h.app.playlists.get_playlist(id).is_none());
+    assert!(!path.exists());
+    assert!(h.app.config.playlists.pins.is_empty());
+    assert!(h.saved_config().playlists.pins.is_empty());
+    assert!(matches!(h.app.viewing, Viewing::Playlist(None)));
+
+    // nothing's left to change:
+    h.send(Message::PlaylistSwap(0, 1));
+    h.send(Message::PlaylistRemove(0));
+    h.send(Message::DeletePlaylist(id));
+}
+
+#[test]
+fn adding_to_a_playlist_after_it_or_the_track_has_gone() {
+    let mut h = Harness::new(2);
+    let id = h.add_playlist("mix.toml", &[0]);
+    h.send(Message::SelectPlaylist(h.tracks[1]));
+    h.send(Message::PlaylistSelected(id));
+    assert_eq!(h.app.playlists.get_playlist(id).unwrap().track_ids(), [
+        h.tracks[0],
+        h.tracks[1]
+    ]);
+    assert_eq!(h.app.selecting_playlist, None);
+
+    // the playlist is deleted while the menu's open:
+    h.send(Message::SelectPlaylist(h.tracks[0]));
+    h.send(Message::DeletePlaylist(id));
+    h.send(Message::PlaylistSelected(id));
+    assert!(h.app.playlists.get_playlist(id).is_none());
+    assert_eq!(h.app.selecting_playlist, None);
+
+    // the track leaves the library while the menu's open:
+    let id = h.add_playlist("other.toml", &[]);
+    h.send(Message::SelectPlaylist(h.tracks[0]));
+    h.app.library = Library::new();
+    h.send(Message::PlaylistSelected(id));
+    assert!(h.app.playlists.get_playlist(id).unwrap().tracks.is_empty());
+    assert_eq!(h.app.selecting_playlist, None);
+}
+
+#[test]
+fn pins_are_saved_to_the_config() {
+    let mut h = Harness::new(1);
+    let root = h.app.library.root_directory().path.clone();
+    h.send(Message::PinAdd(PinKind::Library, root.clone()));
+    assert_eq!(h.app.config.library.pins, std::slice::from_ref(&root));
+    assert_eq!(h.saved_config().library.pins, [root]);
+
+    h.add_playlist("mix.toml", &[0]);
+    h.send(Message::PinAdd(PinKind::Playlist, "mix.toml".into()));
+    // a playlist that doesn't exist isn't pinned:
+    h.send(Message::PinAdd(PinKind::Playlist, "gone.toml".into()));
+    assert_eq!(h.saved_config().playlists.pins, [PathBuf::from("mix.toml")]);
+}
+
+#[test]
+fn volume_follows_the_curve_and_the_track_gain() {
+    use iced::keyboard::{Key, Modifiers, key::Named};
+
+    let mut h = Harness::new(2);
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+    h.send(Message::VolumeChanged(0.5));
+    // halfway along the slider is 30 dB down:
+    assert!(close(h.app.sink.volume(), 10f32.powf(-1.5)));
+
+    h.send(Message::KeyPressed(
+        Key::Named(Named::ArrowUp),
+        Modifiers::SHIFT,
+    ));
+    assert!(close(h.app.volume, 0.51));
+    h.send(Message::VolumeScrolled(-2.0));
+    assert!(close(h.app.volume, 0.47));
+    h.send(Message::VolumeChanged(1.0));
+    assert!(close(h.app.sink.volume(), 1.0));
+
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(Message::SetGain(GainTarget::Track(h.tracks[0]), -6.0));
+    assert!(close(h.app.sink.volume(), 10f32.powf(-6.0 / 20.0)));
+    // offsets only go so far:
+    h.send(Message::SetGain(GainTarget::Track(h.tracks[0]), -40.0));
+    assert_eq!(
+        h.app.user_data.track(h.tracks[0]).gain,
+        -user_data::MAX_GAIN
+    );
+    // and belong to their own track:
+    h.send(QueueMessage::SkipForward);
+    assert!(close(h.app.sink.volume(), 1.0));
+
+    h.send(Message::ToggleMute);
+    h.send(Message::SaveSession);
+    let misc = h.saved_config().misc;
+    assert_eq!((misc.last_volume, misc.last_mute), (Some(1.0), true));
+}
+
+#[test]
+fn tracks_gone_from_the_library_are_passed_over() {
+    let mut h = Harness::new(3);
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.app.previous, [h.tracks[0]]);
+
+    // one that's queued but missing is skipped rather than played:
+    h.app.queue.insert(0, 1234);
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.playing(), None);
+    h.play_until(|h| h.playing() == Some(2));
+
+    let task = h.app.replace_library(Library::new());
+    h.run(task);
+    assert!(h.app.queue.is_empty());
+    assert!(h.app.previous.is_empty());
+    h.send(QueueMessage::SkipBack);
+}
+
+#[test]
+fn library_folders_are_kept_when_others_are_used() {
+    use config::LibraryRoot;
+
+    let mut h = Harness::new(1);
+    let root = h.app.library.root_directory().path.clone();
+    let other = h.dir.join("other");
+    fs::create_dir_all(&other).unwrap();
+
+    // a `--library` folder isn't saved in place of the configured one:
+    h.app.config.library.session_root = Some(other.clone());
+    h.send(Message::PinAdd(PinKind::Library, root.clone()));
+    assert_eq!(h.saved_config().library.roots, [LibraryRoot::new(
+        root.clone()
+    )]);
+
+    // nor is one chosen on the start screen, which is added alongside:
+    let mut start = start_screen::StartScreen::change(
+        other.to_string_lossy().into_owned(),
+        None,
+    );
+    start.lib = Some(Library::new());
+    h.app.start_screen = Some(start);
+    h.send(Message::ScanDone);
+    assert_eq!(h.saved_config().library.roots, [
+        LibraryRoot::new(root),
+        LibraryRoot::new(other)
+    ]);
+    assert_eq!(h.app.config.library.session_root, None);
+    assert_eq!(h.app.library.tracks().len(), 1);
+}
+
+#[test]
+fn dragging_the_playhead_seeks() {
+    let mut h = Harness::new(1);
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(Message::PlayheadMoved(0.5));
+    h.send(Message::PlayheadReleased);
+    h.send(Message::UpdateProgress);
+    let (position, _) = h.app.track_duration.unwrap();
+    assert!(position >= TRACK_LENGTH / 2, "only at {position:?}");
+}
+
+#[test]
+fn only_the_latest_scan_is_used() {
+    let mut h = Harness::new(2);
+    let (_first, second) = {
+        let _guard = h.rt.enter();
+        (h.app.scan_library(true), h.app.scan_library(false))
+    };
+    // the first scan finishing after the second started is ignored:
+    h.send(settings::SettingsMessage::Scanned(
+        1,
+        Some(Box::new(Library::new())),
+    ));
+    assert_eq!(h.app.library.tracks().len(), 2);
+    h.run(second);
+    assert_eq!(h.app.library.tracks().len(), 2);
+}
+
+#[test]
+fn the_chosen_output_is_returned_to() {
+    let mut h = Harness::new(1);
+    assert_eq!(h.app.output.as_ref().unwrap().name(), None);
+    h.app.config.output.device = Some(String::from("Null output"));
+    h.send(Message::CheckOutput);
+    assert_eq!(h.app.output.as_ref().unwrap().name(), Some("Null output"));
+    assert!(!h.app.listing_devices);
+
+    h.send(Message::ViewSettings);
+    assert_eq!(h.app.output_devices, ["Null output"]);
+}
+
+#[test]
+fn a_deeper_track_reopens_the_output_when_bit_perfect() {
+    use rodio::cpal::SampleFormat;
+
+    let mut h = Harness::new(2);
+    let path = h.app.library.get_track(h.tracks[1]).unwrap().path.clone();
+    write_tone_at_depth(&path, 1, 24);
+    h.app.config.output.bit_perfect = true;
+    let sample_format =
+        |h: &Harness| h.app.output.as_ref()?.format().map(|f| f.sample_format);
+
+    h.send(QueueMessage::PlayTrack(0));
+    assert_eq!(sample_format(&h), Some(SampleFormat::I16));
+    // the same rate, but too deep for 16-bit samples:
+    h.send(QueueMessage::SkipForward);
+    assert_eq!(h.playing(), Some(1));
+    assert_eq!(sample_format(&h), Some(SampleFormat::I32));
+}
+
+#[test]
+fn a_playlist_that_cant_be_read_is_left_out() {
+    let mut h = Harness::new(1);
+    let id = h.add_playlist("mix.toml", &[0]);
+    let dir = crate::paths::playlist_dir().unwrap();
+    fs::write(dir.join("saving.toml"), "title = \"Half").unwrap();
+    h.send(Message::PlaylistsChanged);
+    assert!(h.app.playlists.get_playlist(id).is_some());
+    assert_eq!(h.app.playlists.playlists().count(), 1);
+}
+
+#[test]
+fn a_config_written_late_doesnt_replace_a_newer_one() {
+    let mut h = Harness::new(1);
+    h.app.config.misc.startup_volume = 0.25;
+    let older = h.app.write_config();
+    h.app.config.misc.startup_volume = 0.5;
+    let newer = h.app.write_config();
+    h.run(newer);
+    h.run(older);
+    assert_eq!(h.saved_config().misc.startup_volume, 0.5);
+}
+
+#[test]
+fn a_track_of_unknown_length_is_recorded() {
+    let mut h = Harness::new(1);
+    let mut track = h.app.library.get_track(h.tracks[0]).unwrap().clone();
+    track.metadata.duration = None;
+    h.app.library.add_track(track);
+
+    h.send(QueueMessage::PlayTrack(0));
+    h.send(Message::UpdateProgress);
+    assert!(matches!(h.app.track_duration, Some((_, None))));
+    h.play_until(|h| h.app.playing.is_none());
+    let records = h.app.history.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].completion, 1.0);
+    assert!(!records[0].listened.is_zero());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn mpris_requests_control_the_player() {
+    use mpris::Event;
+
+    let mut h = Harness::new(3);
+    h.send(QueueMessage::PlayTrack(0));
+    let state = h.app.mpris_state();
+    assert_eq!(state.status, PlayStatus::Play);
+    assert_eq!(state.track.map(|track| track.id), Some(h.tracks[0]));
+    assert!(state.can_go_next);
+
+    h.send(Event::Next);
+    assert_eq!(h.playing(), Some(1));
+    h.send(Event::Pause);
+    assert_eq!(h.app.play_status, PlayStatus::Pause);
+    // pausing again doesn't toggle it back:
+    h.send(Event::Pause);
+    assert_eq!(h.app.play_status, PlayStatus::Pause);
+    h.send(Event::Play);
+    assert_eq!(h.app.play_status, PlayStatus::Play);
+    h.send(Event::SetRepeat(RepeatStatus::All));
+    assert_eq!(h.app.mpris_state().repeat, RepeatStatus::All);
+    h.send(Event::Previous);
+    assert_eq!(h.playing(), Some(0));
+    h.send(Event::Stop);
+    assert_eq!(h.playing(), None);
+    assert_eq!(h.app.mpris_state().status, PlayStatus::Stopped);
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufReader, Write},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::internal::{Track, library::track_hash};
+
+/// The furthest a gain offset can go either way, in decibels.
+pub const MAX_GAIN: f32 = 12.0;
+
+/// Per-track data set by the user.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TrackData {
+    /// Star rating from 1 - 5, or 0 if unrated.
+    pub rating:   u8,
+    pub favorite: bool,
+    /// How much louder or quieter the track's played, in decibels.
+    pub gain:     f32,
+}
+
+/// What a gain offset is set on.
+#[derive(Clone, Debug)]
+pub enum GainTarget {
+    Track(u64),
+    /// An album, by its `album_key`.
+    Album(String),
+}
+
+/// Identifies the album `track` is on by its title and album artist (or
+/// first artist), or `None` if it isn't on one.
+pub fn album_key(track: &Track) -> Option<String> {
+    let metadata = &track.metadata;
+    let album = metadata.album.as_ref()?;
+    Some(
+        match metadata.album_artist.as_ref().or(metadata.artists.first()) {
+            Some(artist) => format!("{artist} - {album}"),
+            None => album.clone(),
+        },
+    )
+}
+
+impl TrackData {
+    /// Relative likelihood of the track being picked early in a weighted
+    /// shuffle. Unrated tracks are treated as if they were rated 3 stars.
+    pub fn shuffle_weight(&self) -> f64 {
+        let weight = match self.rating {
+            0 => 3.0,
+            rating => rating as f64,
+        };
+        if self.favorite { weight * 2.0 } else { weight }
+    }
+}
+
+/// Data set by the user which isn't part of the library, keyed by
+/// `track_hash` so that it survives the library cache being rebuilt.
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub struct UserData {
+    tracks: HashMap<u64, TrackData>,
+    /// Gain offsets for whole albums, in decibels, by `album_key`.
+    albums: HashMap<String, f32>,
+}
+
+/// User data as it was kept before gain offsets were added.
+#[derive(Deserialize)]
+struct LegacyUserData {
+    tracks: HashMap<u64, LegacyTrackData>,
+}
+
+#[derive(Deserialize)]
+struct LegacyTrackData {
+    rating:   u8,
+    favorite: bool,
+}
+
+impl UserData {
+    pub fn new() -> Self {
+        Self {
+            tracks: HashMap::new(),
+            albums: HashMap::new(),
+        }
+    }
+
+    pub fn file_path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = crate::paths::data_dir()?;
+        // renamed whenever the format changes, so an old file is converted
+        // rather than misread:
+        path.push("userdata-2");
+        Ok(path)
+    }
+
+    fn legacy_file_path() -> Result<PathBuf, Box<dyn Error>> {
+        let mut path = crate::paths::data_dir()?;
+        path.push("userdata");
+        Ok(path)
+    }
+
+    /// Reads the user data, converting it from the older format if it hasn't
+    /// been saved in this one yet.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let path = Self::file_path()?;
+        if path.exists() {
+            return Self::from_file(&path);
+        }
+        let legacy = Self::legacy_file_path()?;
+        if legacy.exists() {
+            return Self::from_legacy_file(&legacy);
+        }
+        Ok(Self::new())
+    }
+
+    pub fn from_file(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        Ok(bincode::serde::decode_from_reader(
+            BufReader::new(File::open(path)?),
+            bincode::config::standard(),
+        )?)
+    }
+
+    fn from_legacy_file(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        let legacy: LegacyUserData = bincode::serde::decode_from_reader(
+            BufReader::new(File::open(path)?),
+            bincode::config::standard(),
+        )?;
+        let tracks = legacy
+            .tracks
+            .into_iter()
+            .map(|(id, data)| {
+                (id, TrackData {
+                    rating:   data.rating,
+                    favorite: data.favorite,
+                    gain:     0.0,
+                })
+            })
+            .collect();
+        Ok(Self {
+            tracks,
+            albums: HashMap::new(),
+        })
+    }
+
+    pub fn write_to_file(&self) -> Result<(), Box<dyn Error>> {
+        let mut f = File::create(Self::file_path()?)?;
+        let data =
+            bincode::serde::encode_to_vec(self, bincode::config::standard())?;
+        Ok(f.write_all(&data)?)
+    }
+
+    pub fn track(&self, id: u64) -> TrackData {
+        self.tracks.get(&id).copied().unwrap_or_default()
+    }
+
+    pub fn track_mut(&mut self, id: u64) -> &mut TrackData {
+        self.tracks.entry(id).or_default()
+    }
+
+    pub fn album_gain(&self, key: &str) -> f32 {
+        self.albums.get(key).copied().unwrap_or_default()
+    }
+
+    pub fn set_gain(&mut self, target: GainTarget, gain: f32) {
+        let gain = gain.clamp(-MAX_GAIN, MAX_GAIN);
+        match target {
+            GainTarget::Track(id) => self.track_mut(id).gain = gain,
+            GainTarget::Album(key) if gain == 0.0 => {
+                self.albums.remove(&key);
+            }
+            GainTarget::Album(key) => {
+                self.albums.insert(key, gain);
+            }
+        }
+    }
+
+    /// The gain offset `track` is played with, its own and its album's
+    /// together, in decibels.
+    pub fn gain(&self, track: &Track) -> f32 {
+        self.track(track_hash(track)).gain
+            + album_key(track).map_or(0.0, |key| self.album_gain(&key))
+    }
+
+    /// Shuffles the given tracks, placing higher-weighted tracks earlier on
+    /// average.
+    pub fn weighted_shuffle(&self, tracks: &mut [u64]) {
+        use rand::Rng;
+
+        // Efraimidis-Spirakis: sort by u^(1/w), where u is uniform in (0, 1].
+        let mut rng = rand::rng();
+        let mut keyed = tracks
+            .iter()
+            .map(|id| {
+                let u: f64 = 1.0 - rng.random::<f64>();
+                (u.powf(1.0 / self.track(*id).shuffle_weight()), *id)
+            })
+            .collect::<Vec<_>>();
+        keyed.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+        tracks
+            .iter_mut()
+            .zip(keyed)
+            .for_each(|(track, (_, id))| *track = id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn older_user_data_is_converted() {
+        #[derive(Serialize)]
+        struct OldTrackData {
+            rating:   u8,
+            favorite: bool,
+        }
+        #[derive(Serialize)]
+        struct OldUserData {
+            tracks: HashMap<u64, OldTrackData>,
+        }
+
+        let old = OldUserData {
+            tracks: HashMap::from([(7, OldTrackData {
+                rating:   4,
+                favorite: true,
+            })]),
+        };
+        let path = std::env::temp_dir()
+            .join(format!("polypody-userdata-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(&old, bincode::config::standard())
+                .unwrap(),
+        )
+        .unwrap();
+        let data = UserData::from_legacy_file(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let track = data.track(7);
+        assert_eq!((track.rating, track.favorite, track.gain), (4, true, 0.0));
+    }
+}
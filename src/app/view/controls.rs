@@ -1,13 +1,49 @@
+use iced::widget::mouse_area;
+
 use super::{column, *};
+use crate::internal::audio::device::DeviceFormat;
 
 impl App {
     pub(super) fn current_track(&self) -> Element {
         column![self.current_title(), self.current_artist(),]
+            .push_maybe(self.output_format().map(|format| {
+                text!("{format}, bit-perfect")
+                    .size(SMALL_TEXT_SIZE)
+                    .center()
+            }))
+            .push_maybe(self.notice().map(|notice| {
+                text(notice)
+                    .size(SMALL_TEXT_SIZE)
+                    .style(text::danger)
+                    .center()
+            }))
             .align_x(iced::Alignment::Center)
             .padding(3)
             .into()
     }
 
+    /// The format the device's playing in, while bit-perfect mode has it open
+    /// to match the current track.
+    fn output_format(&self) -> Option<DeviceFormat> {
+        self.playing.as_ref()?;
+        self.config
+            .output
+            .bit_perfect
+            .then(|| self.output.as_ref()?.format())
+            .flatten()
+    }
+
+    /// What to tell the user about playback, if anything.
+    fn notice(&self) -> Option<&str> {
+        match &self.notice {
+            Some((notice, _)) => Some(notice),
+            None if self.output.is_none() && !self.remote => {
+                Some("No audio output is available")
+            }
+            None => None,
+        }
+    }
+
     fn current_title(&self) -> Element {
         text!(
             "{}",
@@ -37,7 +73,7 @@ impl App {
 
     pub(super) fn progress_bar(&self) -> Element {
         let (current_time, total_duration) = match self.track_duration {
-            Some((current, total)) => (current, total),
+            Some((current, total)) => (current, total.unwrap_or_default()),
             None => (Duration::from_secs(0), Duration::from_secs(0)),
         };
         row![
@@ -97,11 +133,11 @@ impl App {
         .into()
     }
 
-    fn shuffle_button() -> Element<'static> {
+    fn shuffle_button(shuffle: bool) -> Element<'static> {
         control_button!(
             icon: Icon::Shuffle,
-            msg: queue::QueueMessage::Shuffle.into(),
-            style: style::plain_icon_button,
+            msg: queue::QueueMessage::SetShuffle(!shuffle).into(),
+            style: style::toggle_icon_button(shuffle),
         )
         .into()
     }
@@ -124,6 +160,15 @@ impl App {
         .into()
     }
 
+    fn radio_button(radio: bool) -> Element<'static> {
+        control_button!(
+            icon: Icon::Radio,
+            msg: Message::ToggleRadio,
+            style: style::toggle_icon_button(radio),
+        )
+        .into()
+    }
+
     fn repeat_text(repeat: &RepeatStatus) -> Element {
         text(match repeat {
             RepeatStatus::None => "Off",
@@ -160,13 +205,56 @@ impl App {
             .into()
     }
 
+    /// Stands in for the volume slider while bit-perfect mode keeps the
+    /// volume at full.
+    fn fixed_volume() -> Element<'static> {
+        text("Full volume (bit-perfect)")
+            .size(SMALL_TEXT_SIZE)
+            .width(iced::Length::Fill)
+            .into()
+    }
+
+    /// The mute button and volume slider, which the volume can also be
+    /// scrolled on.
+    fn volume_controls(&self) -> Element<'_> {
+        let fixed = self.config.output.bit_perfect && !self.remote;
+        let controls = container(
+            row![
+                Self::volume_button(self.mute, self.volume),
+                if fixed {
+                    Self::fixed_volume()
+                } else {
+                    Self::volume_slider(self.volume)
+                },
+            ]
+            .align_y(iced::Alignment::Center),
+        )
+        .align_y(iced::Alignment::Center)
+        .padding(5)
+        .width(iced::Length::FillPortion(2));
+        if fixed {
+            return controls.into();
+        }
+        mouse_area(controls)
+            .on_scroll(|delta| {
+                use iced::mouse::ScrollDelta;
+
+                // a line is taken to be about 50 pixels of a touchpad:
+                Message::VolumeScrolled(match delta {
+                    ScrollDelta::Lines { y, .. } => y,
+                    ScrollDelta::Pixels { y, .. } => y / 50.0,
+                })
+            })
+            .into()
+    }
+
     pub(super) fn control_bar(&self) -> Element {
         row![
             container(row![
                 fill![Self::back_button()],
                 fill![Self::stop_button()],
                 fill![Self::play_button(&self.play_status)],
-                fill![Self::shuffle_button()],
+                fill![Self::shuffle_button(self.unshuffled.is_some())],
                 fill![Self::forward_button()],
             ])
             .width(iced::Length::FillPortion(3))
@@ -177,6 +265,7 @@ impl App {
                 row![
                     Self::repeat_button(&self.repeat),
                     Self::repeat_text(&self.repeat),
+                    Self::radio_button(self.radio),
                 ]
                 .align_y(iced::Alignment::Center)
             )
@@ -185,16 +274,7 @@ impl App {
             .padding(5)
             .width(iced::Length::FillPortion(2)),
             Space::with_width(iced::Length::FillPortion(4)),
-            container(
-                row![
-                    Self::volume_button(self.mute, self.volume),
-                    Self::volume_slider(self.volume),
-                ]
-                .align_y(iced::Alignment::Center)
-            )
-            .align_y(iced::Alignment::Center)
-            .padding(5)
-            .width(iced::Length::FillPortion(2))
+            self.volume_controls(),
         ]
         .height(iced::Length::Shrink)
         .padding(5)
@@ -0,0 +1,216 @@
+use iced::widget::horizontal_space;
+
+use super::{column, *};
+use crate::app::history::{self, Period};
+
+const RECENT_LEN: usize = 20;
+const NEVER_PLAYED_LEN: usize = 100;
+
+impl App {
+    fn history_header_view(&self) -> Element {
+        let periods = Period::ALL
+            .into_iter()
+            .map(|period| {
+                button(text!("{}", period).size(TEXT_SIZE))
+                    .on_press(Message::HistoryPeriod(period))
+                    .style(style::toggle_icon_button(
+                        period == self.history_period,
+                    ))
+                    .into()
+            })
+            .collect::<Vec<_>>();
+
+        container(column![
+            text("History").size(20),
+            horizontal_space().height(5),
+            row(periods),
+            text!(
+                "Listening time: {}",
+                print_duration(&self.history_stats.listening_time)
+            )
+            .size(SMALL_TEXT_SIZE),
+        ])
+        .width(iced::Length::Fill)
+        .height(148)
+        .padding(20)
+        .style(|theme: &iced::Theme| {
+            let palette = theme.extended_palette();
+
+            container::Style {
+                text_color: Some(palette.background.base.text),
+                background: Some(palette.background.base.color.into()),
+                ..container::Style::default()
+            }
+        })
+        .into()
+    }
+
+    /// A row in one of the history lists. If `track` is given, hovering over
+    /// the row reveals a button to add the track to the queue.
+    fn history_row<'a>(
+        name: String,
+        detail: String,
+        stat: String,
+        track: Option<u64>,
+    ) -> Element<'a> {
+        let item = container(
+            row![
+                text(name)
+                    .size(TEXT_SIZE)
+                    .width(iced::Length::FillPortion(10)),
+                text(detail)
+                    .size(TEXT_SIZE)
+                    .width(iced::Length::FillPortion(10)),
+                text(stat)
+                    .size(TEXT_SIZE)
+                    .width(iced::Length::FillPortion(4)),
+            ]
+            .height(iced::Length::Fill)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(iced::Padding {
+            left: 10.0,
+            ..iced::Padding::default()
+        })
+        .height(36);
+
+        match track {
+            Some(id) => iced::widget::hover(
+                item,
+                container(control_button!(
+                    icon: Icon::Plus,
+                    msg: queue::QueueMessage::Append(id).into(),
+                    style: style::plain_icon_button_with_colors(
+                        iced::Color::parse("#242226").map(|c| c.into()),
+                        None
+                    ),
+                ))
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+                .align_x(iced::Alignment::End)
+                .align_y(iced::Alignment::Center),
+            ),
+            None => item.into(),
+        }
+    }
+
+    fn history_track_row<'a>(&self, id: u64, stat: String) -> Element<'a> {
+        match self.library.get_track(id) {
+            Some(track) => Self::history_row(
+                track.metadata.title.clone().unwrap_or_else(|| {
+                    track.path.file_name().unwrap().to_str().unwrap().to_owned()
+                }),
+                print_artists(&track.metadata.artists),
+                stat,
+                Some(id),
+            ),
+            None => Self::history_row(
+                String::from("(missing track)"),
+                String::new(),
+                stat,
+                None,
+            ),
+        }
+    }
+
+    pub(super) fn history_view(&self) -> Element {
+        let stats = &self.history_stats;
+        let mut contents = vec![];
+
+        contents.push(Self::section_header("Recently played"));
+        let now = history::now();
+        self.history
+            .records()
+            .iter()
+            .rev()
+            .take(RECENT_LEN)
+            .for_each(|record| {
+                contents.push(self.history_track_row(
+                    record.track,
+                    format!(
+                        "{} ({:.0}%), {}",
+                        print_duration(&record.listened),
+                        record.completion * 100.0,
+                        print_elapsed(now.saturating_sub(record.started)),
+                    ),
+                ));
+            });
+
+        contents.push(Self::section_header("Most played tracks"));
+        stats.tracks.iter().for_each(|(id, count)| {
+            contents.push(self.history_track_row(*id, print_plays(*count)));
+        });
+
+        contents.push(Self::section_header("Most played albums"));
+        stats.albums.iter().for_each(|(album, count)| {
+            contents.push(Self::history_row(
+                album.clone(),
+                String::new(),
+                print_plays(*count),
+                None,
+            ));
+        });
+
+        contents.push(Self::section_header("Most played artists"));
+        stats.artists.iter().for_each(|(artist, count)| {
+            contents.push(Self::history_row(
+                artist.clone(),
+                String::new(),
+                print_plays(*count),
+                None,
+            ));
+        });
+
+        contents.push(Self::section_header("Never played"));
+        contents.push(Self::history_row(
+            format!("{} tracks", stats.never_played.len()),
+            String::new(),
+            String::new(),
+            None,
+        ));
+        stats
+            .never_played
+            .iter()
+            .take(NEVER_PLAYED_LEN)
+            .for_each(|id| {
+                contents.push(self.history_track_row(*id, String::new()));
+            });
+
+        container(column![
+            self.history_header_view(),
+            scrollable(column(contents))
+                .direction(scrollable::Direction::Vertical(
+                    scrollable::Scrollbar::default()
+                ))
+                .id(scrollable::Id::new("history"))
+                .spacing(0)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+        ])
+        .style(style::track_list_container)
+        .padding(2)
+        .width(iced::Length::FillPortion(10))
+        .height(iced::Length::Fill)
+        .into()
+    }
+}
+
+fn print_plays(count: usize) -> String {
+    if count == 1 {
+        String::from("1 play")
+    } else {
+        format!("{count} plays")
+    }
+}
+
+fn print_elapsed(secs: u64) -> String {
+    const MIN: u64 = 60;
+    const HOUR: u64 = MIN * 60;
+    const DAY: u64 = HOUR * 24;
+    match secs {
+        0..MIN => String::from("just now"),
+        MIN..HOUR => format!("{}m ago", secs / MIN),
+        HOUR..DAY => format!("{}h ago", secs / HOUR),
+        _ => format!("{}d ago", secs / DAY),
+    }
+}
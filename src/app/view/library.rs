@@ -35,7 +35,7 @@ impl App {
                 .width(CONTROL_BUTTON_SIZE + 10)
                 .align_x(iced::Alignment::Start)
                 .align_y(iced::Alignment::Center),
-                text!("{}", &dir.path.file_name().unwrap().to_str().unwrap())
+                text!("{}", dir.name())
                     .size(TEXT_SIZE)
                     .align_x(iced::Alignment::Start)
                     .align_y(iced::Alignment::Center)
@@ -94,7 +94,7 @@ impl App {
                         .unwrap_or_else(||
                             text!("").size(TEXT_SIZE).into(),
                         ),
-                    text!("{}", dir.path.file_name().unwrap().to_str().unwrap())
+                    text!("{}", dir.name())
                         .size(20)
                         .align_x(iced::Alignment::Start),
                     row![
@@ -109,11 +109,24 @@ impl App {
                             style: style::plain_icon_button,
                         ),
                         control_button!(
-                            icon: Icon::Pin,
-                            msg: Message::PinAdd(PinKind::Library, dir.path.clone()),
+                            icon: Icon::ListStart,
+                            msg: queue::QueueMessage::Insert(0, dir.tracks.clone()).into(),
                             style: style::plain_icon_button,
-                        )
+                        ),
+                        control_button!(
+                            icon: Icon::ListEnd,
+                            msg: queue::QueueMessage::Extend(dir.tracks.clone()).into(),
+                            style: style::plain_icon_button,
+                        ),
                     ]
+                        // the virtual folder holding several roots can't be pinned:
+                        .push_maybe((!dir.path.as_os_str().is_empty()).then(||
+                            control_button!(
+                                icon: Icon::Pin,
+                                msg: Message::PinAdd(PinKind::Library, dir.path.clone()),
+                                style: style::plain_icon_button,
+                            )
+                        ))
                 ]
                     .padding(5)
             ]
@@ -133,25 +146,49 @@ impl App {
             .into()
     }
 
-    pub(super) fn tracks_header(draw: bool) -> Element<'static> {
+    fn tracks_header_button(
+        &self,
+        label: &'static str,
+        column: SortColumn,
+        portion: u16,
+    ) -> Element<'static> {
+        let indicator = match self.track_sort {
+            Some((sorted, false)) if sorted == column => {
+                char::from(Icon::ChevronUp)
+            }
+            Some((sorted, true)) if sorted == column => {
+                char::from(Icon::ChevronDown)
+            }
+            _ => ' ',
+        };
+        button(
+            row![
+                text(label).size(TEXT_SIZE),
+                text!(" {}", indicator).font(ICON_FONT).size(TEXT_SIZE),
+            ]
+            .align_y(iced::Alignment::Center),
+        )
+        .on_press(Message::SortTracks(column))
+        .padding(0)
+        .style(style::plain_icon_button)
+        .width(iced::Length::FillPortion(portion))
+        .into()
+    }
+
+    pub(super) fn tracks_header(&self, draw: bool) -> Element<'static> {
         container(if draw {
             row![
-                text!("#    ")
-                    .size(TEXT_SIZE)
-                    .width(iced::Length::FillPortion(2))
-                    .align_x(iced::Alignment::End),
-                text!("Title")
-                    .size(TEXT_SIZE)
-                    .width(iced::Length::FillPortion(10))
-                    .align_x(iced::Alignment::Start),
-                text!("Album")
-                    .size(TEXT_SIZE)
-                    .width(iced::Length::FillPortion(10))
-                    .align_x(iced::Alignment::Start),
-                text!("Duration")
-                    .size(TEXT_SIZE)
-                    .width(iced::Length::FillPortion(4))
-                    .align_x(iced::Alignment::Start),
+                container(self.tracks_header_button(
+                    "#",
+                    SortColumn::Number,
+                    1
+                ))
+                .align_x(iced::Alignment::End)
+                .width(iced::Length::FillPortion(2)),
+                self.tracks_header_button("Title", SortColumn::Title, 10),
+                self.tracks_header_button("Album", SortColumn::Album, 10),
+                self.tracks_header_button("Rating", SortColumn::Rating, 4),
+                self.tracks_header_button("Duration", SortColumn::Duration, 4),
             ]
             .into()
         } else {
@@ -182,26 +219,34 @@ impl App {
             .into_iter()
             .map(|dir| Self::library_subdir_view(dir));
 
-        let tracks = dir
-            .tracks
-            .iter()
+        let tracks = self
+            .displayed_tracks()
+            .into_iter()
             .map(|id| unsafe {
-                (*id, self.library.get_track(*id).unwrap_unchecked())
+                (id, self.library.get_track(id).unwrap_unchecked())
             })
             .collect::<Vec<_>>();
 
-        let track_items = tracks
-            .into_iter()
-            .enumerate()
-            .map(|(i, (id, track))| Self::track_view(track, id, i + 1, false));
+        let track_items =
+            tracks.into_iter().enumerate().map(|(i, (id, track))| {
+                Self::track_view(
+                    track,
+                    id,
+                    i + 1,
+                    false,
+                    self.user_data.track(id),
+                    self.album_gain(track),
+                )
+            });
 
         let main_elem = container(column![
             Self::library_header_view(dir),
+            self.search_bar(),
             scrollable(column(
                 dir_items
-                    .chain(std::iter::once(Self::tracks_header(
-                        !track_items.is_empty()
-                    )))
+                    .chain(std::iter::once(
+                        self.tracks_header(!track_items.is_empty())
+                    ))
                     .chain(track_items)
             ))
             .direction(scrollable::Direction::Vertical(
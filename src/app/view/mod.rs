@@ -9,12 +9,17 @@ use iced::{
 };
 
 use super::*;
-use crate::internal::{Directory, Track};
+use crate::{
+    app::user_data::{GainTarget, TrackData},
+    internal::{Directory, Track},
+};
 
 mod controls;
+mod history;
 mod library;
 mod playlist;
 pub mod queue;
+pub mod settings;
 pub mod sidebar;
 pub mod start_screen;
 mod style;
@@ -39,17 +44,26 @@ enum Icon {
     DiscAlbum,
     FileMusic,
     Folder,
+    Heart,
+    History,
+    ListEnd,
+    ListStart,
+    ListX,
+    Minus,
     Pause,
     Pin,
     PinOff,
     Play,
     Plus,
     Queue,
+    Radio,
     Repeat,
+    Settings,
     Shuffle,
     SkipBack,
     SkipForward,
     Square,
+    Star,
     Trash,
     VolumeMute,
     VolumeLow,
@@ -69,17 +83,26 @@ impl From<Icon> for char {
             Icon::DiscAlbum => '\u{E561}',
             Icon::FileMusic => '\u{E563}',
             Icon::Folder => '\u{E0DB}',
+            Icon::Heart => '\u{E0F6}',
+            Icon::History => '\u{E1F5}',
+            Icon::ListEnd => '\u{E2DF}',
+            Icon::ListStart => '\u{E2E1}',
+            Icon::ListX => '\u{E240}',
+            Icon::Minus => '\u{E120}',
             Icon::Pause => '\u{E132}',
             Icon::Pin => '\u{E259}',
             Icon::PinOff => '\u{E2B6}',
             Icon::Play => '\u{E140}',
             Icon::Plus => '\u{E141}',
             Icon::Queue => '\u{E2E0}',
+            Icon::Radio => '\u{E146}',
             Icon::Repeat => '\u{E14A}',
+            Icon::Settings => '\u{E158}',
             Icon::Shuffle => '\u{E162}',
             Icon::SkipBack => '\u{E163}',
             Icon::SkipForward => '\u{E164}',
             Icon::Square => '\u{E16B}',
+            Icon::Star => '\u{E17A}',
             Icon::Trash => '\u{E18E}',
             Icon::VolumeMute => '\u{E1AC}',
             Icon::VolumeLow => '\u{E1A9}',
@@ -135,6 +158,22 @@ impl App {
         }
     }
 
+    /// A bold header separating the sections of a list.
+    fn section_header(title: &'static str) -> Element<'static> {
+        container(
+            text(title)
+                .font(Font {
+                    weight: iced::font::Weight::Bold,
+                    ..Font::default()
+                })
+                .size(TEXT_SIZE),
+        )
+        .width(iced::Length::Fill)
+        .padding(8)
+        .style(style::tracks_header)
+        .into()
+    }
+
     fn main_screen(&self) -> Element {
         container(
             column![
@@ -142,9 +181,11 @@ impl App {
                     self.sidebar.view(),
                     iced::widget::vertical_space().width(5),
                     match self.viewing {
+                        Viewing::History => self.history_view(),
                         Viewing::Library => self.library_view(),
                         Viewing::Playlist(None) => self.playlist_list_view(),
                         Viewing::Playlist(Some(id)) => self.playlist_view(id),
+                        Viewing::Settings => self.settings_view(),
                     },
                     iced::widget::vertical_space().width(5),
                     self.view_queue(),
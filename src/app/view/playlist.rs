@@ -250,6 +250,17 @@ impl App {
                         msg: queue::QueueMessage::ShuffleList.into(),
                         style: style::plain_icon_button,
                     ),
+                    control_button!(
+                        icon: Icon::ListStart,
+                        msg: queue::QueueMessage::Insert(0, pl.track_ids())
+                            .into(),
+                        style: style::plain_icon_button,
+                    ),
+                    control_button!(
+                        icon: Icon::ListEnd,
+                        msg: queue::QueueMessage::Extend(pl.track_ids()).into(),
+                        style: style::plain_icon_button,
+                    ),
                     control_button!(
                         icon: Icon::Pin,
                         msg: Message::PinAdd(
@@ -280,24 +291,32 @@ impl App {
     pub(super) fn playlist_view(&self, id: u64) -> Element {
         let pl = self.playlists.get_playlist(id).unwrap();
 
-        let mut contents = pl
-            .tracks
-            .iter()
-            .filter_map(|t| match t {
-                PlaylistTrack::Track(id, _) => {
-                    self.library.get_track(*id).map(|track| (*id, track))
-                }
-                PlaylistTrack::Unresolved(_) => None,
+        // reordering and removing go by position in the playlist, so they're
+        // only offered while the playlist isn't sorted or searched:
+        let editable = self.track_sort.is_none() && self.search.is_empty();
+        let mut contents = self
+            .displayed_tracks()
+            .into_iter()
+            .map(|id| unsafe {
+                (id, self.library.get_track(id).unwrap_unchecked())
             })
             .enumerate()
             .map(|(num, (id, track))| {
-                Self::track_view(track, id, num + 1, true)
+                Self::track_view(
+                    track,
+                    id,
+                    num + 1,
+                    editable,
+                    self.user_data.track(id),
+                    self.album_gain(track),
+                )
             })
             .collect::<Vec<_>>();
-        contents.insert(0, Self::tracks_header(!contents.is_empty()));
+        contents.insert(0, self.tracks_header(!contents.is_empty()));
 
         let main_elem = container(column![
             Self::playlist_header_view(pl),
+            self.search_bar(),
             scrollable(column(contents))
                 .direction(scrollable::Direction::Vertical(
                     scrollable::Scrollbar::default()
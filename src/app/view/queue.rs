@@ -1,13 +1,45 @@
+use std::{collections::BTreeSet, ops::Range};
+
+use iced::{
+    keyboard::Modifiers,
+    widget::{horizontal_space, mouse_area},
+};
+
 use super::{column, *};
+use crate::app::shuffle;
 
 #[derive(Clone, Debug)]
 pub enum QueueMessage {
     Append(u64),
+    /// Empties the queue, leaving the current track playing.
+    Clear,
+    /// Tracks are being dragged over the item at this position.
+    DragOver(usize),
+    /// Tracks are no longer being dragged over the queue.
+    DragLeave,
+    /// Appends tracks or folders by path, starting playback if nothing is
+    /// playing.
+    Enqueue(Vec<PathBuf>),
+    Extend(Vec<u64>),
+    /// Inserts tracks at a position in the queue, so `Insert(0, ..)` plays
+    /// them next.
+    Insert(usize, Vec<u64>),
+    ModifiersChanged(Modifiers),
     PlayFolder,
     PlayList,
     PlayTrack(usize),
+    /// The item at this position was pressed, selecting it and starting a
+    /// drag.
+    Press(usize),
+    /// The mouse button was released anywhere in the window.
+    Release,
     Remove(usize),
-    Shuffle,
+    RemoveAbove(usize),
+    RemoveBelow(usize),
+    RemoveSelected,
+    /// Turns shuffle on or off, putting the queue back in order when it's
+    /// turned off.
+    SetShuffle(bool),
     ShuffleFolder,
     ShuffleList,
     SkipBack,
@@ -16,41 +48,91 @@ pub enum QueueMessage {
     Swap(usize, usize),
 }
 
+/// Which items in the queue are selected, and any drag in progress. Positions
+/// are only meaningful until the queue next changes, when it's cleared.
+#[derive(Default)]
+pub struct QueueState {
+    selected: BTreeSet<usize>,
+    /// Where a range selected with shift starts.
+    anchor: Option<usize>,
+    /// The item the mouse was pressed on, if it's still held.
+    dragging: Option<usize>,
+    drop_target: Option<usize>,
+    modifiers: Modifiers,
+}
+
+impl QueueState {
+    pub fn clear(&mut self) {
+        self.selected.clear();
+        self.anchor = None;
+        self.dragging = None;
+        self.drop_target = None;
+    }
+
+    pub fn has_selection(&self) -> bool {
+        !self.selected.is_empty()
+    }
+}
+
 impl Into<Message> for QueueMessage {
     fn into(self) -> Message {
         Message::Queue(self)
     }
 }
 
+/// How many of the previous tracks are listed above the queue.
+const SHOWN_PREVIOUS: usize = 5;
+
 impl App {
-    fn queue_item(num: usize, track: &Track) -> Element {
-        iced::widget::hover(
-            container(column![
-                text!(
-                    "{}",
-                    track.metadata.title.as_deref().unwrap_or(
-                        track.path.file_name().unwrap().to_str().unwrap()
-                    )
+    /// A track's title and artists, faded by `alpha`.
+    fn queue_track_info(track: &Track, alpha: f32) -> Element {
+        container(column![
+            text!(
+                "{}",
+                track.metadata.title.as_deref().unwrap_or(
+                    track.path.file_name().unwrap().to_str().unwrap()
                 )
-                .size(TEXT_SIZE),
-                text!("{}", print_artists(&track.metadata.artists))
-                    .size(TEXT_SIZE)
-                    .style(|theme: &iced::Theme| {
-                        text::Style {
-                            color: Some(
-                                theme
-                                    .extended_palette()
-                                    .background
-                                    .base
-                                    .text
-                                    .scale_alpha(0.5),
-                            ),
-                        }
-                    }),
-            ])
-            .padding(3)
-            .width(iced::Length::Fill)
-            .height(48),
+            )
+            .size(TEXT_SIZE)
+            .style(move |theme: &iced::Theme| text::Style {
+                color: Some(
+                    theme
+                        .extended_palette()
+                        .background
+                        .base
+                        .text
+                        .scale_alpha(alpha),
+                ),
+            }),
+            text!("{}", print_artists(&track.metadata.artists))
+                .size(TEXT_SIZE)
+                .style(move |theme: &iced::Theme| {
+                    text::Style {
+                        color: Some(
+                            theme
+                                .extended_palette()
+                                .background
+                                .base
+                                .text
+                                .scale_alpha(0.5 * alpha),
+                        ),
+                    }
+                }),
+        ])
+        .padding(3)
+        .width(iced::Length::Fill)
+        .height(48)
+        .into()
+    }
+
+    fn queue_item(
+        num: usize,
+        track: &Track,
+        selected: bool,
+        drop_target: bool,
+    ) -> Element {
+        let item = iced::widget::hover(
+            Self::queue_track_info(track, 1.0),
             container(
                 row![
                     column![
@@ -91,27 +173,106 @@ impl App {
             })
             .width(iced::Length::Fill)
             .height(iced::Length::Fill),
+        );
+
+        iced_aw::ContextMenu::new(
+            mouse_area(
+                container(item).style(style::queue_item(selected, drop_target)),
+            )
+            .on_press(QueueMessage::Press(num).into())
+            .on_enter(QueueMessage::DragOver(num).into()),
+            move || {
+                container(column(
+                    [
+                        ("Remove", QueueMessage::Remove(num)),
+                        ("Remove all above", QueueMessage::RemoveAbove(num)),
+                        ("Remove all below", QueueMessage::RemoveBelow(num)),
+                        ("Clear queue", QueueMessage::Clear),
+                    ]
+                    .map(|(label, msg)| {
+                        button(label)
+                            .on_press(msg.into())
+                            .width(iced::Length::Fill)
+                            .style(style::list_button)
+                            .into()
+                    }),
+                ))
+                .padding(2)
+                .width(144)
+                .style(style::context_menu)
+                .into()
+            },
         )
+        .into()
     }
 
     pub(super) fn view_queue(&self) -> Element {
         let mut contents = vec![];
 
-        let queue = self
+        // only the last few previous tracks are shown, to keep what's coming
+        // up in view:
+        let previous = self
+            .previous
+            .iter()
+            .skip(self.previous.len().saturating_sub(SHOWN_PREVIOUS))
+            .filter_map(|id| self.library.get_track(*id))
+            .map(|track| Self::queue_track_info(track, 0.5))
+            .collect::<Vec<_>>();
+
+        let state = &self.queue_state;
+        let mut queue = self
             .queue
             .iter()
             .enumerate()
             .map(|(i, track)| {
                 let track = self.library.get_track(*track).unwrap();
-                Self::queue_item(i, track)
+                Self::queue_item(
+                    i,
+                    track,
+                    state.selected.contains(&i),
+                    state.dragging.is_some_and(|from| from != i)
+                        && state.drop_target == Some(i),
+                )
             })
             .collect::<Vec<_>>();
+        if !previous.is_empty() {
+            queue.splice(
+                0..0,
+                std::iter::once(Self::section_header("Played"))
+                    .chain(previous)
+                    .chain(std::iter::once(Self::section_header("Up next"))),
+            );
+        }
         contents.push(
-            container(scrollable(column(queue)))
-                .style(style::track_list_container)
-                .width(iced::Length::Fill)
-                .height(iced::Length::Fill)
-                .into(),
+            container(
+                row![
+                    text("Queue").size(TEXT_SIZE),
+                    horizontal_space(),
+                    icon_button(Icon::ListX, CONTROL_BUTTON_SIZE / 2)
+                        .style(style::plain_icon_button)
+                        .on_press_maybe(
+                            (!self.queue.is_empty())
+                                .then(|| QueueMessage::Clear.into())
+                        ),
+                ]
+                .align_y(iced::Alignment::Center),
+            )
+            .padding(iced::Padding {
+                left: 8.0,
+                ..iced::Padding::default()
+            })
+            .style(style::tracks_header)
+            .into(),
+        );
+        contents.push(
+            container(
+                mouse_area(scrollable(column(queue)))
+                    .on_exit(QueueMessage::DragLeave.into()),
+            )
+            .style(style::track_list_container)
+            .width(iced::Length::Fill)
+            .height(iced::Length::Fill)
+            .into(),
         );
 
         column(contents)
@@ -120,40 +281,169 @@ impl App {
             .into()
     }
 
+    /// Replaces the queue with `tracks`, shuffling all but the first `keep` of
+    /// them if shuffle is on.
+    fn replace_queue(&mut self, tracks: Vec<u64>, keep: usize) {
+        let shuffle = self.unshuffled.is_some();
+        if shuffle {
+            self.unshuffled = Some(tracks.clone());
+        }
+        self.queue = tracks;
+        if shuffle {
+            self.shuffle_queue(keep);
+        }
+    }
+
+    /// Shuffles the queue from position `from` on.
+    fn shuffle_queue(&mut self, from: usize) {
+        let weights = self
+            .config
+            .ratings
+            .weighted_shuffle
+            .then_some(&self.user_data);
+        let from = from.min(self.queue.len());
+        shuffle::shuffle(
+            &mut self.queue[from..],
+            self.config.shuffle.strategy,
+            &self.library,
+            weights,
+            &self.previous,
+        );
+    }
+
+    /// While shuffle is on, adds tracks which were queued to the queue's
+    /// original order too, at the front or the back.
+    fn queue_unshuffled(&mut self, front: bool, tracks: &[u64]) {
+        let Some(original) = &mut self.unshuffled else {
+            return;
+        };
+        if front {
+            original.splice(0..0, tracks.iter().copied());
+        } else {
+            original.extend_from_slice(tracks);
+        }
+    }
+
     pub fn update_queue(&mut self, msg: QueueMessage) -> Task<Message> {
         fn get_tracks_from_playlist(app: &App, playlist: u64) -> Vec<u64> {
-            app.playlists
-                .get_playlist(playlist)
-                .unwrap()
-                .tracks
-                .iter()
-                .filter_map(|track| match track {
-                    PlaylistTrack::Track(id, _) => Some(*id),
-                    _ => None,
-                })
-                .collect::<Vec<_>>()
+            app.playlists.get_playlist(playlist).unwrap().track_ids()
         }
 
-        fn shuffle_into_queue(queue: &mut Vec<u64>, tracks: &[u64]) {
-            use rand::{rng, seq::SliceRandom};
-
-            queue.resize(tracks.len(), 0);
-            let mut shuffle = (0..tracks.len()).collect::<Vec<usize>>();
-            shuffle.shuffle(&mut rng());
-            for (i, j) in shuffle.into_iter().enumerate() {
-                queue[i] = tracks[j];
+        let state = &mut self.queue_state;
+        match msg {
+            QueueMessage::DragOver(i) => {
+                state.drop_target = Some(i);
+                return Task::none();
+            }
+            QueueMessage::DragLeave => {
+                state.drop_target = None;
+                return Task::none();
+            }
+            QueueMessage::ModifiersChanged(modifiers) => {
+                state.modifiers = modifiers;
+                return Task::none();
             }
+            QueueMessage::Press(i) => {
+                if state.modifiers.shift() {
+                    let anchor = *state.anchor.get_or_insert(i);
+                    state.selected = (anchor.min(i)..=anchor.max(i)).collect();
+                } else if state.modifiers.command() {
+                    if !state.selected.remove(&i) {
+                        state.selected.insert(i);
+                    }
+                    state.anchor = Some(i);
+                } else if !state.selected.contains(&i) {
+                    state.selected = BTreeSet::from([i]);
+                    state.anchor = Some(i);
+                }
+                state.dragging = Some(i);
+                state.drop_target = Some(i);
+                return Task::none();
+            }
+            QueueMessage::Release => {
+                let Some(from) = state.dragging.take() else {
+                    return Task::none();
+                };
+                match state.drop_target.take() {
+                    Some(to) if to != from && to < self.queue.len() => {
+                        // dragging an item that isn't selected moves just it:
+                        let items = if state.selected.contains(&from) {
+                            std::mem::take(&mut state.selected)
+                        } else {
+                            BTreeSet::from([from])
+                        };
+                        if items.contains(&to) {
+                            state.selected = items;
+                            return Task::none();
+                        }
+                        let moved =
+                            move_items(&mut self.queue, &items, to, to > from);
+                        state.anchor = Some(moved.start);
+                        state.selected = moved.collect();
+                    }
+                    // a click on one of several selected items selects just
+                    // it, once it's clear it isn't being dragged:
+                    Some(to) if to == from && state.modifiers.is_empty() => {
+                        state.selected = BTreeSet::from([from]);
+                        state.anchor = Some(from);
+                    }
+                    _ => (),
+                }
+                return Task::none();
+            }
+            QueueMessage::RemoveSelected => {
+                for i in std::mem::take(&mut state.selected).into_iter().rev() {
+                    if i < self.queue.len() {
+                        self.queue.remove(i);
+                    }
+                }
+                state.clear();
+                return Task::none();
+            }
+            // anything else may move tracks around the queue:
+            _ => state.clear(),
         }
 
         match msg {
             QueueMessage::Append(id) => {
+                self.queue_unshuffled(false, &[id]);
                 self.queue.push(id);
                 Task::none()
             }
+            QueueMessage::Clear => {
+                self.queue.clear();
+                if let Some(original) = &mut self.unshuffled {
+                    original.clear();
+                }
+                Task::none()
+            }
+            QueueMessage::Extend(tracks) => {
+                self.queue_unshuffled(false, &tracks);
+                self.queue.extend(tracks);
+                Task::none()
+            }
+            QueueMessage::Insert(i, tracks) => {
+                let i = i.min(self.queue.len());
+                self.queue_unshuffled(i == 0, &tracks);
+                self.queue.splice(i..i, tracks);
+                Task::none()
+            }
+            QueueMessage::Enqueue(paths) => {
+                let tracks = crate::app::resolve_paths(&self.library, &paths);
+                if tracks.is_empty() {
+                    return Task::none();
+                }
+                self.queue_unshuffled(false, &tracks);
+                self.queue.extend(tracks);
+                if self.playing.is_none() {
+                    Task::done(Message::PlayNext)
+                } else {
+                    Task::none()
+                }
+            }
             QueueMessage::PlayFolder => {
-                let tracks = &self.library.current_directory().tracks;
-                self.queue.resize(tracks.len(), 0);
-                self.queue.copy_from_slice(tracks);
+                let tracks = self.library.current_directory().tracks.clone();
+                self.replace_queue(tracks, 0);
                 Task::done(Message::PlayNext)
             }
             QueueMessage::PlayList => {
@@ -161,60 +451,51 @@ impl App {
                     return Task::none();
                 };
                 let tracks = get_tracks_from_playlist(self, list);
-                self.queue.resize(tracks.len(), 0);
-                self.queue.copy_from_slice(&tracks);
+                self.replace_queue(tracks, 0);
                 Task::done(Message::PlayNext)
             }
             QueueMessage::PlayTrack(i) => {
-                self.queue.clear();
-                match self.viewing {
-                    Viewing::Library => {
-                        self.library.current_directory().tracks[i..]
-                            .iter()
-                            .for_each(|track| self.queue.push(*track));
-                        if self.repeat == RepeatStatus::All {
-                            self.library.current_directory().tracks[..i]
-                                .iter()
-                                .for_each(|track| self.queue.push(*track));
-                        }
-                    }
-                    Viewing::Playlist(pl) => unsafe {
-                        let id = pl.unwrap_unchecked();
-                        let pl =
-                            self.playlists.get_playlist(id).unwrap_unchecked();
-                        pl.tracks[i..]
-                            .iter()
-                            .filter_map(|pt| match pt {
-                                PlaylistTrack::Unresolved(_) => None,
-                                PlaylistTrack::Track(id, _) => Some(*id),
-                            })
-                            .for_each(|track| self.queue.push(track));
-                        if self.repeat == RepeatStatus::All {
-                            pl.tracks[..i]
-                                .iter()
-                                .filter_map(|pt| match pt {
-                                    PlaylistTrack::Unresolved(_) => None,
-                                    PlaylistTrack::Track(id, _) => Some(*id),
-                                })
-                                .for_each(|track| self.queue.push(track));
-                        }
-                    },
-                };
+                let tracks = self.displayed_tracks();
+                if i >= tracks.len() {
+                    return Task::none();
+                }
+                let mut queue = tracks[i..].to_vec();
+                if self.repeat == RepeatStatus::All {
+                    queue.extend_from_slice(&tracks[..i]);
+                }
+                // the chosen track plays first even when shuffling:
+                self.replace_queue(queue, 1);
                 Task::done(Message::PlayNext)
             }
             QueueMessage::Remove(i) => {
-                self.queue.remove(i);
+                if i < self.queue.len() {
+                    self.queue.remove(i);
+                }
                 Task::none()
             }
-            QueueMessage::Shuffle => {
-                use rand::{rng, seq::SliceRandom};
-
-                self.queue.shuffle(&mut rng());
+            QueueMessage::RemoveAbove(i) => {
+                self.queue.drain(..i.min(self.queue.len()));
+                Task::none()
+            }
+            QueueMessage::RemoveBelow(i) => {
+                self.queue.truncate(i + 1);
+                Task::none()
+            }
+            QueueMessage::SetShuffle(on) => {
+                if on && self.unshuffled.is_none() {
+                    self.unshuffled = Some(self.queue.clone());
+                    self.shuffle_queue(0);
+                } else if !on && let Some(original) = self.unshuffled.take() {
+                    let current = self.playing.as_ref().map(track_hash);
+                    self.queue =
+                        shuffle::unshuffle(&self.queue, &original, current);
+                }
                 Task::none()
             }
             QueueMessage::ShuffleFolder => {
-                let tracks = &self.library.current_directory().tracks;
-                shuffle_into_queue(&mut self.queue, tracks);
+                let tracks = self.library.current_directory().tracks.clone();
+                self.unshuffled.get_or_insert_default();
+                self.replace_queue(tracks, 0);
                 Task::done(Message::PlayNext)
             }
             QueueMessage::ShuffleList => {
@@ -222,34 +503,58 @@ impl App {
                     return Task::none();
                 };
                 let tracks = get_tracks_from_playlist(self, list);
-                shuffle_into_queue(&mut self.queue, &tracks);
+                self.unshuffled.get_or_insert_default();
+                self.replace_queue(tracks, 0);
                 Task::done(Message::PlayNext)
             }
             QueueMessage::SkipBack => {
-                let Some(playing) = &self.playing else {
-                    return Task::none();
-                };
-                match self.repeat {
-                    RepeatStatus::None | RepeatStatus::One => {
-                        self.sink.try_seek(Duration::from_secs(0));
-                        Task::none()
-                    }
-                    RepeatStatus::All => {
-                        let Some((current, _)) = &self.track_duration else {
-                            return Task::none();
-                        };
-                        if current.as_secs() <= 1 && !self.queue.is_empty() {
-                            let last =
-                                unsafe { self.queue.pop().unwrap_unchecked() };
-                            self.queue.insert(0, last);
-                            self.queue.insert(1, track_hash(playing));
-                            Task::done(Message::PlayNext)
-                        } else {
-                            self.sink.try_seek(Duration::from_secs(0));
-                            Task::none()
+                // past the first second, the current track is restarted:
+                if self
+                    .track_duration
+                    .is_some_and(|(current, _)| current.as_secs() > 1)
+                {
+                    return self.seek_to(Duration::ZERO);
+                }
+                let from_previous = self.previous.back().copied();
+                let back = match from_previous {
+                    Some(id) => {
+                        // with Repeat All, it was also queued again at the end:
+                        if self.repeat == RepeatStatus::All
+                            && self.queue.last() == Some(&id)
+                        {
+                            self.queue.pop();
                         }
+                        Some(id)
                     }
+                    // with Repeat All, the end of the queue comes before the
+                    // current track even if it hasn't been played yet:
+                    None if self.repeat == RepeatStatus::All
+                        && self.playing.is_some() =>
+                    {
+                        self.queue.pop()
+                    }
+                    None => None,
+                };
+                let Some(back) = back else {
+                    return self.seek_to(Duration::ZERO);
+                };
+                if let Some(playing) = &self.playing {
+                    self.queue.insert(0, track_hash(playing));
+                }
+                self.queue.insert(0, back);
+                // the daemon's status shows the track was skipped back to,
+                // and it's taken out of the previous tracks then:
+                #[cfg(unix)]
+                if self.remote {
+                    return Task::done(Message::PlayNext);
                 }
+                if from_previous.is_some() {
+                    self.previous.pop_back();
+                }
+                // stopped first, so the current track isn't remembered as
+                // having been played before this one:
+                self.stop();
+                Task::done(Message::PlayNext)
             }
             QueueMessage::SkipForward => {
                 let Some(playing) = &self.playing else {
@@ -263,15 +568,81 @@ impl App {
             QueueMessage::Stop => {
                 self.play_status = PlayStatus::Stopped;
                 self.queue.clear();
+                if let Some(original) = &mut self.unshuffled {
+                    original.clear();
+                }
                 self.stop();
                 Task::none()
             }
-            QueueMessage::Swap(i, j) => unsafe {
-                if j < self.queue.len() {
-                    self.queue.swap_unchecked(i, j);
+            QueueMessage::Swap(i, j) => {
+                if i < self.queue.len() && j < self.queue.len() {
+                    self.queue.swap(i, j);
                 }
                 Task::none()
-            },
+            }
+            // handled above:
+            QueueMessage::DragOver(_)
+            | QueueMessage::DragLeave
+            | QueueMessage::ModifiersChanged(_)
+            | QueueMessage::Press(_)
+            | QueueMessage::Release
+            | QueueMessage::RemoveSelected => Task::none(),
         }
     }
 }
+
+/// Keeps track of the modifier keys, which change how queue items are
+/// selected, and of the mouse button being released, which ends a drag
+/// wherever it happens.
+pub fn subscription() -> iced::Subscription<Message> {
+    iced::event::listen_with(|event, _, _| match event {
+        iced::Event::Keyboard(iced::keyboard::Event::ModifiersChanged(
+            modifiers,
+        )) => Some(QueueMessage::ModifiersChanged(modifiers).into()),
+        iced::Event::Mouse(iced::mouse::Event::ButtonReleased(
+            iced::mouse::Button::Left,
+        )) => Some(QueueMessage::Release.into()),
+        _ => None,
+    })
+}
+
+/// Moves the tracks at the positions in `items` next to the one at `target`,
+/// after it if `after` is set, keeping them in order. Returns where they end
+/// up.
+fn move_items(
+    queue: &mut Vec<u64>,
+    items: &BTreeSet<usize>,
+    target: usize,
+    after: bool,
+) -> Range<usize> {
+    let moved = items.iter().map(|&i| queue[i]).collect::<Vec<_>>();
+    let mut i = 0;
+    queue.retain(|_| {
+        i += 1;
+        !items.contains(&(i - 1))
+    });
+    let at = target - items.range(..target).count() + usize::from(after);
+    queue.splice(at..at, moved);
+    at..at + items.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn selected_tracks_move_together() {
+        let mut queue = vec![0, 1, 2, 3, 4];
+        let moved = move_items(&mut queue, &BTreeSet::from([0, 2]), 3, true);
+        assert_eq!(queue, [1, 3, 0, 2, 4]);
+        assert_eq!(moved, 2..4);
+
+        let moved = move_items(&mut queue, &BTreeSet::from([4]), 1, false);
+        assert_eq!(queue, [1, 4, 3, 0, 2]);
+        assert_eq!(moved, 1..2);
+
+        let moved = move_items(&mut queue, &BTreeSet::from([0, 1]), 4, true);
+        assert_eq!(queue, [3, 0, 2, 1, 4]);
+        assert_eq!(moved, 3..5);
+    }
+}
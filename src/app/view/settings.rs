@@ -0,0 +1,600 @@
+use iced::widget::{
+    checkbox, horizontal_space, pick_list, text_input, vertical_space,
+};
+
+use super::{column, *};
+use crate::app::{
+    config::LibraryRoot, output::Device, radio::Relation, shuffle::Strategy,
+};
+
+#[derive(Default)]
+pub struct Settings {
+    new_root: String,
+    /// Why `new_root` can't be added, checked as it's typed.
+    new_root_error: Option<&'static str>,
+    scanning: bool,
+    /// Counts the scans started, so that only the latest one's result is used.
+    scan_generation: u64,
+}
+
+#[derive(Clone, Debug)]
+pub enum SettingsMessage {
+    AddRoot,
+    ChangeLibrary,
+    /// The devices to offer, listed in the background.
+    DevicesListed(Vec<String>),
+    NewRootChanged(String),
+    OpenRootDialog,
+    OutputDevice(Device),
+    RemoveRoot(usize),
+    Rescan,
+    RootSelected(Option<rfd::FileHandle>),
+    /// A background scan finished, or failed if there's no library. Scans are
+    /// numbered by `Settings::scan_generation`.
+    Scanned(u64, Option<Box<Library>>),
+    ShuffleStrategy(Strategy),
+    StartupVolume(f32),
+    ToggleBitPerfect(bool),
+    ToggleFullRescan(bool),
+    ToggleRememberVolume(bool),
+    ToggleRadioRelation(Relation, bool),
+    ToggleRoot(usize),
+    ToggleWeightedShuffle(bool),
+    ToggleWriteRatings(bool),
+    WriteConfig,
+}
+
+impl Into<Message> for SettingsMessage {
+    fn into(self) -> Message {
+        Message::Settings(self)
+    }
+}
+
+/// Checks that `path` can be added as a library folder alongside `roots`.
+fn validate_root(
+    path: &str,
+    roots: &[LibraryRoot],
+) -> Result<PathBuf, &'static str> {
+    let path = PathBuf::from(path);
+    if !path.is_absolute() {
+        return Err("The path must be absolute.");
+    }
+    if !path.is_dir() {
+        return Err("There's no folder at this path.");
+    }
+    if roots.iter().any(|root| root.path == path) {
+        return Err("This folder is already in the library.");
+    }
+    if roots.iter().any(|root| {
+        path.starts_with(&root.path) || root.path.starts_with(&path)
+    }) {
+        return Err("This folder overlaps with another library folder.");
+    }
+    Ok(path)
+}
+
+impl App {
+    /// Scans the enabled library folders in the background: from scratch if
+    /// `full` is set, or else only for changes to the current library.
+    pub fn scan_library(&mut self, full: bool) -> Task<Message> {
+        self.settings.scanning = true;
+        self.settings.scan_generation += 1;
+        let generation = self.settings.scan_generation;
+        let roots = self.config.library.enabled_roots();
+        let cached = (!full).then(|| self.library.clone());
+        Task::perform(
+            tokio::task::spawn_blocking(move || match cached {
+                Some(lib) => crate::internal::partial_scan(&roots, lib),
+                None => crate::internal::scan(&roots),
+            }),
+            move |result| {
+                SettingsMessage::Scanned(
+                    generation,
+                    result
+                        .inspect_err(|e| eprintln!("Scan failed: {e}"))
+                        .ok()
+                        .map(Box::new),
+                )
+                .into()
+            },
+        )
+    }
+
+    pub fn update_settings(&mut self, msg: SettingsMessage) -> Task<Message> {
+        match msg {
+            SettingsMessage::AddRoot => {
+                let Ok(path) = validate_root(
+                    &self.settings.new_root,
+                    &self.config.library.roots,
+                ) else {
+                    return Task::none();
+                };
+                self.config.library.roots.push(LibraryRoot::new(path));
+                self.config.library.session_root = None;
+                self.settings.new_root.clear();
+                self.settings.new_root_error = None;
+                Task::batch([self.write_config(), self.scan_library(false)])
+            }
+            SettingsMessage::ChangeLibrary => {
+                let (path, replacing) = match &self.config.library.roots[..] {
+                    [root] => {
+                        (root.path.to_string_lossy().into_owned(), Some(0))
+                    }
+                    _ => (String::new(), None),
+                };
+                self.start_screen =
+                    Some(start_screen::StartScreen::change(path, replacing));
+                Task::none()
+            }
+            SettingsMessage::DevicesListed(names) => {
+                self.output_devices = names;
+                Task::none()
+            }
+            SettingsMessage::NewRootChanged(s) => {
+                self.settings.new_root_error = if s.is_empty() {
+                    None
+                } else {
+                    validate_root(&s, &self.config.library.roots).err()
+                };
+                self.settings.new_root = s;
+                Task::none()
+            }
+            SettingsMessage::OpenRootDialog => {
+                Task::perform(rfd::AsyncFileDialog::new().pick_folder(), |fh| {
+                    SettingsMessage::RootSelected(fh).into()
+                })
+            }
+            SettingsMessage::OutputDevice(device) => {
+                let name = device.name().map(String::from);
+                if name == self.config.output.device {
+                    return Task::none();
+                }
+                self.switch_output(name.as_deref());
+                self.config.output.device = name;
+                self.write_config()
+            }
+            SettingsMessage::RemoveRoot(i) => {
+                if i >= self.config.library.roots.len() {
+                    return Task::none();
+                }
+                self.config.library.roots.remove(i);
+                self.config.library.session_root = None;
+                Task::batch([self.write_config(), self.scan_library(false)])
+            }
+            SettingsMessage::Rescan => self.scan_library(true),
+            SettingsMessage::RootSelected(fh) => {
+                let Some(fh) = fh else {
+                    return Task::none();
+                };
+                Task::done(
+                    SettingsMessage::NewRootChanged(
+                        fh.path().to_string_lossy().into_owned(),
+                    )
+                    .into(),
+                )
+            }
+            SettingsMessage::Scanned(generation, lib) => {
+                // a newer scan has started since, which will have a more up
+                // to date library:
+                if generation != self.settings.scan_generation {
+                    return Task::none();
+                }
+                self.settings.scanning = false;
+                match lib {
+                    Some(lib) => self.replace_library(*lib),
+                    None => Task::none(),
+                }
+            }
+            SettingsMessage::StartupVolume(val) => {
+                self.config.misc.startup_volume = val;
+                Task::none()
+            }
+            SettingsMessage::ToggleBitPerfect(val) => {
+                self.config.output.bit_perfect = val;
+                let device = self.config.output.device.clone();
+                self.switch_output(device.as_deref());
+                self.write_config()
+            }
+            SettingsMessage::ToggleFullRescan(val) => {
+                self.config.library.full_rescan_on_start = val;
+                self.write_config()
+            }
+            SettingsMessage::ToggleRememberVolume(val) => {
+                self.config.misc.remember_volume = val;
+                self.write_config()
+            }
+            SettingsMessage::ToggleRoot(i) => {
+                let Some(root) = self.config.library.roots.get_mut(i) else {
+                    return Task::none();
+                };
+                root.enabled = !root.enabled;
+                self.config.library.session_root = None;
+                Task::batch([self.write_config(), self.scan_library(false)])
+            }
+            SettingsMessage::ShuffleStrategy(strategy) => {
+                self.config.shuffle.strategy = strategy;
+                self.write_config()
+            }
+            SettingsMessage::ToggleRadioRelation(relation, val) => {
+                // kept in the same order however they're toggled:
+                let relations = &self.config.radio.relations;
+                self.config.radio.relations = Relation::ALL
+                    .into_iter()
+                    .filter(|r| {
+                        if *r == relation {
+                            val
+                        } else {
+                            relations.contains(r)
+                        }
+                    })
+                    .collect();
+                self.write_config()
+            }
+            SettingsMessage::ToggleWeightedShuffle(val) => {
+                self.config.ratings.weighted_shuffle = val;
+                self.write_config()
+            }
+            SettingsMessage::ToggleWriteRatings(val) => {
+                self.config.ratings.write_to_files = val;
+                self.write_config()
+            }
+            SettingsMessage::WriteConfig => self.write_config(),
+        }
+    }
+
+    fn settings_header_view(&self) -> Element {
+        container(column![
+            text("Settings").size(20),
+            horizontal_space().height(5),
+            text!("Saved to {}", match Config::file_path() {
+                Ok(path) => path.display().to_string(),
+                Err(_) => String::from("(unknown)"),
+            })
+            .size(SMALL_TEXT_SIZE),
+        ])
+        .width(iced::Length::Fill)
+        .height(148)
+        .padding(20)
+        .style(|theme: &iced::Theme| {
+            let palette = theme.extended_palette();
+
+            container::Style {
+                text_color: Some(palette.background.base.text),
+                background: Some(palette.background.base.color.into()),
+                ..container::Style::default()
+            }
+        })
+        .into()
+    }
+
+    /// A row in one of the settings lists, with a button on the right.
+    fn settings_item<'a>(
+        item: impl Into<Element<'a>>,
+        icon: Icon,
+        msg: Message,
+    ) -> Element<'a> {
+        container(
+            row![
+                item.into(),
+                horizontal_space(),
+                control_button!(
+                    icon: icon,
+                    msg: msg,
+                    style: style::plain_icon_button,
+                ),
+            ]
+            .height(iced::Length::Fill)
+            .align_y(iced::Alignment::Center),
+        )
+        .padding(iced::Padding {
+            left: 10.0,
+            right: 5.0,
+            ..iced::Padding::default()
+        })
+        .height(42)
+        .into()
+    }
+
+    fn settings_option<'a>(
+        label: &'a str,
+        is_checked: bool,
+        on_toggle: impl Fn(bool) -> SettingsMessage + 'a,
+    ) -> Element<'a> {
+        container(
+            checkbox(label, is_checked)
+                .on_toggle(move |val| on_toggle(val).into())
+                .text_size(TEXT_SIZE),
+        )
+        .padding(10)
+        .into()
+    }
+
+    fn settings_library(&self) -> Vec<Element> {
+        let mut contents = vec![];
+
+        self.config
+            .library
+            .roots
+            .iter()
+            .enumerate()
+            .for_each(|(i, root)| {
+                contents.push(Self::settings_item(
+                    checkbox(root.path.to_string_lossy(), root.enabled)
+                        .on_toggle(move |_| {
+                            SettingsMessage::ToggleRoot(i).into()
+                        })
+                        .text_size(TEXT_SIZE),
+                    Icon::Trash,
+                    SettingsMessage::RemoveRoot(i).into(),
+                ));
+            });
+
+        let valid = !self.settings.new_root.is_empty()
+            && self.settings.new_root_error.is_none();
+        contents.push(
+            container(row![
+                text_input("Add a folder", &self.settings.new_root)
+                    .on_input(|s| SettingsMessage::NewRootChanged(s).into())
+                    .on_submit(SettingsMessage::AddRoot.into())
+                    .size(TEXT_SIZE)
+                    .width(iced::Length::Fill),
+                control_button!(
+                    icon: Icon::Folder,
+                    msg: SettingsMessage::OpenRootDialog.into(),
+                    style: style::plain_icon_button,
+                ),
+                icon_button(Icon::Plus, CONTROL_BUTTON_SIZE / 2)
+                    .width(CONTROL_BUTTON_SIZE)
+                    .height(CONTROL_BUTTON_SIZE)
+                    .style(style::plain_icon_button)
+                    .on_press_maybe(
+                        valid.then(|| SettingsMessage::AddRoot.into())
+                    ),
+            ])
+            .padding(iced::Padding {
+                left: 10.0,
+                right: 5.0,
+                ..iced::Padding::default()
+            })
+            .into(),
+        );
+        if let Some(error) = self.settings.new_root_error {
+            contents.push(
+                container(
+                    text(error)
+                        .size(SMALL_TEXT_SIZE)
+                        .color(iced::theme::Theme::Ferra.palette().danger),
+                )
+                .padding(iced::Padding {
+                    left: 10.0,
+                    ..iced::Padding::default()
+                })
+                .into(),
+            );
+        }
+
+        contents.push(Self::settings_option(
+            "Rescan the whole library on every startup",
+            self.config.library.full_rescan_on_start,
+            SettingsMessage::ToggleFullRescan,
+        ));
+        contents.push(
+            container(
+                row![
+                    button(
+                        text(if self.settings.scanning {
+                            "Scanning..."
+                        } else {
+                            "Rescan now"
+                        })
+                        .size(TEXT_SIZE)
+                    )
+                    .style(style::outlined_button)
+                    .on_press_maybe(
+                        (!self.settings.scanning)
+                            .then(|| SettingsMessage::Rescan.into())
+                    ),
+                ]
+                // with several folders, they're added and removed above instead:
+                .push_maybe(
+                    (self.config.library.roots.len() <= 1).then(|| {
+                        row![
+                            vertical_space().width(5),
+                            button(
+                                text("Change library folder...")
+                                    .size(TEXT_SIZE)
+                            )
+                            .style(style::outlined_button)
+                            .on_press(SettingsMessage::ChangeLibrary.into()),
+                        ]
+                    }),
+                ),
+            )
+            .padding(10)
+            .into(),
+        );
+        contents
+    }
+
+    fn settings_pins(&self) -> Vec<Element> {
+        let mut contents = vec![];
+
+        contents.push(Self::section_header("Pinned folders"));
+        self.config
+            .library
+            .pins
+            .iter()
+            .enumerate()
+            .for_each(|(i, path)| {
+                contents.push(Self::settings_item(
+                    text(path.to_string_lossy()).size(TEXT_SIZE),
+                    Icon::PinOff,
+                    sidebar::SidebarMessage::LibraryRemove(i).into(),
+                ));
+            });
+
+        contents.push(Self::section_header("Pinned playlists"));
+        self.config
+            .playlists
+            .pins
+            .iter()
+            .enumerate()
+            .for_each(|(i, path)| {
+                let title =
+                    self.playlists.get_playlist(path_hash(path)).map_or_else(
+                        || path.to_string_lossy().into_owned(),
+                        |pl| pl.title.clone(),
+                    );
+                contents.push(Self::settings_item(
+                    text(title).size(TEXT_SIZE),
+                    Icon::PinOff,
+                    sidebar::SidebarMessage::PlaylistRemove(i).into(),
+                ));
+            });
+        contents
+    }
+
+    pub(super) fn settings_view(&self) -> Element {
+        let mut contents = vec![];
+
+        contents.push(Self::section_header("Library folders"));
+        contents.extend(self.settings_library());
+        contents.extend(self.settings_pins());
+
+        contents.push(Self::section_header("Ratings"));
+        contents.push(Self::settings_option(
+            "Also save ratings into the tracks' tags",
+            self.config.ratings.write_to_files,
+            SettingsMessage::ToggleWriteRatings,
+        ));
+        contents.push(Self::settings_option(
+            "Favor higher-rated tracks when shuffling",
+            self.config.ratings.weighted_shuffle,
+            SettingsMessage::ToggleWeightedShuffle,
+        ));
+
+        contents.push(Self::section_header("Radio"));
+        contents.push(
+            container(
+                text(
+                    "When radio is on and the queue runs out, tracks like the \
+                     last one are queued:",
+                )
+                .size(TEXT_SIZE),
+            )
+            .padding(10)
+            .into(),
+        );
+        for relation in Relation::ALL {
+            contents.push(Self::settings_option(
+                relation.description(),
+                self.config.radio.relations.contains(&relation),
+                move |val| SettingsMessage::ToggleRadioRelation(relation, val),
+            ));
+        }
+
+        contents.push(Self::section_header("Playback"));
+        let device = match &self.config.output.device {
+            Some(name) => Device::Named(name.clone()),
+            None => Device::Default,
+        };
+        contents.push(
+            container(row![
+                text("Output device").size(TEXT_SIZE),
+                vertical_space().width(10),
+                pick_list(self.device_choices(), Some(device), |device| {
+                    SettingsMessage::OutputDevice(device).into()
+                })
+                .text_size(TEXT_SIZE),
+            ])
+            .padding(10)
+            .into(),
+        );
+        contents.push(Self::settings_option(
+            "Bit-perfect: play each track in its own format, at full volume",
+            self.config.output.bit_perfect,
+            SettingsMessage::ToggleBitPerfect,
+        ));
+        contents.push(
+            container(row![
+                text("Shuffle by").size(TEXT_SIZE),
+                vertical_space().width(10),
+                pick_list(
+                    Strategy::ALL,
+                    Some(self.config.shuffle.strategy),
+                    |strategy| SettingsMessage::ShuffleStrategy(strategy)
+                        .into(),
+                )
+                .text_size(TEXT_SIZE),
+            ])
+            .padding(10)
+            .into(),
+        );
+        contents.push(Self::settings_option(
+            "Start at the volume the app was closed with",
+            self.config.misc.remember_volume,
+            SettingsMessage::ToggleRememberVolume,
+        ));
+        contents.push(
+            container(row![
+                text("Volume on startup").size(TEXT_SIZE),
+                vertical_space().width(10),
+                slider(0.0..=1.0, self.config.misc.startup_volume, |val| {
+                    SettingsMessage::StartupVolume(val).into()
+                })
+                .on_release(SettingsMessage::WriteConfig.into())
+                .step(0.01_f32)
+                .width(200)
+                .style(style::clean_slider),
+                vertical_space().width(10),
+                text!("{:.0}%", self.config.misc.startup_volume * 100.0)
+                    .size(TEXT_SIZE),
+            ])
+            .padding(10)
+            .into(),
+        );
+
+        container(column![
+            self.settings_header_view(),
+            scrollable(column(contents))
+                .direction(scrollable::Direction::Vertical(
+                    scrollable::Scrollbar::default()
+                ))
+                .id(scrollable::Id::new("settings"))
+                .spacing(0)
+                .width(iced::Length::Fill)
+                .height(iced::Length::Fill)
+        ])
+        .style(style::track_list_container)
+        .padding(2)
+        .width(iced::Length::FillPortion(10))
+        .height(iced::Length::Fill)
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn root_validation() {
+        let tmp = std::env::temp_dir();
+        let roots = [LibraryRoot::new(tmp.clone())];
+        let tmp = tmp.to_str().unwrap();
+
+        assert!(validate_root("relative/path", &roots).is_err());
+        assert!(validate_root("/no/such/folder/for/polypody", &roots).is_err());
+        assert!(validate_root(tmp, &roots).is_err());
+        // nested inside an existing root:
+        let nested = std::env::temp_dir()
+            .join(format!("polypody-validate-{}", std::process::id()));
+        std::fs::create_dir_all(&nested).unwrap();
+        assert!(validate_root(nested.to_str().unwrap(), &roots).is_err());
+        assert_eq!(
+            validate_root(nested.to_str().unwrap(), &[]),
+            Ok(nested.clone())
+        );
+        std::fs::remove_dir(nested).unwrap();
+    }
+}
@@ -32,6 +32,31 @@ impl Sidebar {
         }
     }
 
+    pub fn from_config(config: &Config, playlists: &PlaylistMap) -> Self {
+        Self::new(
+            config
+                .library
+                .pins
+                .iter()
+                .map(|path| {
+                    (
+                        path_hash(path),
+                        path.file_stem().unwrap().to_str().unwrap().to_owned(),
+                    )
+                })
+                .collect(),
+            config
+                .playlists
+                .pins
+                .iter()
+                .map(|path| {
+                    let id = path_hash(path);
+                    (id, playlists.get_playlist(id).unwrap().title.to_owned())
+                })
+                .collect(),
+        )
+    }
+
     pub fn update(&mut self, msg: SidebarMessage, config: &mut Config) {
         match msg {
             SidebarMessage::LibraryAppend(id, name) => {
@@ -41,31 +66,32 @@ impl Sidebar {
                 config.library.pins.remove(i);
                 self.library_pins.remove(i);
             }
-            SidebarMessage::LibrarySwap(i, j) => unsafe {
-                if j < self.library_pins.len() {
-                    config.library.pins.swap_unchecked(i, j);
-                    self.library_pins.swap_unchecked(i, j);
+            SidebarMessage::LibrarySwap(i, j) => {
+                if i < self.library_pins.len() && j < self.library_pins.len() {
+                    config.library.pins.swap(i, j);
+                    self.library_pins.swap(i, j);
                 }
-            },
+            }
             SidebarMessage::PlaylistAppend(id, name) => {
                 self.playlist_pins.push((id, name));
             }
             SidebarMessage::PlaylistRemove(i) => {
-                config.library.pins.remove(i);
+                config.playlists.pins.remove(i);
                 self.playlist_pins.remove(i);
             }
-            SidebarMessage::PlaylistSwap(i, j) => unsafe {
-                if j < self.playlist_pins.len() {
-                    config.library.pins.swap_unchecked(i, j);
-                    self.playlist_pins.swap_unchecked(i, j);
+            SidebarMessage::PlaylistSwap(i, j) => {
+                if i < self.playlist_pins.len() && j < self.playlist_pins.len()
+                {
+                    config.playlists.pins.swap(i, j);
+                    self.playlist_pins.swap(i, j);
                 }
-            },
+            }
         }
     }
 
     pub fn view(&self) -> Element {
         let mut contents = Vec::with_capacity(
-            self.library_pins.len() + self.playlist_pins.len() + 2,
+            self.library_pins.len() + self.playlist_pins.len() + 4,
         );
         contents.push(Self::section_btn(
             Icon::DiscAlbum,
@@ -95,6 +121,16 @@ impl Sidebar {
                 SidebarMessage::PlaylistRemove(i).into(),
             ));
         });
+        contents.push(Self::section_btn(
+            Icon::History,
+            " History",
+            Message::ViewHistory,
+        ));
+        contents.push(Self::section_btn(
+            Icon::Settings,
+            " Settings",
+            Message::ViewSettings,
+        ));
         container(scrollable(column(contents)))
             .style(style::bordered_container)
             .padding(1)
@@ -9,14 +9,20 @@ use super::{Icon, control_button};
 use crate::internal::library::Library;
 
 pub struct StartScreen {
-    error:    bool,
-    pub lib:  Option<Library>,
+    /// Whether the screen was opened to change an existing library, which can
+    /// be cancelled.
+    changing: bool,
+    error: bool,
+    pub lib: Option<Library>,
     pub path: String,
+    /// The library folder the chosen one takes the place of, if any.
+    pub replacing: Option<usize>,
     scanning: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
+    Cancel,
     OpenDialog,
     PathChanged(String),
     Scan,
@@ -29,15 +35,30 @@ pub enum Message {
 impl StartScreen {
     pub fn new() -> Self {
         Self {
+            changing: false,
             error: false,
             lib: None,
             path: String::from(""),
+            replacing: None,
             scanning: false,
         }
     }
 
+    /// Opens the screen to add a library folder, or to replace the one at
+    /// `replacing`, starting from `path`.
+    pub fn change(path: String, replacing: Option<usize>) -> Self {
+        Self {
+            changing: true,
+            path,
+            replacing,
+            ..Self::new()
+        }
+    }
+
     pub fn update(&mut self, msg: Message) -> Task<Message> {
         match msg {
+            // handled by the app, which closes the screen:
+            Message::Cancel => Task::none(),
             Message::OpenDialog => Task::perform(
                 rfd::AsyncFileDialog::new().set_directory("/").pick_folder(),
                 Message::Selected,
@@ -51,7 +72,7 @@ impl StartScreen {
                 match path.try_exists() {
                     Ok(true) => {
                         self.scanning = true;
-                        Task::done(Message::Lib(crate::internal::scan(&path)))
+                        Task::done(Message::Lib(crate::internal::scan(&[path])))
                     }
                     Ok(false) => {
                         eprintln!("Given library path does not exist!");
@@ -128,24 +149,37 @@ impl StartScreen {
                     horizontal_space(),
                 ],
                 horizontal_space().height(20),
-                row![
-                    horizontal_space(),
-                    text!(
-                        "(If you've already set a library path and are still \
-                         seeing this screen, then a problem may have occurred \
-                         reading either your config file or the directory.)"
-                    )
-                    .size(super::SMALL_TEXT_SIZE)
-                    .width(iced::Length::Fill)
-                    .color(
-                        iced::theme::Theme::Ferra
-                            .palette()
-                            .text
-                            .scale_alpha(0.6)
-                    )
-                    .center(),
-                    horizontal_space(),
-                ],
+                if self.changing {
+                    row![
+                        horizontal_space(),
+                        iced::widget::button(
+                            text("Cancel").size(super::TEXT_SIZE)
+                        )
+                        .on_press(Message::Cancel)
+                        .style(super::style::outlined_button),
+                        horizontal_space(),
+                    ]
+                } else {
+                    row![
+                        horizontal_space(),
+                        text!(
+                            "(If you've already set a library path and are \
+                             still seeing this screen, then a problem may \
+                             have occurred reading either your config file or \
+                             the directory.)"
+                        )
+                        .size(super::SMALL_TEXT_SIZE)
+                        .width(iced::Length::Fill)
+                        .color(
+                            iced::theme::Theme::Ferra
+                                .palette()
+                                .text
+                                .scale_alpha(0.6)
+                        )
+                        .center(),
+                        horizontal_space(),
+                    ]
+                },
                 vertical_space(),
             ]
             .width(iced::Length::Fill)
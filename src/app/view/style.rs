@@ -301,3 +301,27 @@ pub(super) fn tracks_header(theme: &iced::Theme) -> container::Style {
         ..container::Style::default()
     }
 }
+
+/// A track in the queue, highlighted if it's selected, and outlined while
+/// tracks are dragged over it.
+pub(super) fn queue_item(
+    selected: bool,
+    drop_target: bool,
+) -> impl Fn(&iced::Theme) -> container::Style {
+    move |theme: &iced::Theme| {
+        let palette = theme.extended_palette();
+
+        container::Style {
+            background: selected
+                .then(|| palette.primary.weak.color.scale_alpha(0.3).into()),
+            border: if drop_target {
+                iced::border::rounded(1)
+                    .color(palette.primary.base.color)
+                    .width(1)
+            } else {
+                Border::default()
+            },
+            ..container::Style::default()
+        }
+    }
+}
@@ -1,8 +1,15 @@
-use iced::widget::vertical_space;
+use iced::widget::{text_input, vertical_space};
 
 use super::{column, *};
 
 impl App {
+    /// The album `track` is on, if any, and the gain offset set for it.
+    pub(super) fn album_gain(&self, track: &Track) -> Option<(String, f32)> {
+        let key = crate::app::user_data::album_key(track)?;
+        let gain = self.user_data.album_gain(&key);
+        Some((key, gain))
+    }
+
     pub(super) fn add_to_playlist_menu(&self) -> Element {
         let content = self
             .playlists
@@ -49,7 +56,175 @@ impl App {
         .into()
     }
 
-    fn track_list_item(track: &Track, num: usize) -> Element {
+    /// Returns the tracks in the folder or playlist being viewed, in the order
+    /// they're displayed.
+    pub(super) fn displayed_tracks(&self) -> Vec<u64> {
+        let mut tracks = match self.viewing {
+            Viewing::Library => self.library.current_directory().tracks.clone(),
+            Viewing::Playlist(Some(id)) => {
+                self.playlists.get_playlist(id).map_or(vec![], |pl| {
+                    pl.tracks
+                        .iter()
+                        .filter_map(|t| match t {
+                            PlaylistTrack::Track(id, _) => Some(*id),
+                            PlaylistTrack::Unresolved(_) => None,
+                        })
+                        .filter(|id| self.library.get_track(*id).is_some())
+                        .collect()
+                })
+            }
+            Viewing::History | Viewing::Playlist(None) | Viewing::Settings => {
+                vec![]
+            }
+        };
+        if !self.search.is_empty() {
+            let search = self.search.to_lowercase();
+            tracks.retain(|id| {
+                self.library
+                    .get_track(*id)
+                    .is_some_and(|track| track_matches(track, &search))
+            });
+        }
+
+        let Some((column, descending)) = self.track_sort else {
+            return tracks;
+        };
+        let get = |id: &u64| unsafe {
+            self.library.get_track(*id).unwrap_unchecked()
+        };
+        match column {
+            SortColumn::Number => (),
+            SortColumn::Title => tracks.sort_by_cached_key(|id| {
+                let track = get(id);
+                track.metadata.title.as_ref().map_or_else(
+                    || track.path.file_name().unwrap().to_ascii_lowercase(),
+                    |title| title.to_lowercase().into(),
+                )
+            }),
+            SortColumn::Album => tracks.sort_by_cached_key(|id| {
+                get(id).metadata.album.as_ref().map(|s| s.to_lowercase())
+            }),
+            SortColumn::Rating => {
+                tracks.sort_by_key(|id| self.user_data.track(*id).rating)
+            }
+            SortColumn::Duration => {
+                tracks.sort_by_key(|id| get(id).metadata.duration)
+            }
+        }
+        if descending {
+            tracks.reverse();
+        }
+        tracks
+    }
+
+    /// A field to filter the tracks being viewed, focused with the search
+    /// shortcut.
+    pub(super) fn search_bar(&self) -> Element {
+        container(
+            text_input("Search", &self.search)
+                .id(text_input::Id::new("search"))
+                .on_input(Message::SearchChanged)
+                .size(TEXT_SIZE)
+                .width(iced::Length::Fill),
+        )
+        .padding(5)
+        .width(iced::Length::Fill)
+        .into()
+    }
+
+    fn rating_view(data: TrackData) -> Element<'static> {
+        let stars = (1..=5).map(|i| {
+            text!("{}", char::from(Icon::Star))
+                .font(ICON_FONT)
+                .size(SMALL_TEXT_SIZE)
+                .style(move |theme: &iced::Theme| {
+                    let palette = theme.extended_palette();
+
+                    text::Style {
+                        color: Some(if i <= data.rating {
+                            palette.primary.base.color
+                        } else {
+                            palette.background.base.text.scale_alpha(0.2)
+                        }),
+                    }
+                })
+                .into()
+        });
+        let favorite = text!(
+            "{}",
+            if data.favorite {
+                char::from(Icon::Heart)
+            } else {
+                ' '
+            }
+        )
+        .font(ICON_FONT)
+        .size(SMALL_TEXT_SIZE)
+        .style(style::toggle_text(true));
+
+        row(std::iter::once(favorite.into())
+            .chain(std::iter::once(Space::with_width(4).into()))
+            .chain(stars))
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    /// Buttons to set a track's rating and toggle it as a favorite.
+    fn rating_buttons(id: u64, data: TrackData) -> Element<'static> {
+        let stars = (1..=5).map(|i| {
+            icon_button(Icon::Star, SMALL_TEXT_SIZE)
+                // clicking the current rating again clears it:
+                .on_press(Message::SetRating(
+                    id,
+                    if data.rating == i { 0 } else { i },
+                ))
+                .padding(1)
+                .style(style::toggle_icon_button(i <= data.rating))
+                .into()
+        });
+        row(std::iter::once(
+            icon_button(Icon::Heart, SMALL_TEXT_SIZE)
+                .on_press(Message::ToggleFavorite(id))
+                .padding(1)
+                .style(style::toggle_icon_button(data.favorite))
+                .into(),
+        )
+        .chain(stars))
+        .align_y(iced::Alignment::Center)
+        .into()
+    }
+
+    /// Nudges a gain offset up or down a decibel at a time, or resets it when
+    /// its value is clicked.
+    fn gain_buttons(
+        label: &str,
+        target: GainTarget,
+        gain: f32,
+    ) -> Element<'static> {
+        const STEP: f32 = 1.0;
+
+        row![
+            text!("{label} gain").size(SMALL_TEXT_SIZE),
+            Space::with_width(iced::Length::Fill),
+            icon_button(Icon::Minus, SMALL_TEXT_SIZE)
+                .on_press(Message::SetGain(target.clone(), gain - STEP))
+                .padding(1)
+                .style(style::plain_icon_button),
+            button(text!("{gain:+.0} dB").size(SMALL_TEXT_SIZE))
+                .on_press(Message::SetGain(target.clone(), 0.0))
+                .padding(1)
+                .style(style::plain_icon_button),
+            icon_button(Icon::Plus, SMALL_TEXT_SIZE)
+                .on_press(Message::SetGain(target, gain + STEP))
+                .padding(1)
+                .style(style::plain_icon_button),
+        ]
+        .align_y(iced::Alignment::Center)
+        .padding(5)
+        .into()
+    }
+
+    fn track_list_item(track: &Track, num: usize, data: TrackData) -> Element {
         container(
             row![
                 text!("{}    ", num)
@@ -97,6 +272,9 @@ impl App {
                 .align_x(iced::Alignment::Start)
                 .align_y(iced::Alignment::Center)
                 .width(iced::Length::FillPortion(10)),
+                container(Self::rating_view(data))
+                    .align_y(iced::Alignment::Center)
+                    .width(iced::Length::FillPortion(4)),
                 match &track.metadata.duration {
                     Some(duration) => text!("{}", print_duration(duration)),
                     None => text!(""),
@@ -113,43 +291,62 @@ impl App {
         .into()
     }
 
-    fn track_buttons(id: u64, num: usize, playlist: bool) -> Element<'static> {
+    fn track_buttons(
+        id: u64,
+        num: usize,
+        playlist: bool,
+        data: TrackData,
+    ) -> Element<'static> {
         let right_hand: Element = if playlist {
-            container(row![
-                column![
-                    icon_button(Icon::ChevronUp, 16)
-                        .on_press(Message::PlaylistSwap(
-                            num,
-                            num.saturating_sub(1)
-                        ))
-                        .padding(1)
-                        .style(style::plain_icon_button_with_colors(
-                            iced::Color::parse("#242226").map(|c| c.into()),
-                            None
-                        )),
-                    icon_button(Icon::ChevronDown, 16)
-                        .on_press(Message::PlaylistSwap(
-                            num,
-                            num.saturating_add(1)
-                        ))
-                        .padding(1)
-                        .style(style::plain_icon_button_with_colors(
-                            iced::Color::parse("#242226").map(|c| c.into()),
-                            None
-                        )),
-                ],
-                control_button!(
-                    icon: Icon::Trash,
-                    msg: Message::PlaylistRemove(num),
-                    style: style::plain_icon_button,
-                )
-            ])
+            container(
+                row![
+                    Self::rating_buttons(id, data),
+                    vertical_space().width(10),
+                    column![
+                        icon_button(Icon::ChevronUp, 16)
+                            .on_press(Message::PlaylistSwap(
+                                num,
+                                num.saturating_sub(1)
+                            ))
+                            .padding(1)
+                            .style(style::plain_icon_button_with_colors(
+                                iced::Color::parse("#242226").map(|c| c.into()),
+                                None
+                            )),
+                        icon_button(Icon::ChevronDown, 16)
+                            .on_press(Message::PlaylistSwap(
+                                num,
+                                num.saturating_add(1)
+                            ))
+                            .padding(1)
+                            .style(style::plain_icon_button_with_colors(
+                                iced::Color::parse("#242226").map(|c| c.into()),
+                                None
+                            )),
+                    ],
+                    control_button!(
+                        icon: Icon::Trash,
+                        msg: Message::PlaylistRemove(num),
+                        style: style::plain_icon_button,
+                    )
+                ]
+                .align_y(iced::Alignment::Center),
+            )
             .align_x(iced::Alignment::End)
             .width(iced::Length::FillPortion(24))
             .height(iced::Length::Fill)
             .into()
         } else {
-            vertical_space().width(iced::Length::FillPortion(24)).into()
+            container(Self::rating_buttons(id, data))
+                .align_x(iced::Alignment::End)
+                .align_y(iced::Alignment::Center)
+                .padding(iced::Padding {
+                    right: 10.0,
+                    ..iced::Padding::default()
+                })
+                .width(iced::Length::FillPortion(24))
+                .height(iced::Length::Fill)
+                .into()
         };
         row![
             control_button!(
@@ -181,30 +378,69 @@ impl App {
         id: u64,
         num: usize,
         playlist: bool,
+        data: TrackData,
+        album_gain: Option<(String, f32)>,
     ) -> Element {
         iced_aw::ContextMenu::new(
             iced::widget::hover(
-                Self::track_list_item(track, num),
-                Self::track_buttons(id, num - 1, playlist),
+                Self::track_list_item(track, num, data),
+                Self::track_buttons(id, num - 1, playlist, data),
             ),
             move || {
-                container(column(vec![
-                    button("Play")
-                        .on_press(queue::QueueMessage::PlayTrack(num).into())
+                container(column(
+                    vec![
+                        button("Play")
+                            .on_press(
+                                queue::QueueMessage::PlayTrack(num - 1).into(),
+                            )
+                            .width(iced::Length::Fill)
+                            .style(style::list_button)
+                            .into(),
+                        button("Play next")
+                            .on_press(
+                                queue::QueueMessage::Insert(0, vec![id]).into(),
+                            )
+                            .width(iced::Length::Fill)
+                            .style(style::list_button)
+                            .into(),
+                        button("Add to queue")
+                            .on_press(queue::QueueMessage::Append(id).into())
+                            .width(iced::Length::Fill)
+                            .style(style::list_button)
+                            .into(),
+                        button("Add to playlist...")
+                            .on_press(Message::SelectPlaylist(id))
+                            .width(iced::Length::Fill)
+                            .style(style::list_button)
+                            .into(),
+                        button(if data.favorite {
+                            "Remove from favorites"
+                        } else {
+                            "Add to favorites"
+                        })
+                        .on_press(Message::ToggleFavorite(id))
                         .width(iced::Length::Fill)
                         .style(style::list_button)
                         .into(),
-                    button("Add to queue")
-                        .on_press(queue::QueueMessage::Append(id).into())
-                        .width(iced::Length::Fill)
-                        .style(style::list_button)
-                        .into(),
-                    button("Add to playlist...")
-                        .on_press(Message::SelectPlaylist(id))
-                        .width(iced::Length::Fill)
-                        .style(style::list_button)
-                        .into(),
-                ]))
+                        container(Self::rating_buttons(id, data))
+                            .padding(5)
+                            .into(),
+                        Self::gain_buttons(
+                            "Track",
+                            GainTarget::Track(id),
+                            data.gain,
+                        ),
+                    ]
+                    .into_iter()
+                    .chain(album_gain.clone().map(|(key, gain)| {
+                        Self::gain_buttons(
+                            "Album",
+                            GainTarget::Album(key),
+                            gain,
+                        )
+                    }))
+                    .collect::<Vec<_>>(),
+                ))
                 .padding(2)
                 .width(144)
                 .style(style::context_menu)
@@ -214,3 +450,18 @@ impl App {
         .into()
     }
 }
+
+/// Whether the track's title, artists, album or file name contain `search`,
+/// which should be lowercase.
+fn track_matches(track: &Track, search: &str) -> bool {
+    let metadata = &track.metadata;
+    metadata
+        .title
+        .iter()
+        .chain(&metadata.artists)
+        .chain(&metadata.album)
+        .any(|s| s.to_lowercase().contains(search))
+        || track.path.file_name().is_some_and(|name| {
+            name.to_string_lossy().to_lowercase().contains(search)
+        })
+}
@@ -0,0 +1,200 @@
+//! Notices when the config or the playlists are changed by another program,
+//! so they can be reloaded rather than overwritten.
+//!
+//! Each file's contents are hashed whenever the app reads or writes it. A file
+//! which no longer matches its hash was changed by someone else: it's reloaded
+//! when the watcher reports it, and if the app tries to write it first, the
+//! app's version is set aside in a `.conflict` file instead.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::futures::{SinkExt, StreamExt, channel::mpsc};
+use notify::{RecursiveMode, Watcher};
+
+use super::{Message, config::Config};
+
+/// How long to wait for more changes once one is seen, since programs often
+/// write a file in several steps.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// The hashes of watched files' contents, as the app last read or wrote them.
+#[derive(Default)]
+pub struct FileStamps {
+    map: HashMap<PathBuf, u64>,
+}
+
+impl FileStamps {
+    pub fn record(&mut self, path: &Path, contents: &[u8]) {
+        self.map
+            .insert(path.to_owned(), xxhash_rust::xxh3::xxh3_64(contents));
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.map.remove(path);
+    }
+
+    /// Whether `path` has been changed, created or removed since the app last
+    /// read or wrote it.
+    pub fn changed(&self, path: &Path) -> bool {
+        let on_disk = std::fs::read(path)
+            .ok()
+            .map(|contents| xxhash_rust::xxh3::xxh3_64(&contents));
+        on_disk != self.map.get(path).copied()
+    }
+
+    /// Whether `paths` are exactly the files recorded, each unchanged.
+    pub fn unchanged(&self, paths: &[PathBuf]) -> bool {
+        paths.len() == self.map.len()
+            && paths.iter().all(|path| !self.changed(path))
+    }
+
+    /// Writes `contents` to `path`, unless it's been changed by another
+    /// program, in which case they're written to `<path>.conflict` instead.
+    pub fn write(
+        &mut self,
+        path: &Path,
+        contents: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.changed(path) {
+            let mut conflict = path.as_os_str().to_owned();
+            conflict.push(".conflict");
+            let conflict = PathBuf::from(conflict);
+            std::fs::write(&conflict, contents)?;
+            return Err(format!(
+                "{} was changed by another program, so these changes were \
+                 saved to {} instead",
+                path.display(),
+                conflict.display()
+            )
+            .into());
+        }
+        std::fs::write(path, contents)?;
+        self.record(path, contents.as_bytes());
+        Ok(())
+    }
+}
+
+/// Watches the config file and the playlist folder, sending
+/// `Message::ConfigChanged` or `Message::PlaylistsChanged` when they change.
+/// The app's own writes are reported too, and should be told apart with
+/// [`FileStamps::changed`].
+pub fn subscription() -> iced::Subscription<Message> {
+    iced::Subscription::run(|| {
+        iced::stream::channel(16, |mut output| async move {
+            let config = match Config::file_path()
+                .and_then(|path| Ok(std::path::absolute(path)?))
+            {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Couldn't watch the config: {e}");
+                    return;
+                }
+            };
+            let playlists = match crate::paths::playlist_dir() {
+                Ok(path) => path,
+                Err(e) => {
+                    eprintln!("Couldn't watch the playlists: {e}");
+                    return;
+                }
+            };
+
+            let (tx, mut rx) = mpsc::unbounded();
+            let watcher = notify::recommended_watcher(move |event| {
+                let _ = tx.unbounded_send(event);
+            });
+            let mut watcher = match watcher {
+                Ok(watcher) => watcher,
+                Err(e) => {
+                    eprintln!("Couldn't watch for changes to files: {e}");
+                    return;
+                }
+            };
+            // editors often replace a file rather than writing to it, which
+            // is only seen by watching its folder:
+            if let Some(dir) = config.parent() {
+                let _ = watcher
+                    .watch(dir, RecursiveMode::NonRecursive)
+                    .inspect_err(|e| {
+                        eprintln!("Couldn't watch {}: {e}", dir.display())
+                    });
+            }
+            let _ = watcher
+                .watch(&playlists, RecursiveMode::NonRecursive)
+                .inspect_err(|e| {
+                    eprintln!("Couldn't watch {}: {e}", playlists.display())
+                });
+
+            while let Some(event) = rx.next().await {
+                let mut events = vec![event];
+                tokio::time::sleep(SETTLE_TIME).await;
+                while let Ok(Some(event)) = rx.try_next() {
+                    events.push(event);
+                }
+
+                let mut config_changed = false;
+                let mut playlists_changed = false;
+                for event in events {
+                    let event = match event {
+                        Ok(event) if !event.kind.is_access() => event,
+                        Ok(_) => continue,
+                        Err(e) => {
+                            eprintln!("Problem watching for changes: {e}");
+                            continue;
+                        }
+                    };
+                    for path in event.paths {
+                        if path == config {
+                            config_changed = true;
+                        } else if path.parent() == Some(playlists.as_path())
+                            && path.extension().is_some_and(|ext| ext == "toml")
+                        {
+                            playlists_changed = true;
+                        }
+                    }
+                }
+                if config_changed {
+                    let _ = output.send(Message::ConfigChanged).await;
+                }
+                if playlists_changed {
+                    let _ = output.send(Message::PlaylistsChanged).await;
+                }
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conflicting_writes_are_set_aside() {
+        let path = std::env::temp_dir().join("polypody_watch.toml");
+        let conflict =
+            std::env::temp_dir().join("polypody_watch.toml.conflict");
+        let _ = std::fs::remove_file(&conflict);
+
+        let mut stamps = FileStamps::default();
+        std::fs::write(&path, "a = 1\n").unwrap();
+        assert!(stamps.changed(&path));
+        stamps.record(&path, b"a = 1\n");
+        assert!(!stamps.changed(&path));
+
+        stamps.write(&path, "a = 2\n").unwrap();
+        assert!(!stamps.changed(&path));
+
+        std::fs::write(&path, "a = 3\n").unwrap();
+        assert!(stamps.changed(&path));
+        assert!(stamps.write(&path, "a = 4\n").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a = 3\n");
+        assert_eq!(std::fs::read_to_string(&conflict).unwrap(), "a = 4\n");
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&conflict).unwrap();
+    }
+}
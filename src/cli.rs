@@ -0,0 +1,428 @@
+//! Command-line arguments, and the subcommands which run without a window.
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use clap::{Parser, Subcommand};
+
+use super::*;
+use crate::app::{
+    LaunchOptions,
+    config::Config,
+    playlist::{PlaylistMap, PlaylistTrack},
+};
+
+#[derive(Parser)]
+#[command(version, about, subcommand_precedence_over_arg = true)]
+pub struct Cli {
+    /// Tracks or folders in the library to play, replacing the queue. If
+    /// Polypody is already running, they're added to its queue instead.
+    pub paths: Vec<PathBuf>,
+
+    /// Use this folder as the library instead of the configured ones, for this
+    /// run only.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub library: Option<PathBuf>,
+
+    /// Read and write the config at this path instead of the default.
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Only scan for new tracks on startup, regardless of the config.
+    #[arg(long, global = true, conflicts_with = "full_rescan")]
+    pub rescan: bool,
+
+    /// Rescan the whole library on startup, regardless of the config.
+    #[arg(long, global = true)]
+    pub full_rescan: bool,
+
+    /// Play into nothing instead of a sound card, at this many times real
+    /// time, for running without audio hardware.
+    #[arg(
+        long,
+        value_name = "SPEED",
+        num_args = 0..=1,
+        default_missing_value = "1",
+        value_parser = positive
+    )]
+    pub null_output: Option<f32>,
+
+    /// Run without a window, taking commands over a Unix socket.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with = "attach")]
+    pub daemon: bool,
+
+    /// Open a window which controls a running daemon.
+    #[cfg(unix)]
+    #[arg(long)]
+    pub attach: bool,
+
+    /// Toggle play/pause in the running player.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with_all = ["daemon", "attach"])]
+    pub play_pause: bool,
+
+    /// Skip to the next track in the running player.
+    #[cfg(unix)]
+    #[arg(long, conflicts_with_all = ["daemon", "attach"])]
+    pub next: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Scan the library and update the cache, without opening a window.
+    Scan,
+    /// Measure every track's duration from its audio, listing those whose
+    /// tags or headers give a different one.
+    Verify {
+        /// Save the measured durations into the cache.
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Decode a track the way it's played, writing the audio to a WAV file
+    /// instead of a device.
+    Render {
+        /// The audio file to render, which needn't be in the library.
+        track: PathBuf,
+        /// Where to write the WAV file.
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+        /// How far into the track to start.
+        #[arg(long, value_name = "SECONDS", default_value_t = 0.0)]
+        start: f64,
+        /// How much to render, instead of the rest of the track.
+        #[arg(long, value_name = "SECONDS")]
+        length: Option<f64>,
+        /// The volume to render at, as a position on the volume slider from
+        /// 0.0 to 1.0. Bit-perfect rendering is always at full volume.
+        #[arg(long, default_value_t = 1.0)]
+        volume: f32,
+        /// Render as bit-perfect mode plays, writing samples at the track's
+        /// own depth.
+        #[arg(long)]
+        bit_perfect: bool,
+    },
+    /// List the saved playlists.
+    ListPlaylists,
+    /// Write a playlist out as an M3U file.
+    ExportPlaylist {
+        /// The playlist's title or file name.
+        playlist: String,
+        /// Where to write the playlist, instead of standard output.
+        #[arg(short, long, value_name = "FILE")]
+        output:   Option<PathBuf>,
+    },
+}
+
+impl Cli {
+    pub fn launch_options(&self) -> LaunchOptions {
+        LaunchOptions {
+            library: self.library.clone(),
+            full_rescan: if self.full_rescan {
+                Some(true)
+            } else if self.rescan {
+                Some(false)
+            } else {
+                None
+            },
+            play: self.paths.clone(),
+            null_output: self.null_output,
+        }
+    }
+
+    /// The requests to send a player that's already running, in place of
+    /// starting another.
+    #[cfg(unix)]
+    pub fn forwarded_requests(&self) -> Vec<crate::ipc::Request> {
+        use crate::ipc::Request;
+
+        let mut requests = self
+            .paths
+            .iter()
+            .map(|path| Request::Enqueue {
+                // the running player has its own working directory:
+                path: std::path::absolute(path).unwrap_or(path.clone()),
+            })
+            .collect::<Vec<_>>();
+        if self.next {
+            requests.push(Request::Next);
+        }
+        if self.play_pause {
+            requests.push(Request::Toggle);
+        }
+        requests
+    }
+}
+
+pub fn run(
+    command: Command,
+    options: LaunchOptions,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Scan => scan(options),
+        Command::Verify { fix } => verify(options, fix),
+        Command::Render {
+            track,
+            output,
+            start,
+            length,
+            volume,
+            bit_perfect,
+        } => render(&track, &output, start, length, volume, bit_perfect),
+        Command::ListPlaylists => list_playlists(),
+        Command::ExportPlaylist { playlist, output } => {
+            export_playlist(&playlist, output)
+        }
+    }
+}
+
+/// Loads the library as the app would, returning it along with the folders it
+/// was scanned from.
+fn load_library(
+    options: LaunchOptions,
+) -> Result<(Library, Vec<PathBuf>), Box<dyn Error>> {
+    let mut config = match Config::from_file(Config::file_path()?) {
+        Ok(config) => config,
+        // only a missing config is replaced, never one that couldn't be read:
+        Err(e)
+            if options.library.is_some()
+                && e.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                    e.kind() == std::io::ErrorKind::NotFound
+                }) =>
+        {
+            Config::default()
+        }
+        Err(e) => return Err(format!("couldn't read config: {e}").into()),
+    };
+    // the configured folders are left as they are:
+    config.library.session_root = options.library;
+    let roots = config.library.enabled_roots();
+    let lib = app::load_library(
+        &roots,
+        options
+            .full_rescan
+            .unwrap_or(config.library.full_rescan_on_start),
+    );
+    Ok((lib, roots))
+}
+
+fn scan(options: LaunchOptions) -> Result<(), Box<dyn Error>> {
+    let (lib, roots) = load_library(options)?;
+    lib.write_to_file()?;
+    let tracks = match lib.tracks().len() {
+        1 => String::from("1 track"),
+        n => format!("{n} tracks"),
+    };
+    match &roots[..] {
+        [root] => println!("{tracks} in {}", root.display()),
+        roots => println!("{tracks} in {} folders", roots.len()),
+    }
+    Ok(())
+}
+
+fn verify(options: LaunchOptions, fix: bool) -> Result<(), Box<dyn Error>> {
+    use internal::audio::duration;
+
+    let (mut lib, _) = load_library(options)?;
+    let mut tracks = lib.tracks().map(|(_, track)| track).collect::<Vec<_>>();
+    tracks.sort_by(|a, b| a.path.cmp(&b.path));
+
+    // reading every file takes a while, so it's spread across threads:
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = tracks.len().div_ceil(threads).max(1);
+    let measured = std::thread::scope(|scope| {
+        tracks
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|track| {
+                            duration::exact_duration(&track.path)
+                                .map_err(|e| e.to_string())
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    let format = |duration: Option<Duration>| {
+        duration.map_or(String::from("unknown"), |duration| {
+            format!("{:.3}s", duration.as_secs_f64())
+        })
+    };
+    let mut wrong = vec![];
+    for (track, exact) in tracks.iter().zip(measured) {
+        match exact {
+            Ok(exact) if !duration::agrees(track.metadata.duration, exact) => {
+                println!(
+                    "{}\t{}\t{}",
+                    track.path.display(),
+                    format(track.metadata.duration),
+                    format(Some(exact))
+                );
+                wrong.push(((*track).clone(), exact));
+            }
+            Ok(_) => (),
+            Err(e) => {
+                eprintln!("Couldn't measure {}: {e}", track.path.display())
+            }
+        }
+    }
+
+    let count = tracks.len();
+    let summary = match wrong.len() {
+        1 => format!("1 of {count} tracks has the wrong duration"),
+        n => format!("{n} of {count} tracks have the wrong duration"),
+    };
+    if fix && !wrong.is_empty() {
+        for (mut track, exact) in wrong {
+            track.metadata.duration = Some(exact);
+            lib.add_track(track);
+        }
+        lib.write_to_file()?;
+        eprintln!("{summary}, now corrected in the cache");
+    } else {
+        eprintln!("{summary}");
+    }
+    Ok(())
+}
+
+fn render(
+    track: &PathBuf,
+    output: &PathBuf,
+    start: f64,
+    length: Option<f64>,
+    volume: f32,
+    bit_perfect: bool,
+) -> Result<(), Box<dyn Error>> {
+    use internal::audio::render::{RenderOptions, render_wav};
+
+    let seconds = |s: f64| {
+        Duration::try_from_secs_f64(s)
+            .map_err(|_| format!("{s} isn't a valid number of seconds"))
+    };
+    // the decoder runs as a task, the same as when playing:
+    let runtime = tokio::runtime::Runtime::new()?;
+    let _guard = runtime.enter();
+    let mut stream = internal::audio::AudioStream::new(
+        track,
+        symphonia::default::get_codecs(),
+        symphonia::default::get_probe(),
+        // so the render stops wherever the file does:
+        None,
+        bit_perfect,
+    )?;
+    let bits_per_sample = match stream.format().bits_per_sample {
+        Some(bits @ 1..=32) if bit_perfect => Some(bits as u16),
+        _ => None,
+    };
+    let options = RenderOptions {
+        start: seconds(start)?,
+        length: length.map(seconds).transpose()?,
+        // the volume's left alone in bit-perfect mode, as when playing:
+        volume: if bit_perfect {
+            1.0
+        } else {
+            internal::audio::volume_gain(volume)
+        },
+        bits_per_sample,
+    };
+    let file = BufWriter::new(File::create(output)?);
+    let frames = render_wav(&mut stream, options, file)?;
+    eprintln!(
+        "Rendered {:.3}s to {}",
+        frames as f64 / stream.format().sample_rate as f64,
+        output.display()
+    );
+    Ok(())
+}
+
+fn list_playlists() -> Result<(), Box<dyn Error>> {
+    let mut playlists = PlaylistMap::new();
+    playlists.scan_playlists()?;
+    let mut playlists =
+        playlists.playlists().map(|(_, pl)| pl).collect::<Vec<_>>();
+    playlists.sort_by(|a, b| a.title.cmp(&b.title));
+    for pl in playlists {
+        println!("{}\t{}\t{} tracks", pl.filename, pl.title, pl.tracks.len());
+    }
+    Ok(())
+}
+
+fn export_playlist(
+    name: &str,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut playlists = PlaylistMap::new();
+    playlists.scan_playlists()?;
+    let pl = playlists
+        .playlists()
+        .map(|(_, pl)| pl)
+        .find(|pl| {
+            pl.filename == name
+                || pl.filename.strip_suffix(".toml") == Some(name)
+                || pl.title == name
+        })
+        .ok_or_else(|| format!("no playlist named {name}"))?;
+
+    // the cache is only used for track info, so it's fine if it's missing:
+    let lib = Library::file_path()
+        .ok()
+        .and_then(|path| Library::from_file(&path).ok())
+        .unwrap_or_default();
+
+    let mut f: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    writeln!(f, "#EXTM3U")?;
+    writeln!(f, "#PLAYLIST:{}", pl.title)?;
+    for track in &pl.tracks {
+        let path = match track {
+            PlaylistTrack::Track(id, path) => {
+                if let Some(track) = lib.get_track(*id) {
+                    let secs = track
+                        .metadata
+                        .duration
+                        .map_or(-1, |duration| duration.as_secs() as i64);
+                    let title =
+                        track.metadata.title.clone().unwrap_or_else(|| {
+                            path.file_stem().unwrap().to_string_lossy().into()
+                        });
+                    if track.metadata.artists.is_empty() {
+                        writeln!(f, "#EXTINF:{secs},{title}")?;
+                    } else {
+                        let artists = track.metadata.artists.join(", ");
+                        writeln!(f, "#EXTINF:{secs},{artists} - {title}")?;
+                    }
+                }
+                path
+            }
+            PlaylistTrack::Unresolved(path) => {
+                eprintln!("Warning: {} doesn't exist", path.display());
+                path
+            }
+        };
+        writeln!(f, "{}", path.display())?;
+    }
+    Ok(f.flush()?)
+}
+
+fn positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(n) if n > 0.0 => Ok(n),
+        Ok(_) => Err("must be more than 0".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,382 @@
+//! Headless mode, which plays from the library without opening a window and
+//! takes commands over a Unix socket (see [`crate::ipc`]).
+
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc,
+};
+
+use super::*;
+use crate::{
+    app::{LaunchOptions, PlayStatus, RepeatStatus, config::Config},
+    internal::library::path_hash,
+    ipc::{Request, Response, Status},
+};
+
+/// How often the daemon checks whether the current track has finished.
+const TICK: Duration = Duration::from_millis(50);
+
+struct Player {
+    codec_registry: &'static CodecRegistry,
+    probe: &'static Probe,
+    library: Library,
+    sink: rodio::Sink,
+    position: internal::audio::Position,
+    failure: internal::audio::Failure,
+    queue: Vec<u64>,
+    playing: Option<Track>,
+    status: PlayStatus,
+    repeat: RepeatStatus,
+    volume: f32,
+}
+
+impl Player {
+    fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Clear => {
+                self.queue.clear();
+            }
+            Request::Enqueue { path } => {
+                let tracks = self.library.tracks_under(&path).or_else(|| {
+                    path.canonicalize()
+                        .ok()
+                        .and_then(|path| self.library.tracks_under(&path))
+                });
+                let Some(tracks) = tracks else {
+                    return Response::error(format!(
+                        "{} isn't in the library",
+                        path.display()
+                    ));
+                };
+                self.queue.extend(tracks);
+                if self.playing.is_none() && !self.queue.is_empty() {
+                    self.play_next();
+                }
+            }
+            Request::Jump { index } => {
+                if index >= self.queue.len() {
+                    return Response::error(
+                        "index is past the end of the queue",
+                    );
+                }
+                self.queue.drain(..index);
+                self.play_next();
+            }
+            Request::Next => {
+                if self.repeat == RepeatStatus::All
+                    && let Some(playing) = &self.playing
+                {
+                    self.queue.push(track_hash(playing));
+                }
+                self.play_next();
+            }
+            Request::Pause => {
+                if self.status == PlayStatus::Play {
+                    self.sink.pause();
+                    self.status = PlayStatus::Pause;
+                }
+            }
+            Request::Play => {
+                if self.playing.is_some() {
+                    self.sink.play();
+                    self.status = PlayStatus::Play;
+                } else if !self.queue.is_empty() {
+                    self.play_next();
+                } else {
+                    return Response::error("the queue is empty");
+                }
+            }
+            // handled by the connection, since it stops the server:
+            Request::Quit => (),
+            Request::Repeat { repeat } => {
+                self.repeat = repeat;
+            }
+            Request::Seek { position } => {
+                let Some(total) =
+                    self.playing.as_ref().and_then(|t| t.metadata.duration)
+                else {
+                    return Response::error("nothing is playing");
+                };
+                let Ok(pos) = Duration::try_from_secs_f64(position) else {
+                    return Response::error("invalid position");
+                };
+                if pos >= total {
+                    return Response::error("position is past the end");
+                }
+                if let Err(e) = self.sink.try_seek(pos) {
+                    return Response::error(e);
+                }
+            }
+            Request::SetQueue { paths } => {
+                let track_at = |path: &PathBuf| {
+                    let id = path_hash(path);
+                    self.library.get_track(id).map(|_| id)
+                };
+                let mut queue = vec![];
+                for path in paths {
+                    let id = track_at(&path).or_else(|| {
+                        path.canonicalize()
+                            .ok()
+                            .and_then(|path| track_at(&path))
+                    });
+                    let Some(id) = id else {
+                        return Response::error(format!(
+                            "{} isn't in the library",
+                            path.display()
+                        ));
+                    };
+                    queue.push(id);
+                }
+                self.queue = queue;
+            }
+            Request::Status => return Response::status(self.status()),
+            Request::Stop => {
+                self.queue.clear();
+                self.stop();
+            }
+            Request::Toggle => {
+                return self.handle(if self.status == PlayStatus::Play {
+                    Request::Pause
+                } else {
+                    Request::Play
+                });
+            }
+            Request::Volume { volume } => {
+                self.volume = volume.clamp(0.0, 1.0);
+                self.sink
+                    .set_volume(internal::audio::volume_gain(self.volume));
+            }
+        }
+        Response::ok()
+    }
+
+    /// Plays the first track in the queue which can be played, passing over
+    /// any which can't.
+    fn play_next(&mut self) {
+        self.sink.stop();
+        while !self.queue.is_empty() {
+            let track = self.library.get_track(self.queue.remove(0)).unwrap();
+            let stream = internal::audio::AudioStream::new(
+                &track.path,
+                self.codec_registry,
+                self.probe,
+                track.metadata.duration,
+                false,
+            );
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Couldn't play {}: {e}", track.path.display());
+                    continue;
+                }
+            };
+            self.position = stream.position_handle();
+            self.failure = stream.failure_handle();
+            self.sink.append(stream);
+            self.playing = Some(track.clone());
+            self.sink.play();
+            self.status = PlayStatus::Play;
+            return;
+        }
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.sink.stop();
+        self.playing = None;
+        self.status = PlayStatus::Stopped;
+    }
+
+    fn status(&self) -> Status {
+        Status {
+            state:    self.status,
+            track:    self.playing.as_ref().map(|track| track.path.clone()),
+            position: if self.playing.is_some() {
+                self.position.get().as_secs_f64()
+            } else {
+                0.0
+            },
+            duration: self
+                .playing
+                .as_ref()
+                .and_then(|track| track.metadata.duration)
+                .map(|duration| duration.as_secs_f64()),
+            volume:   self.volume,
+            repeat:   self.repeat,
+            queue:    self
+                .queue
+                .iter()
+                .filter_map(|id| self.library.get_track(*id))
+                .map(|track| track.path.clone())
+                .collect(),
+        }
+    }
+
+    /// Moves on to the next track once the current one has finished, the
+    /// same way the app does.
+    fn update(&mut self) {
+        if !self.sink.empty() {
+            return;
+        }
+        let Some(playing) = self.playing.take() else {
+            return;
+        };
+        // a track which broke off isn't repeated:
+        if let Some(e) = self.failure.take() {
+            eprintln!("Couldn't play {}: {e}", playing.path.display());
+            self.play_next();
+            return;
+        }
+        let last = track_hash(&playing);
+        match self.repeat {
+            RepeatStatus::One => self.queue.insert(0, last),
+            RepeatStatus::All => self.queue.push(last),
+            RepeatStatus::None => (),
+        }
+        self.play_next();
+    }
+}
+
+/// Runs the daemon until it's sent a `quit` command or interrupted.
+pub fn run(options: LaunchOptions) -> Result<(), Box<dyn Error>> {
+    let config =
+        match (Config::from_file(Config::file_path()?), &options.library) {
+            (Ok(config), _) => config,
+            (Err(_), Some(_)) => Config::default(),
+            (Err(e), None) => {
+                return Err(format!(
+                    "couldn't read config ({e}); start Polypody normally \
+                     once, or pass --library, to choose a library"
+                )
+                .into());
+            }
+        };
+    let roots = match options.library {
+        Some(path) => vec![path],
+        None => config.library.enabled_roots(),
+    };
+    let library = app::load_library(
+        &roots,
+        options
+            .full_rescan
+            .unwrap_or(config.library.full_rescan_on_start),
+    );
+    let _ = library
+        .write_to_file()
+        .inspect_err(|e| eprintln!("Couldn't write library cache: {e}"));
+    let queue = app::resolve_paths(&library, &options.play);
+
+    let backend = internal::audio::backend(options.null_output);
+    let device = config.output.device.as_deref();
+    let output = match (backend.open(device), device) {
+        (Err(e), Some(device)) => {
+            eprintln!("Couldn't open {device}, so the default is used: {e}");
+            backend.open(None)?
+        }
+        (output, _) => output?,
+    };
+    let sink = output.sink()?;
+    let volume = config.misc.startup_volume.clamp(0.0, 1.0);
+    sink.set_volume(internal::audio::volume_gain(volume));
+
+    let player = Player {
+        codec_registry: symphonia::default::get_codecs(),
+        probe: symphonia::default::get_probe(),
+        library,
+        sink,
+        position: internal::audio::Position::default(),
+        failure: internal::audio::Failure::default(),
+        queue,
+        playing: None,
+        status: PlayStatus::Stopped,
+        repeat: RepeatStatus::None,
+        volume,
+    };
+
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let mut player = player;
+        if !player.queue.is_empty() {
+            player.play_next();
+        }
+        serve(player).await
+    })
+}
+
+async fn serve(player: Player) -> Result<(), Box<dyn Error>> {
+    let path = ipc::socket_path()?;
+    if UnixStream::connect(&path).await.is_ok() {
+        return Err(format!(
+            "another daemon is already listening at {}",
+            path.display()
+        )
+        .into());
+    }
+    // left behind by a daemon that didn't exit cleanly:
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    println!("Listening at {}", path.display());
+
+    let player = Arc::new(Mutex::new(player));
+    let (quit_tcx, mut quit_rcx) = mpsc::channel(1);
+
+    let ticking = player.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            ticking.lock().unwrap().update();
+        }
+    });
+
+    loop {
+        tokio::select! {
+            conn = listener.accept() => {
+                let (stream, _) = conn?;
+                let player = player.clone();
+                let quit_tcx = quit_tcx.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, player, quit_tcx)
+                        .await
+                        .inspect_err(|e| eprintln!("Connection error: {e}"));
+                });
+            }
+            _ = quit_rcx.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    Ok(std::fs::remove_file(&path)?)
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    player: Arc<Mutex<Player>>,
+    quit: mpsc::Sender<()>,
+) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let request = serde_json::from_str::<Request>(&line);
+        let response = match &request {
+            Ok(request) => player.lock().unwrap().handle(request.clone()),
+            Err(e) => Response::error(format!("invalid request: {e}")),
+        };
+
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+
+        if let Ok(Request::Quit) = request {
+            let _ = quit.send(()).await;
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! Keeps a single player running. Whichever process plays audio (the app, or
+//! the daemon) holds a lock on [`lock_path`], with the path of its control
+//! socket written inside; launching Polypody again while it's held forwards
+//! the new arguments over that socket instead of starting a second player.
+//!
+//! The app's socket takes a subset of the daemon's [`Request`]s, which are
+//! handled as ordinary `Message`s.
+
+use std::{
+    error::Error,
+    fs::{File, OpenOptions, TryLockError},
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use iced::futures::{SinkExt, channel::mpsc::Sender};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt},
+    net::UnixListener,
+};
+
+use crate::{
+    app::Message,
+    ipc::{Request, Response},
+};
+
+/// How long a later launch is waited on for each request before it's hung up
+/// on.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Held by the running player for as long as it's open.
+pub struct InstanceLock {
+    _file: File,
+}
+
+pub enum Instance {
+    /// No other player is running; this process is now the one.
+    Primary(InstanceLock),
+    /// Another player is running, listening at the given socket.
+    Secondary(PathBuf),
+}
+
+pub fn lock_path() -> std::io::Result<PathBuf> {
+    let mut path = crate::paths::data_dir()?;
+    path.push("polypody.lock");
+    Ok(path)
+}
+
+/// The socket the app listens on while it holds the lock. The daemon uses
+/// `ipc::socket_path` instead.
+pub fn socket_path() -> std::io::Result<PathBuf> {
+    let mut path = crate::paths::data_dir()?;
+    path.push("polypody-app.sock");
+    Ok(path)
+}
+
+/// Takes the lock if no other player holds it, recording `socket` as where to
+/// forward requests.
+pub fn acquire(socket: &Path) -> std::io::Result<Instance> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path()?)?;
+    match file.try_lock() {
+        Ok(()) => {
+            file.set_len(0)?;
+            file.write_all(socket.as_os_str().as_encoded_bytes())?;
+            file.flush()?;
+            Ok(Instance::Primary(InstanceLock { _file: file }))
+        }
+        Err(TryLockError::WouldBlock) => {
+            let mut path = String::new();
+            file.read_to_string(&mut path)?;
+            Ok(Instance::Secondary(PathBuf::from(path)))
+        }
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Sends `requests` to the running player at `socket`, one at a time, failing
+/// if any of them are refused.
+pub fn forward(
+    socket: &Path,
+    requests: &[Request],
+) -> Result<(), Box<dyn Error>> {
+    let stream = UnixStream::connect(socket).map_err(|e| {
+        format!(
+            "couldn't reach the running player at {}: {e}",
+            socket.display()
+        )
+    })?;
+    let mut write = stream.try_clone()?;
+    let mut read = BufReader::new(stream);
+
+    let mut refused = 0;
+    for request in requests {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        write.write_all(line.as_bytes())?;
+
+        let mut line = String::new();
+        read.read_line(&mut line)?;
+        let response = serde_json::from_str::<Response>(&line)?;
+        if !response.ok {
+            eprintln!(
+                "Polypody refused {request:?}: {}",
+                response.error.unwrap_or_default()
+            );
+            refused += 1;
+        }
+    }
+    match refused {
+        0 => Ok(()),
+        1 => Err("1 request was refused".into()),
+        n => Err(format!("{n} requests were refused").into()),
+    }
+}
+
+/// Listens on [`socket_path`] for requests from later launches. Should only
+/// run while this process holds the lock.
+pub fn subscription() -> iced::Subscription<Message> {
+    iced::Subscription::run(|| {
+        iced::stream::channel(16, |output| async move {
+            let listener = match socket_path().and_then(|path| {
+                // safe to remove, since whoever left it doesn't hold the lock:
+                let _ = std::fs::remove_file(&path);
+                UnixListener::bind(path)
+            }) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Couldn't listen for other launches: {e}");
+                    return;
+                }
+            };
+
+            // each connection is handled separately, so that one which hangs
+            // doesn't hold up the rest:
+            while let Ok((stream, _)) = listener.accept().await {
+                let output = output.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, output)
+                        .await
+                        .inspect_err(|e| {
+                            eprintln!(
+                                "Couldn't take requests from a launch: {e}"
+                            )
+                        });
+                });
+            }
+        })
+    })
+}
+
+async fn handle_connection(
+    stream: tokio::net::UnixStream,
+    mut output: Sender<Message>,
+) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(read).lines();
+
+    while let Some(line) =
+        tokio::time::timeout(READ_TIMEOUT, lines.next_line()).await??
+    {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match Message::from_request(request.clone()) {
+                Some(message) => {
+                    let _ = output.send(message).await;
+                    Response::ok()
+                }
+                None => Response::error(format!(
+                    "{request:?} is only supported by the daemon"
+                )),
+            },
+            Err(e) => Response::error(format!("invalid request: {e}")),
+        };
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        write.write_all(line.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use iced::futures::{StreamExt, channel::mpsc};
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn a_silent_launch_doesnt_hold_up_another() {
+        let (output, mut messages) = mpsc::channel(16);
+        let (_silent, server) = tokio::net::UnixStream::pair().unwrap();
+        tokio::spawn(handle_connection(server, output.clone()));
+
+        let (mut client, server) = tokio::net::UnixStream::pair().unwrap();
+        tokio::spawn(handle_connection(server, output));
+        let mut line = serde_json::to_string(&Request::Toggle).unwrap();
+        line.push('\n');
+        client.write_all(line.as_bytes()).await.unwrap();
+
+        let message = tokio::time::timeout(READ_TIMEOUT, messages.next())
+            .await
+            .unwrap();
+        assert!(matches!(message, Some(Message::TogglePlay)));
+        let mut response = [0; 64];
+        let n = client.read(&mut response).await.unwrap();
+        let response =
+            serde_json::from_slice::<Response>(&response[..n]).unwrap();
+        assert!(response.ok);
+    }
+}
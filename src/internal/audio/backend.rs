@@ -0,0 +1,51 @@
+//! Where audio is played: on the system's devices through rodio, or nowhere at
+//! all (see [`super::null`]), so playback can run without a sound card.
+
+use std::error::Error;
+
+use super::{StreamFormat, device::DeviceFormat};
+
+/// Somewhere to open outputs on.
+pub trait Backend: Send + Sync {
+    /// The names of the devices which are available. A device that's already
+    /// in use may be left out, depending on the system.
+    fn device_names(&self) -> Vec<String>;
+
+    /// Opens the device called `name`, or the default device if `name` is
+    /// `None`.
+    fn open(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>>;
+
+    /// Opens the device called `name`, or the default device, at the sample
+    /// rate and channels of `format` and with samples at least as deep, so
+    /// that nothing's resampled or lost on the way. Fails if the device can't
+    /// play it.
+    fn open_native(
+        &self,
+        name: Option<&str>,
+        format: StreamFormat,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>>;
+}
+
+/// An open output device, which stays open for as long as it's kept.
+pub trait Output {
+    /// A new sink which plays on the device.
+    fn sink(&self) -> Result<rodio::Sink, Box<dyn Error>>;
+
+    /// The name of the device, or `None` if it's the default device.
+    fn name(&self) -> Option<&str>;
+
+    /// The format the device was opened in, if it was opened to match a track.
+    fn format(&self) -> Option<DeviceFormat>;
+}
+
+/// The system's devices, or the null backend playing at `null_speed` times
+/// real time if that's given.
+pub fn backend(null_speed: Option<f32>) -> Box<dyn Backend> {
+    match null_speed {
+        Some(speed) => Box::new(super::null::NullBackend::new(speed)),
+        None => Box::new(super::device::RodioBackend),
+    }
+}
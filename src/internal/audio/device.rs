@@ -0,0 +1,171 @@
+//! The system's devices, which audio's played on through rodio.
+
+use std::error::Error;
+
+use rodio::{
+    OutputStream, OutputStreamHandle,
+    cpal::{
+        Device, SampleFormat, SampleRate,
+        traits::{DeviceTrait, HostTrait},
+    },
+};
+
+use super::{
+    StreamFormat,
+    backend::{Backend, Output},
+};
+
+/// The system's devices.
+pub struct RodioBackend;
+
+/// An open device on the system.
+pub struct RodioOutput {
+    _stream: OutputStream,
+    handle:  OutputStreamHandle,
+    name:    Option<String>,
+    format:  Option<DeviceFormat>,
+}
+
+/// The format an output device was opened in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviceFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+}
+
+impl DeviceFormat {
+    /// Whether a stream in `format` plays on the device without being
+    /// resampled, remixed or cut down to fewer bits.
+    pub fn plays_natively(&self, format: StreamFormat) -> bool {
+        self.sample_rate == format.sample_rate
+            && self.channels == format.channels
+            && lossless_formats(format.bits_per_sample)
+                .contains(&self.sample_format)
+    }
+}
+
+impl std::fmt::Display for DeviceFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rate = self.sample_rate as f64 / 1000.0;
+        let depth = match self.sample_format {
+            SampleFormat::F32 | SampleFormat::F64 => {
+                format!("{}-bit float", self.sample_format.sample_size() * 8)
+            }
+            format => format!("{}-bit", format.sample_size() * 8),
+        };
+        write!(f, "{rate} kHz, {depth}")
+    }
+}
+
+/// The sample formats a track of the given depth can be played in without
+/// losing anything, best first.
+pub(super) fn lossless_formats(
+    bits_per_sample: Option<u32>,
+) -> &'static [SampleFormat] {
+    match bits_per_sample {
+        Some(..=8) => &[SampleFormat::I8, SampleFormat::I16, SampleFormat::I32],
+        Some(..=16) => {
+            &[SampleFormat::I16, SampleFormat::I32, SampleFormat::F32]
+        }
+        Some(..=24) => &[SampleFormat::I32, SampleFormat::F32],
+        Some(_) => &[SampleFormat::I32],
+        // decoded straight to floats, as with lossy formats:
+        None => &[SampleFormat::F32, SampleFormat::I32],
+    }
+}
+
+fn find_device(name: Option<&str>) -> Result<Device, Box<dyn Error>> {
+    let host = rodio::cpal::default_host();
+    match name {
+        Some(name) => host
+            .output_devices()?
+            .find(|device| device.name().is_ok_and(|n| n == name))
+            .ok_or_else(|| format!("there's no device called {name}").into()),
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "there's no default device".into()),
+    }
+}
+
+impl Backend for RodioBackend {
+    fn device_names(&self) -> Vec<String> {
+        rodio::cpal::default_host()
+            .output_devices()
+            .map(|devices| {
+                devices.filter_map(|device| device.name().ok()).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn open(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>> {
+        let (stream, handle) = match name {
+            Some(name) => {
+                OutputStream::try_from_device(&find_device(Some(name))?)?
+            }
+            None => OutputStream::try_default()?,
+        };
+        Ok(Box::new(RodioOutput {
+            _stream: stream,
+            handle,
+            name: name.map(String::from),
+            format: None,
+        }))
+    }
+
+    fn open_native(
+        &self,
+        name: Option<&str>,
+        format: StreamFormat,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>> {
+        let device = find_device(name)?;
+        let configs = device.supported_output_configs()?.collect::<Vec<_>>();
+        let config = lossless_formats(format.bits_per_sample)
+            .iter()
+            .find_map(|sample_format| {
+                configs.iter().find(|config| {
+                    config.channels() == format.channels
+                        && config.sample_format() == *sample_format
+                        && config.min_sample_rate().0 <= format.sample_rate
+                        && config.max_sample_rate().0 >= format.sample_rate
+                })
+            })
+            .ok_or_else(|| {
+                format!(
+                    "it can't play {} Hz with {} channels natively",
+                    format.sample_rate, format.channels
+                )
+            })?
+            .with_sample_rate(SampleRate(format.sample_rate));
+        let device_format = DeviceFormat {
+            sample_rate: format.sample_rate,
+            channels: format.channels,
+            sample_format: config.sample_format(),
+        };
+        let (stream, handle) =
+            OutputStream::try_from_device_config(&device, config)?;
+        Ok(Box::new(RodioOutput {
+            _stream: stream,
+            handle,
+            name: name.map(String::from),
+            format: Some(device_format),
+        }))
+    }
+}
+
+impl Output for RodioOutput {
+    fn sink(&self) -> Result<rodio::Sink, Box<dyn Error>> {
+        Ok(rodio::Sink::try_new(&self.handle)?)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn format(&self) -> Option<DeviceFormat> {
+        self.format
+    }
+}
@@ -0,0 +1,97 @@
+//! Measuring a track's exact duration from its audio, rather than trusting its
+//! tags or headers.
+
+use std::{error::Error, fs::File, path::Path, time::Duration};
+
+use symphonia::core::{
+    errors::Error as SymphoniaError,
+    formats::FormatOptions,
+    io::{MediaSourceStream, MediaSourceStreamOptions},
+    meta::{Limit, MetadataOptions},
+    probe::Hint,
+};
+
+use super::frames_to_duration;
+
+/// How far a track's duration may be off before it's reported as wrong.
+/// Encoders pad MP3s by a fraction of a frame, which isn't worth a mention.
+pub const TOLERANCE: Duration = Duration::from_millis(250);
+
+/// Measures how long the track at `path` plays for, by reading through every
+/// packet of it. Packets which don't say how long they are, as with some
+/// formats and broken files, are decoded to find out.
+///
+/// This counts every frame of a VBR MP3, so it's right even without a Xing
+/// header to say how many there are.
+pub fn exact_duration(path: &Path) -> Result<Duration, Box<dyn Error>> {
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(
+                Box::new(File::open(path)?),
+                MediaSourceStreamOptions::default(),
+            ),
+            &FormatOptions::default(),
+            &MetadataOptions {
+                limit_metadata_bytes: Limit::Maximum(0),
+                limit_visual_bytes:   Limit::Maximum(0),
+            },
+        )?
+        .format;
+    let track = format.default_track().ok_or("there's no audio track")?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let sample_rate = params.sample_rate.ok_or("the sample rate is unknown")?;
+
+    let mut decoder = None;
+    // timestamps are counted in the track's time base, if it has one, and
+    // decoded frames at the sample rate:
+    let mut timestamps = 0;
+    let mut frames = 0;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        if packet.dur() > 0 && params.time_base.is_some() {
+            timestamps += packet
+                .dur()
+                .saturating_sub((packet.trim_start + packet.trim_end) as u64);
+            continue;
+        }
+        let decoder = match &mut decoder {
+            Some(decoder) => decoder,
+            None => decoder.insert(
+                symphonia::default::get_codecs()
+                    .make(&params, &Default::default())?,
+            ),
+        };
+        // a damaged packet doesn't play, so it doesn't count:
+        if let Ok(decoded) = decoder.decode(&packet) {
+            frames += decoded.frames() as u64;
+        }
+    }
+
+    let time = params.time_base.map_or(Duration::ZERO, |time_base| {
+        let time = time_base.calc_time(timestamps);
+        Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+    });
+    Ok(time + frames_to_duration(frames, sample_rate))
+}
+
+/// Whether `stated` is close enough to `exact` to be trusted.
+pub fn agrees(stated: Option<Duration>, exact: Duration) -> bool {
+    stated.is_some_and(|stated| stated.abs_diff(exact) <= TOLERANCE)
+}
@@ -1,8 +1,49 @@
-pub use output::AudioStream;
+use std::time::Duration;
+
+pub use backend::{Backend, Output, backend};
+pub use output::{AudioStream, Failure, Position, StreamFormat};
 pub use pcm::*;
 
+pub mod backend;
+pub mod device;
+pub mod duration;
+pub mod null;
 pub mod output;
 pub mod pcm;
+pub mod render;
 
 const WRITE_THRESHOLD: usize = 8;
 const RINGBUF_CAPACITY: usize = 65536 + WRITE_THRESHOLD;
+/// How far below full volume the bottom of the volume slider is.
+const VOLUME_RANGE_DB: f32 = 60.0;
+
+/// The time at which the given frame is played.
+pub fn frames_to_duration(frames: u64, sample_rate: u32) -> Duration {
+    Duration::from_nanos(
+        (frames as u128 * 1_000_000_000 / sample_rate as u128) as u64,
+    )
+}
+
+/// The amplitude a volume slider `position` from 0.0 - 1.0 plays at. The
+/// slider moves evenly in decibels, as loudness is heard, rather than in
+/// amplitude, which would crowd every quiet level into its bottom end.
+pub fn volume_gain(position: f32) -> f32 {
+    if position <= 0.0 {
+        return 0.0;
+    }
+    db_to_gain(VOLUME_RANGE_DB * (position.min(1.0) - 1.0))
+}
+
+/// The volume slider position which plays at amplitude `gain`, the inverse
+/// of `volume_gain`. Anything too quiet for the slider is at the bottom.
+pub fn volume_position(gain: f32) -> f32 {
+    if gain <= 0.0 {
+        return 0.0;
+    }
+    (1.0 + 20.0 * gain.log10() / VOLUME_RANGE_DB).clamp(0.0, 1.0)
+}
+
+/// The amplitude a change in level of `db` decibels scales samples by.
+pub fn db_to_gain(db: f32) -> f32 {
+    10_f32.powf(db / 20.0)
+}
@@ -0,0 +1,159 @@
+//! An output which plays into nothing, taking samples at the rate a device
+//! would, or faster, so playback behaves as it would on a real device without
+//! needing one.
+
+use std::{
+    error::Error,
+    thread,
+    time::{Duration, Instant},
+};
+
+use rodio::{Source, queue::SourcesQueueOutput};
+
+use super::{
+    StreamFormat,
+    backend::{Backend, Output},
+    device::{DeviceFormat, lossless_formats},
+};
+
+/// The name of the one device the null backend has.
+pub const NULL_DEVICE: &str = "Null output";
+
+/// How much audio is taken at a time.
+const CHUNK: Duration = Duration::from_millis(10);
+
+pub struct NullBackend {
+    speed: f32,
+}
+
+impl NullBackend {
+    /// A backend whose outputs play at `speed` times real time, or as fast as
+    /// samples can be decoded if it's infinite.
+    pub fn new(speed: f32) -> Self {
+        assert!(speed > 0.0, "the speed must be positive");
+        Self { speed }
+    }
+}
+
+impl Backend for NullBackend {
+    fn device_names(&self) -> Vec<String> {
+        vec![NULL_DEVICE.to_owned()]
+    }
+
+    fn open(
+        &self,
+        name: Option<&str>,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>> {
+        if let Some(name) = name
+            && name != NULL_DEVICE
+        {
+            return Err(format!("there's no device called {name}").into());
+        }
+        Ok(Box::new(NullOutput {
+            speed:  self.speed,
+            name:   name.map(String::from),
+            format: None,
+        }))
+    }
+
+    fn open_native(
+        &self,
+        name: Option<&str>,
+        format: StreamFormat,
+    ) -> Result<Box<dyn Output>, Box<dyn Error>> {
+        self.open(name)?;
+        // any format can be played into nothing:
+        Ok(Box::new(NullOutput {
+            speed:  self.speed,
+            name:   name.map(String::from),
+            format: Some(DeviceFormat {
+                sample_rate: format.sample_rate,
+                channels: format.channels,
+                sample_format: lossless_formats(format.bits_per_sample)[0],
+            }),
+        }))
+    }
+}
+
+pub struct NullOutput {
+    speed:  f32,
+    name:   Option<String>,
+    format: Option<DeviceFormat>,
+}
+
+impl Output for NullOutput {
+    fn sink(&self) -> Result<rodio::Sink, Box<dyn Error>> {
+        let (sink, queue) = rodio::Sink::new_idle();
+        let speed = self.speed;
+        thread::Builder::new()
+            .name("null output".into())
+            .spawn(move || consume(queue, speed))?;
+        Ok(sink)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn format(&self) -> Option<DeviceFormat> {
+        self.format
+    }
+}
+
+/// Takes samples from `queue` at `speed` times real time until the sink it
+/// belongs to is dropped.
+fn consume(mut queue: SourcesQueueOutput<f32>, speed: f32) {
+    let start = Instant::now();
+    let mut played = Duration::ZERO;
+    loop {
+        let samples = (queue.sample_rate() as u128 * CHUNK.as_nanos()
+            / 1_000_000_000) as usize
+            * queue.channels() as usize;
+        // the queue only ends once the sink's gone:
+        if queue.by_ref().take(samples).count() < samples {
+            return;
+        }
+        played += CHUNK;
+        let due = start + played.div_f32(speed);
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rodio::source::SineWave;
+
+    use super::*;
+
+    #[test]
+    fn null_output_plays_at_its_speed() {
+        let output = NullBackend::new(10.0).open(None).unwrap();
+        let sink = output.sink().unwrap();
+        let start = Instant::now();
+        sink.append(SineWave::new(440.0).take_duration(Duration::from_secs(1)));
+        sink.sleep_until_end();
+        let elapsed = start.elapsed();
+        assert!(
+            (Duration::from_millis(90)..Duration::from_millis(500))
+                .contains(&elapsed),
+            "a second took {elapsed:?} at ten times real time"
+        );
+        assert!(sink.empty());
+    }
+
+    #[test]
+    fn only_the_null_device_opens() {
+        let backend = NullBackend::new(1.0);
+        assert!(backend.open(Some(NULL_DEVICE)).is_ok());
+        assert!(backend.open(Some("Speakers")).is_err());
+        let format = StreamFormat {
+            sample_rate: 96_000,
+            channels: 2,
+            bits_per_sample: Some(24),
+        };
+        let output = backend.open_native(None, format).unwrap();
+        assert_eq!(output.format().unwrap().sample_rate, 96_000);
+    }
+}
@@ -1,4 +1,13 @@
-use std::{fs::File, path::PathBuf, time::Duration};
+use std::{
+    error::Error,
+    fs::File,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
 use ringbuf::{HeapCons, HeapProd, HeapRb, traits::*};
 use rodio::Source;
@@ -6,11 +15,13 @@ use symphonia::core::{
     audio::SampleBuffer,
     codecs::{CodecRegistry, Decoder, DecoderOptions},
     conv::ConvertibleSample,
+    errors::{Error as SymphoniaError, SeekErrorKind},
     formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
     io::{MediaSourceStream, MediaSourceStreamOptions},
     meta::{Limit, MetadataOptions},
     probe::{Hint, Probe},
     sample::SampleFormat,
+    units::TimeBase,
 };
 use tokio::{
     sync::mpsc::{self, Receiver, Sender},
@@ -19,35 +30,153 @@ use tokio::{
 
 use super::*;
 
+/// How many frames before a seek's target decoding starts from, which is the
+/// largest block a Vorbis decoder can need to warm up.
+const SEEK_PREROLL: u64 = 8192;
+
+/// How many damaged packets in a row are skipped before a track is given up
+/// on.
+const MAX_DAMAGED_PACKETS: usize = 100;
+
+/// How far short of its length a track can run out and still count as having
+/// ended, allowing for lengths which were only estimated.
+const TRUNCATION_TOLERANCE: Duration = Duration::from_secs(1);
+
 enum StreamMessage {
-    Seek(Duration),
+    /// Seeks to a time, numbering the request so the stream can tell when
+    /// it's been done.
+    Seek(Duration, u64),
     Vacancy,
 }
 
+/// How far into its track an [`AudioStream`] has played, readable after the
+/// stream is handed to a sink.
+#[derive(Clone, Debug, Default)]
+pub struct Position(Arc<AtomicU64>);
+
+impl Position {
+    pub fn get(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, pos: Duration) {
+        self.0.store(pos.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Why the track an [`AudioStream`] was playing stopped before its end, if it
+/// did, readable after the stream is handed to a sink.
+#[derive(Clone, Debug, Default)]
+pub struct Failure(Arc<Mutex<Option<SymphoniaError>>>);
+
+impl Failure {
+    /// Takes the error which stopped the track, if there was one.
+    pub fn take(&self) -> Option<SymphoniaError> {
+        self.0.lock().unwrap().take()
+    }
+
+    fn set(&self, error: SymphoniaError) {
+        *self.0.lock().unwrap() = Some(error);
+    }
+}
+
+/// What to do about an error from the demuxer or decoder.
+#[derive(Debug, PartialEq)]
+enum Recovery {
+    /// Only the packet is damaged, so playback carries on after it.
+    Skip,
+    /// The decoder has to be reset before it can carry on.
+    Reset,
+    /// The track has run out, which is only a failure if it's well short of
+    /// its length (see [`TRUNCATION_TOLERANCE`]).
+    End,
+    /// Nothing more can be played.
+    Fail,
+}
+
+impl Recovery {
+    fn of(error: &SymphoniaError) -> Self {
+        match error {
+            SymphoniaError::DecodeError(_) => Self::Skip,
+            SymphoniaError::ResetRequired => Self::Reset,
+            SymphoniaError::IoError(e)
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                Self::End
+            }
+            _ => Self::Fail,
+        }
+    }
+}
+
+/// The shape of a track's samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// How deep the samples are, for formats that store them as integers.
+    pub bits_per_sample: Option<u32>,
+}
+
+/// The decoder's account of the last seek it carried out.
+#[derive(Default)]
+struct Seeked {
+    generation: u64,
+    /// How many samples the decoder had written before the seek, all of which
+    /// should be thrown away.
+    boundary: u64,
+    /// The frame the first sample written after the seek belongs to.
+    frame: u64,
+}
+
 pub struct AudioStream {
     channels: u16,
+    bits_per_sample: Option<u32>,
     handle: JoinHandle<()>,
     read_counter: usize,
     ring_buf_reader: HeapCons<f32>,
     sample_rate: u32,
-    total_duration: Duration,
+    total_duration: Option<Duration>,
     tcx: Sender<StreamMessage>,
+
+    position: Position,
+    failure: Failure,
+    seeked: Arc<Mutex<Seeked>>,
+    /// The last seek requested, which is still pending if it's ahead of the
+    /// decoder's.
+    seek_generation: u64,
+    seek_pending: bool,
+    /// Samples taken out of the ring buffer so far, whether played or not.
+    read: u64,
+    /// The frame played samples are counted from, and how many samples have
+    /// been played since.
+    base_frame: u64,
+    played: u64,
 }
 
 impl AudioStream {
+    /// Opens the track at `path` and starts decoding it, failing if it can't
+    /// be read or isn't in a format that can be played. `duration` is the
+    /// track's length, if it's known, so that a file which has been cut short
+    /// is reported as a failure when it runs out. If `exact` is set, samples
+    /// are scaled so that they reach a device of the same depth unchanged
+    /// (see [`pcm_s16_to_exact`]).
     pub fn new(
         path: &PathBuf,
         codec_registry: &CodecRegistry,
         probe: &Probe,
-        duration: Duration,
-    ) -> Self {
+        duration: Option<Duration>,
+        exact: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|ext| ext.to_str()) {
+            hint.with_extension(extension);
+        }
         let format = probe
             .format(
-                Hint::new().with_extension(
-                    path.extension().unwrap().to_str().unwrap(),
-                ),
+                &hint,
                 MediaSourceStream::new(
-                    Box::new(File::open(path).unwrap()),
+                    Box::new(File::open(path)?),
                     MediaSourceStreamOptions::default(),
                 ),
                 &FormatOptions {
@@ -58,33 +187,49 @@ impl AudioStream {
                     limit_metadata_bytes: Limit::Maximum(0), // we already have metadata
                     limit_visual_bytes:   Limit::Maximum(0), // visuals currently aren't used
                 },
-            )
-            .unwrap()
+            )?
             .format;
-        let decoder = codec_registry
-            .make(
-                &format.default_track().unwrap().codec_params,
-                &DecoderOptions::default(),
-            )
-            .unwrap();
+        let codec_params = &format
+            .default_track()
+            .ok_or("there's no audio track")?
+            .codec_params;
+        let decoder =
+            codec_registry.make(codec_params, &DecoderOptions::default())?;
 
         let codec_params = &format.tracks()[0].codec_params;
-        let channels = codec_params.channels.unwrap().count() as u16;
-        let sample_rate = codec_params.sample_rate.unwrap();
+        let channels = codec_params
+            .channels
+            .ok_or("the number of channels is unknown")?
+            .count() as u16;
+        let sample_rate = codec_params
+            .sample_rate
+            .ok_or("the sample rate is unknown")?;
+        let bits_per_sample = codec_params.bits_per_sample;
 
         let (prod, cons) = HeapRb::<f32>::new(RINGBUF_CAPACITY).split();
         let (tcx, rcx) = mpsc::channel(16);
+        let seeked = Arc::new(Mutex::new(Seeked::default()));
+        let failure = Failure::default();
+        let context = DecoderContext {
+            seeked: seeked.clone(),
+            failure: failure.clone(),
+            expected_frames: duration.map(|duration| {
+                (duration.as_nanos() * sample_rate as u128 / 1_000_000_000)
+                    as u64
+            }),
+        };
 
-        let sample_format = codec_params.sample_format.unwrap_or_else(|| {
-            match codec_params.bits_per_sample.unwrap_or(32) {
+        let sample_format = match codec_params.sample_format {
+            Some(format) => format,
+            None => match codec_params.bits_per_sample.unwrap_or(32) {
                 8 => SampleFormat::S8,
                 16 => SampleFormat::S16,
                 24 => SampleFormat::S24,
                 32 => SampleFormat::S32,
                 64 => SampleFormat::F64,
-                _ => panic!(),
-            }
-        });
+                _ => return Err("the sample format is unsupported".into()),
+            },
+        };
 
         let handle = tokio::spawn(async move {
             match sample_format {
@@ -94,7 +239,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_u8_to_ieee,
+                        context,
+                        if exact {
+                            pcm_u8_to_exact
+                        } else {
+                            pcm_u8_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -104,7 +254,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_u16_to_ieee,
+                        context,
+                        if exact {
+                            pcm_u16_to_exact
+                        } else {
+                            pcm_u16_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -114,7 +269,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_u24_to_ieee,
+                        context,
+                        if exact {
+                            pcm_u24_to_exact
+                        } else {
+                            pcm_u24_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -124,7 +284,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_u32_to_ieee,
+                        context,
+                        if exact {
+                            pcm_u32_to_exact
+                        } else {
+                            pcm_u32_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -134,7 +299,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_s8_to_ieee,
+                        context,
+                        if exact {
+                            pcm_s8_to_exact
+                        } else {
+                            pcm_s8_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -144,7 +314,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_s16_to_ieee,
+                        context,
+                        if exact {
+                            pcm_s16_to_exact
+                        } else {
+                            pcm_s16_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -154,7 +329,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_s24_to_ieee,
+                        context,
+                        if exact {
+                            pcm_s24_to_exact
+                        } else {
+                            pcm_s24_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -164,7 +344,12 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
-                        pcm_s32_to_ieee,
+                        context,
+                        if exact {
+                            pcm_s32_to_exact
+                        } else {
+                            pcm_s32_to_ieee
+                        },
                     );
                     decoder.run().await;
                 }
@@ -174,6 +359,7 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
+                        context,
                         |x| x,
                     );
                     decoder.run().await;
@@ -184,6 +370,7 @@ impl AudioStream {
                         format,
                         rcx,
                         prod,
+                        context,
                         |x: f64| x as f32,
                     );
                     decoder.run().await;
@@ -191,32 +378,135 @@ impl AudioStream {
             }
         });
 
-        Self {
+        Ok(Self {
             channels,
+            bits_per_sample,
             handle,
             read_counter: 0,
             ring_buf_reader: cons,
             sample_rate,
             total_duration: duration,
             tcx,
+            position: Position::default(),
+            failure,
+            seeked,
+            seek_generation: 0,
+            seek_pending: false,
+            read: 0,
+            base_frame: 0,
+            played: 0,
+        })
+    }
+
+    pub fn format(&self) -> StreamFormat {
+        StreamFormat {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            bits_per_sample: self.bits_per_sample,
         }
     }
-}
 
-impl Iterator for AudioStream {
-    type Item = f32;
+    /// A handle on how far the stream has played, which keeps working after
+    /// the stream is handed to a sink.
+    pub fn position_handle(&self) -> Position {
+        self.position.clone()
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// A handle on why the stream stopped early, if it does, which keeps
+    /// working after the stream is handed to a sink.
+    pub fn failure_handle(&self) -> Failure {
+        self.failure.clone()
+    }
+
+    /// Whether the decoder is yet to carry out the last seek. Once it has,
+    /// anything it wrote beforehand is thrown away.
+    fn check_seek(&mut self) -> bool {
+        if !self.seek_pending {
+            return false;
+        }
+        let Ok(seeked) = self.seeked.try_lock() else {
+            return true;
+        };
+        if seeked.generation != self.seek_generation {
+            return true;
+        }
+        while self.read < seeked.boundary {
+            match self.ring_buf_reader.try_pop() {
+                Some(_) => self.read += 1,
+                // the rest hasn't been written yet:
+                None => return true,
+            }
+        }
+        self.base_frame = seeked.frame;
+        self.played = 0;
+        self.seek_pending = false;
+        false
+    }
+
+    /// Takes the next sample, waiting for the decoder rather than filling in
+    /// with silence, or returns `None` once the track ends. This is for
+    /// reading a stream other than in real time, as when it's rendered.
+    pub fn next_decoded(&mut self) -> Option<f32> {
+        loop {
+            match self.pull() {
+                Pull::Sample(sample) => return Some(sample),
+                Pull::Waiting => std::thread::sleep(Duration::from_millis(1)),
+                Pull::Ended => return None,
+            }
+        }
+    }
+
+    fn pull(&mut self) -> Pull {
         self.read_counter += 1;
         if self.read_counter >= (WRITE_THRESHOLD - 1) {
             self.tcx.blocking_send(StreamMessage::Vacancy);
             self.read_counter = 0;
         }
-        let res = self.ring_buf_reader.try_pop();
-        if res.is_none() {
-            (!self.handle.is_finished()).then_some(0_f32)
+        if self.check_seek() {
+            return self.waiting_or_ended();
+        }
+        let Some(sample) = self.ring_buf_reader.try_pop() else {
+            return self.waiting_or_ended();
+        };
+        self.read += 1;
+        self.played += 1;
+        if self.played.is_multiple_of(self.channels as u64) {
+            let frame = self.base_frame + self.played / self.channels as u64;
+            self.position
+                .set(frames_to_duration(frame, self.sample_rate));
+        }
+        Pull::Sample(sample)
+    }
+
+    /// Whether an empty buffer means the decoder has more to come.
+    fn waiting_or_ended(&self) -> Pull {
+        // it may have written its last samples just before finishing:
+        if !self.handle.is_finished() || !self.ring_buf_reader.is_empty() {
+            Pull::Waiting
         } else {
-            res
+            Pull::Ended
+        }
+    }
+}
+
+/// What's next to play from an [`AudioStream`].
+enum Pull {
+    Sample(f32),
+    /// The decoder hasn't caught up.
+    Waiting,
+    Ended,
+}
+
+impl Iterator for AudioStream {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // a device can't wait, so it's given silence until the decoder
+        // catches up:
+        match self.pull() {
+            Pull::Sample(sample) => Some(sample),
+            Pull::Waiting => Some(0.0),
+            Pull::Ended => None,
         }
     }
 }
@@ -235,15 +525,20 @@ impl Source for AudioStream {
     }
 
     fn total_duration(&self) -> Option<Duration> {
-        Some(self.total_duration)
+        self.total_duration
     }
 
     fn try_seek(
         &mut self,
         pos: Duration,
     ) -> Result<(), rodio::source::SeekError> {
-        self.ring_buf_reader.clear();
-        self.tcx.blocking_send(StreamMessage::Seek(pos));
+        // everything buffered so far was decoded before the seek:
+        self.read += self.ring_buf_reader.clear() as u64;
+        self.seek_generation += 1;
+        self.seek_pending = true;
+        self.position.set(pos);
+        self.tcx
+            .blocking_send(StreamMessage::Seek(pos, self.seek_generation));
         Ok(())
     }
 }
@@ -264,6 +559,34 @@ where
     rcx: Receiver<StreamMessage>,
     rcx_buf: Vec<StreamMessage>,
     ring_buf_writer: HeapProd<f32>,
+
+    seeked: Arc<Mutex<Seeked>>,
+    failure: Failure,
+    /// How many frames the track should have, if that's known.
+    expected_frames: Option<u64>,
+    time_base: Option<TimeBase>,
+    sample_rate: u32,
+    /// Samples written to the ring buffer so far.
+    written: u64,
+    /// The frame following the last one decoded.
+    next_frame: u64,
+    /// Frames before this one are decoded but not written, so that a seek
+    /// lands on the exact frame asked for.
+    skip_until: u64,
+    /// A seek which is reported to the stream once the first packet after it
+    /// is decoded.
+    pending_seek: Option<Seeked>,
+    /// Set once a seek goes past the end of the track.
+    ended: bool,
+}
+
+/// What a decoder shares with the stream it decodes for, and knows of the
+/// track beforehand.
+struct DecoderContext {
+    seeked: Arc<Mutex<Seeked>>,
+    failure: Failure,
+    /// How many frames the track should have, if that's known.
+    expected_frames: Option<u64>,
 }
 
 enum WaitErr {
@@ -277,9 +600,19 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
         format: Box<dyn FormatReader + 'static>,
         rcx: Receiver<StreamMessage>,
         ring_buf_writer: HeapProd<f32>,
+        context: DecoderContext,
         convert: fn(S) -> f32,
     ) -> Self {
+        let DecoderContext {
+            seeked,
+            failure,
+            expected_frames,
+        } = context;
         let rcx_buf = Vec::with_capacity(16);
+        let codec_params = &format.tracks()[0].codec_params;
+        let time_base = codec_params.time_base;
+        // checked by `AudioStream::new`:
+        let sample_rate = codec_params.sample_rate.unwrap();
         Self {
             convert,
             inner: decoder,
@@ -287,6 +620,16 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
             rcx,
             rcx_buf,
             ring_buf_writer,
+            seeked,
+            failure,
+            expected_frames,
+            time_base,
+            sample_rate,
+            written: 0,
+            next_frame: 0,
+            skip_until: 0,
+            pending_seek: None,
+            ended: false,
         }
     }
 
@@ -297,27 +640,32 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
             self.rcx_buf
                 .iter()
                 .filter_map(|msg| {
-                    if let StreamMessage::Seek(target) = msg {
-                        Some(*target)
+                    if let StreamMessage::Seek(target, generation) = msg {
+                        Some((*target, *generation))
                     } else {
                         None
                     }
                 })
                 .next_back()
-                .inspect(|target| {
-                    self.seek(*target);
+                .inspect(|(target, generation)| {
+                    self.seek(*target, *generation);
                 });
-            let Some(sample_buf) = self.get_packet() else {
+            if self.ended {
+                break;
+            }
+            let Some((sample_buf, skip)) = self.get_packet() else {
                 break;
             };
-            let packet_len = sample_buf.len();
+            let samples = &sample_buf.samples()[skip..];
+            let packet_len = samples.len();
             match self.wait_for_vacancy(packet_len).await {
                 Ok(_) => {
                     let mut samples =
-                        sample_buf.samples().iter().map(|s| (self.convert)(*s));
+                        samples.iter().map(|s| (self.convert)(*s));
                     let written =
                         self.ring_buf_writer.push_iter(samples.by_ref());
                     debug_assert_eq!(written, packet_len);
+                    self.written += written as u64;
                 }
                 Err(WaitErr::SeekRequested) => {
                     continue;
@@ -329,20 +677,129 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
         }
     }
 
-    fn get_packet(&mut self) -> Option<SampleBuffer<S>> {
-        let packet =
-            self.inner.decode(&self.format.next_packet().ok()?).unwrap();
-        let spec = packet.spec();
-        let mut samples = SampleBuffer::<S>::new(packet.frames() as u64, *spec);
-        samples.copy_interleaved_ref(packet);
-        Some(samples)
+    /// Decodes the next packet with any frames to be played, returning it
+    /// along with how many of its samples come before the frame being sought
+    /// to. Damaged packets are skipped over, and `None` is returned once the
+    /// track ends or can't be played any further.
+    fn get_packet(&mut self) -> Option<(SampleBuffer<S>, usize)> {
+        let mut damaged = 0;
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(e) => {
+                    if self.recover(e, &mut damaged) {
+                        continue;
+                    }
+                    return None;
+                }
+            };
+            let start = self.ts_to_frame(packet.ts());
+            let decoded = match self.inner.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    if self.recover(e, &mut damaged) {
+                        continue;
+                    }
+                    return None;
+                }
+            };
+            let frames = decoded.frames() as u64;
+            self.next_frame = start + frames;
+            // vorbis decodes no frames from the first packet after a seek:
+            if self.next_frame <= self.skip_until || frames == 0 {
+                continue;
+            }
+            let skip = self.skip_until.saturating_sub(start);
+            if let Some(seeked) = self.pending_seek.take() {
+                *self.seeked.lock().unwrap() = Seeked {
+                    frame: start + skip,
+                    ..seeked
+                };
+            }
+            let spec = *decoded.spec();
+            let mut samples = SampleBuffer::<S>::new(frames, spec);
+            samples.copy_interleaved_ref(decoded);
+            return Some((samples, (skip as usize) * spec.channels.count()));
+        }
     }
 
-    fn seek(&mut self, target: Duration) {
-        self.format.seek(SeekMode::Accurate, SeekTo::Time {
-            time:     target.into(),
+    /// Deals with an error from the demuxer or decoder, returning whether
+    /// decoding can carry on. `damaged` counts the damaged packets skipped in
+    /// a row.
+    fn recover(&mut self, error: SymphoniaError, damaged: &mut usize) -> bool {
+        let recovery = match Recovery::of(&error) {
+            Recovery::Skip | Recovery::Reset
+                if *damaged >= MAX_DAMAGED_PACKETS =>
+            {
+                Recovery::Fail
+            }
+            recovery => recovery,
+        };
+        match recovery {
+            Recovery::Skip => *damaged += 1,
+            Recovery::Reset => {
+                *damaged += 1;
+                self.inner.reset();
+            }
+            Recovery::End => {
+                let tolerance =
+                    TRUNCATION_TOLERANCE.as_secs() * self.sample_rate as u64;
+                if self.expected_frames.is_some_and(|expected| {
+                    self.next_frame + tolerance < expected
+                }) {
+                    self.failure.set(SymphoniaError::IoError(
+                        std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "the file ends before the track does",
+                        ),
+                    ));
+                }
+            }
+            Recovery::Fail => self.failure.set(error),
+        }
+        matches!(recovery, Recovery::Skip | Recovery::Reset)
+    }
+
+    fn ts_to_frame(&self, ts: u64) -> u64 {
+        match self.time_base {
+            Some(tb) => {
+                (ts as u128 * tb.numer as u128 * self.sample_rate as u128
+                    / tb.denom as u128) as u64
+            }
+            None => ts,
+        }
+    }
+
+    /// Seeks to `target`, so that the next packet decoded tells the stream
+    /// which samples to throw away and which frame playback picks up from.
+    fn seek(&mut self, target: Duration, generation: u64) {
+        // a decoder needs a packet or two before it gives out frames again, so
+        // it's started early enough to have them by the target:
+        let preroll = frames_to_duration(SEEK_PREROLL, self.sample_rate);
+        let seeked = self.format.seek(SeekMode::Accurate, SeekTo::Time {
+            time:     target.saturating_sub(preroll).into(),
             track_id: None,
         });
+        match seeked {
+            Ok(_) => {
+                self.inner.reset();
+                self.skip_until = (target.as_nanos() * self.sample_rate as u128
+                    / 1_000_000_000) as u64;
+            }
+            Err(SymphoniaError::SeekError(SeekErrorKind::OutOfRange)) => {
+                self.ended = true;
+            }
+            // playback carries on from where it was:
+            Err(e) => {
+                eprintln!("Couldn't seek: {e}");
+                self.skip_until = self.next_frame;
+            }
+        }
+        self.pending_seek = Some(Seeked {
+            generation,
+            boundary: self.written,
+            frame: self.skip_until,
+        });
     }
 
     /// Returns `WaitErr::SeekRequested` if the decoder received and handled a
@@ -358,8 +815,8 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
             tokio::select! {
                 msg = self.rcx.recv() => {
                     match msg {
-                        Some(StreamMessage::Seek(target)) => {
-                            self.seek(target);
+                        Some(StreamMessage::Seek(target, generation)) => {
+                            self.seek(target, generation);
                             return Err(WaitErr::SeekRequested);
                         }
                         Some(StreamMessage::Vacancy) => {
@@ -378,3 +835,175 @@ impl<S: ConvertibleSample> SymphoniaDecoder<S> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WALTZ: &str =
+        "test/Minute_Waltz,_by_Chopin_-_Performed_by_Sergej_Rachmaninoff.ogg";
+
+    fn open(path: &str) -> AudioStream {
+        AudioStream::new(
+            &PathBuf::from(path),
+            symphonia::default::get_codecs(),
+            symphonia::default::get_probe(),
+            Some(Duration::from_secs(121)),
+            false,
+        )
+        .unwrap()
+    }
+
+    /// Writes a copy of the waltz, broken by `damage`, returning its path.
+    fn damaged_copy(name: &str, damage: impl FnOnce(&mut Vec<u8>)) -> PathBuf {
+        let mut bytes = std::fs::read(WALTZ).unwrap();
+        damage(&mut bytes);
+        let path = std::env::temp_dir()
+            .join(format!("polypody-{name}-{}.ogg", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// Plays `stream` until it ends, returning how far it got.
+    fn play_out(stream: &mut AudioStream) -> Duration {
+        let position = stream.position_handle();
+        let start = std::time::Instant::now();
+        while stream.next().is_some() {
+            assert!(start.elapsed() < Duration::from_secs(60));
+        }
+        position.get()
+    }
+
+    /// Plays `count` frames from `stream`, returning their samples.
+    fn play(stream: &mut AudioStream, count: u64) -> Vec<f32> {
+        let channels = stream.channels() as usize;
+        let position = stream.position_handle();
+        let mut samples = vec![];
+        let mut last = position.get();
+        let mut pending = vec![];
+        while samples.len() < count as usize * channels {
+            pending.push(stream.next().expect("the stream ended early"));
+            // the position moves once a whole frame has played, and not for
+            // silence while waiting on the decoder:
+            if position.get() != last {
+                last = position.get();
+                samples.extend_from_slice(&pending[pending.len() - channels..]);
+                pending.clear();
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn seeks_land_on_the_exact_frame() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let mut stream = open(WALTZ);
+        let rate = stream.sample_rate();
+        let position = stream.position_handle();
+        assert_eq!(position.get(), Duration::ZERO);
+        play(&mut stream, 4410);
+
+        let target = Duration::from_millis(30_250);
+        stream.try_seek(target).unwrap();
+        assert_eq!(position.get(), target);
+        let target_frame = (target.as_secs_f64() * rate as f64) as u64;
+        let seeked = play(&mut stream, 100);
+        assert_eq!(
+            position.get(),
+            frames_to_duration(target_frame + 100, rate)
+        );
+
+        // the same frames, decoded straight through from the start:
+        let mut straight = open(WALTZ);
+        play(&mut straight, target_frame);
+        let expected = play(&mut straight, 100);
+        let error = seeked
+            .iter()
+            .zip(&expected)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(error < 0.01, "seeked audio differs by {error}");
+    }
+
+    #[test]
+    fn seeking_past_the_end_ends_the_stream() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let mut stream = open(WALTZ);
+        stream.try_seek(Duration::from_secs(600)).unwrap();
+        let start = std::time::Instant::now();
+        while stream.next().is_some() {
+            assert!(start.elapsed() < Duration::from_secs(10));
+        }
+    }
+
+    #[test]
+    fn damaged_tracks_play_as_far_as_they_can() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let _guard = rt.enter();
+
+        let truncated = damaged_copy("truncated", |bytes| {
+            bytes.truncate(bytes.len() / 2 + 1234)
+        });
+        let mut stream = open(truncated.to_str().unwrap());
+        let failure = stream.failure_handle();
+        let played = play_out(&mut stream);
+        assert!(played > Duration::from_secs(50), "stopped at {played:?}");
+        assert!(played < Duration::from_secs(70), "went on to {played:?}");
+        // it's cut short, rather than over:
+        assert!(failure.take().is_some());
+
+        let corrupted = damaged_copy("corrupted", |bytes| {
+            let middle = bytes.len() / 2;
+            bytes[middle..middle + 20_000].fill(0x55);
+        });
+        let mut stream = open(corrupted.to_str().unwrap());
+        let failure = stream.failure_handle();
+        let played = play_out(&mut stream);
+        assert!(played > Duration::from_secs(115), "stopped at {played:?}");
+        assert!(failure.take().is_none());
+
+        let garbage = damaged_copy("garbage", |bytes| bytes.fill(0x55));
+        let missing = std::env::temp_dir().join("polypody-missing.ogg");
+        for path in [&garbage, &missing] {
+            assert!(
+                AudioStream::new(
+                    path,
+                    symphonia::default::get_codecs(),
+                    symphonia::default::get_probe(),
+                    Some(Duration::from_secs(121)),
+                    false,
+                )
+                .is_err()
+            );
+        }
+
+        for path in [truncated, corrupted, garbage] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        use std::io::{Error as IoError, ErrorKind};
+
+        let eof = IoError::from(ErrorKind::UnexpectedEof);
+        let denied = IoError::from(ErrorKind::PermissionDenied);
+        assert_eq!(
+            Recovery::of(&SymphoniaError::DecodeError("bad frame")),
+            Recovery::Skip
+        );
+        assert_eq!(
+            Recovery::of(&SymphoniaError::ResetRequired),
+            Recovery::Reset
+        );
+        assert_eq!(Recovery::of(&SymphoniaError::IoError(eof)), Recovery::End);
+        assert_eq!(
+            Recovery::of(&SymphoniaError::IoError(denied)),
+            Recovery::Fail
+        );
+    }
+}
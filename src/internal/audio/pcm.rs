@@ -74,9 +74,49 @@ pub fn pcm_u32_to_ieee(sample: u32) -> f32 {
     (sample as f32 * P_U32MAX_I) - 1.0
 }
 
+// For bit-perfect output, samples are instead scaled by a power of two, which
+// loses nothing for depths up to 24 bits, and which is exactly what's undone
+// when they're turned back into integers for the device. The largest positive
+// sample falls just short of 1.0.
+
+pub fn pcm_s8_to_exact(sample: i8) -> f32 {
+    sample as f32 / 128.0
+}
+
+pub fn pcm_u8_to_exact(sample: u8) -> f32 {
+    (sample as f32 - 128.0) / 128.0
+}
+
+pub fn pcm_s16_to_exact(sample: i16) -> f32 {
+    sample as f32 / 32_768.0
+}
+
+pub fn pcm_u16_to_exact(sample: u16) -> f32 {
+    (sample as f32 - 32_768.0) / 32_768.0
+}
+
+pub fn pcm_s24_to_exact(sample: i24) -> f32 {
+    sample.inner() as f32 / 8_388_608.0
+}
+
+pub fn pcm_u24_to_exact(sample: u24) -> f32 {
+    (sample.inner() as f32 - 8_388_608.0) / 8_388_608.0
+}
+
+/// Only the top 24 bits survive.
+pub fn pcm_s32_to_exact(sample: i32) -> f32 {
+    sample as f32 / 2_147_483_648.0
+}
+
+/// Only the top 24 bits survive.
+pub fn pcm_u32_to_exact(sample: u32) -> f32 {
+    (sample as f32 - 2_147_483_648.0) / 2_147_483_648.0
+}
+
 #[cfg(test)]
 mod test {
     use paste::paste;
+    use rodio::cpal::FromSample;
 
     use super::*;
 
@@ -106,4 +146,18 @@ mod test {
     test_pcm!(pcm_u24, u24::MIN, u24::MAX);
     test_pcm!(pcm_s32, i32::MIN, i32::MAX);
     test_pcm!(pcm_u32, u32::MIN, u32::MAX);
+
+    #[test]
+    fn exact_samples_come_back_unchanged() {
+        for sample in i16::MIN..=i16::MAX {
+            assert_eq!(i16::from_sample_(pcm_s16_to_exact(sample)), sample);
+        }
+        for sample in (I24_MIN..=I24_MAX).step_by(251).chain([I24_MAX]) {
+            let exact = pcm_s24_to_exact(i24::from(sample));
+            // a 24-bit device takes them in the top of 32 bits:
+            assert_eq!(i32::from_sample_(exact), sample << 8);
+        }
+        assert_eq!(u16::from_sample_(pcm_u16_to_exact(u16::MAX)), u16::MAX);
+        assert_eq!(u8::from_sample_(pcm_u8_to_exact(0)), 0);
+    }
 }
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, hash_map},
     error::Error,
     fs::File,
     io::{BufReader, Write},
@@ -61,6 +61,40 @@ impl Library {
         self.track_registry.get(&id)
     }
 
+    pub fn tracks(&self) -> hash_map::Iter<'_, u64, Track> {
+        self.track_registry.iter()
+    }
+
+    /// Returns the track at the given path, or every track under the directory
+    /// at the given path, in the order they're displayed.
+    pub fn tracks_under(&self, path: &PathBuf) -> Option<Vec<u64>> {
+        fn add_dir(lib: &Library, id: u64, tracks: &mut Vec<u64>) {
+            let Some(dir) = lib.get_directory(id) else {
+                return;
+            };
+            tracks.extend_from_slice(&dir.tracks);
+            let mut subdirs = dir.subdirs.clone();
+            subdirs.sort_by_key(|id| {
+                lib.get_directory(*id)
+                    .and_then(|dir| dir.path.file_name().map(|s| s.to_owned()))
+            });
+            subdirs
+                .into_iter()
+                .for_each(|subdir| add_dir(lib, subdir, tracks));
+        }
+
+        let id = path_hash(path);
+        if self.get_track(id).is_some() {
+            Some(vec![id])
+        } else if self.get_directory(id).is_some() {
+            let mut tracks = vec![];
+            add_dir(self, id, &mut tracks);
+            Some(tracks)
+        } else {
+            None
+        }
+    }
+
     pub fn current_directory(&self) -> &Directory {
         self.dir_registry.get(&self.curr_dir).unwrap()
     }
@@ -78,11 +112,39 @@ impl Library {
         self.curr_dir = id;
     }
 
+    /// Sets the top level of the library from the directories of its root
+    /// folders. A single root is the top level itself; several are gathered
+    /// under a virtual directory with an empty path.
+    pub fn set_roots(&mut self, roots: Vec<u64>) {
+        self.dir_registry.remove(&path_hash(&PathBuf::new()));
+        let root = match roots[..] {
+            [root] => {
+                if let Some(dir) = self.get_directory_mut(root) {
+                    dir.parent = 0;
+                }
+                root
+            }
+            _ => self.add_directory(Directory {
+                subdirs: roots,
+                ..Directory::new(PathBuf::new())
+            }),
+        };
+        self.set_root(root);
+    }
+
+    /// Drops every directory and track that isn't under one of `roots`.
+    pub fn retain_under(&mut self, roots: &[PathBuf]) {
+        let under =
+            |path: &PathBuf| roots.iter().any(|root| path.starts_with(root));
+        self.dir_registry.retain(|_, dir| under(&dir.path));
+        self.track_registry.retain(|_, track| under(&track.path));
+    }
+
     pub fn file_path() -> std::io::Result<PathBuf> {
-        let mut path = crate::exe_path()?;
-        path.push(".cache/");
-        std::fs::create_dir_all(&path)?;
-        path.push("library");
+        let mut path = crate::paths::cache_dir()?;
+        // renamed whenever the format changes, so an old cache is rescanned
+        // rather than misread:
+        path.push("library-3");
         Ok(path)
     }
 
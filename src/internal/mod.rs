@@ -11,6 +11,7 @@ pub use scan::{partial_scan, scan};
 pub mod audio;
 pub mod library;
 pub mod scan;
+pub mod tags;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Directory {
@@ -31,6 +32,18 @@ impl Directory {
             tracks: vec![],
         }
     }
+
+    /// The name shown for the directory: its folder's name, or "Library" for
+    /// the virtual directory holding several library roots.
+    pub fn name(&self) -> String {
+        if self.path.as_os_str().is_empty() {
+            return String::from("Library");
+        }
+        self.path.file_name().map_or_else(
+            || self.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
 }
 
 #[derive(Copy, Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -56,4 +69,6 @@ pub struct Metadata {
     pub discnum: Option<usize>,
     pub num: Option<usize>,
     pub duration: Option<Duration>,
+    pub album_artist: Option<String>,
+    pub genres: Vec<String>,
 }
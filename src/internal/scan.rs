@@ -7,17 +7,30 @@ enum ScanResult {
     Image(PathBuf),
 }
 
-pub fn scan(path: &Path) -> Library {
-    let mut lib = Library::new();
-    let root = scan_dir(&mut lib, path.to_path_buf()).unwrap_or_else(|| {
-        lib.add_directory(Directory::new(path.to_path_buf()))
-    });
-    lib.set_root(root);
-    lib
+pub fn scan(roots: &[PathBuf]) -> Library {
+    partial_scan(roots, Library::new())
 }
 
-pub fn partial_scan(path: &Path, mut lib: Library) -> Library {
-    scan_dir(&mut lib, path.to_path_buf());
+/// Scans `roots` for anything not already in `lib`, dropping whatever `lib`
+/// has from outside of them.
+pub fn partial_scan(roots: &[PathBuf], mut lib: Library) -> Library {
+    lib.retain_under(roots);
+    let mut ids = vec![];
+    for path in roots {
+        if !path.is_dir() {
+            eprintln!("Library folder {} isn't available", path.display());
+            // keep whatever was cached from it:
+            let id = library::path_hash(path);
+            if lib.get_directory(id).is_some() {
+                ids.push(id);
+            }
+            continue;
+        }
+        let id = scan_dir(&mut lib, path.clone())
+            .unwrap_or_else(|| lib.add_directory(Directory::new(path.clone())));
+        ids.push(id);
+    }
+    lib.set_roots(ids);
     lib
 }
 
@@ -45,6 +58,9 @@ fn scan_flac(path: &PathBuf) -> Track {
     let title = reader.get_tag("TITLE").next().map(|s| s.to_owned());
     let artists = reader.get_tag("ARTIST").map(|s| s.to_owned()).collect();
     let album = reader.get_tag("ALBUM").next().map(|s| s.to_owned());
+    let album_artist =
+        reader.get_tag("ALBUMARTIST").next().map(|s| s.to_owned());
+    let genres = reader.get_tag("GENRE").map(|s| s.to_owned()).collect();
     let discnum = reader
         .get_tag("DISCNUMBER")
         .next()
@@ -55,8 +71,9 @@ fn scan_flac(path: &PathBuf) -> Track {
         .map(|s| s.split('/').next().unwrap().parse::<usize>().unwrap_or(0));
     let duration = {
         let stream_info = reader.streaminfo();
-        Duration::from_secs(
-            stream_info.samples.unwrap() / stream_info.sample_rate as u64,
+        audio::frames_to_duration(
+            stream_info.samples.unwrap(),
+            stream_info.sample_rate,
         )
     };
 
@@ -70,6 +87,8 @@ fn scan_flac(path: &PathBuf) -> Track {
             discnum,
             num,
             duration: Some(duration),
+            album_artist,
+            genres,
         },
     }
 }
@@ -98,8 +117,9 @@ fn scan_vorbis(path: &PathBuf) -> Option<Track> {
     let stream = OggStreamReader::new(File::open(path).unwrap()).ok()?;
 
     let duration = sample_len.map(|samples| {
-        Duration::from_secs(
-            samples as u64 / stream.ident_hdr.audio_sample_rate as u64,
+        audio::frames_to_duration(
+            samples as u64,
+            stream.ident_hdr.audio_sample_rate,
         )
     });
 
@@ -108,6 +128,8 @@ fn scan_vorbis(path: &PathBuf) -> Option<Track> {
     let mut album = None;
     let mut discnum = None;
     let mut num = None;
+    let mut album_artist = None;
+    let mut genres = vec![];
 
     for (key, value) in stream.comment_hdr.comment_list {
         match key.as_str() {
@@ -120,6 +142,12 @@ fn scan_vorbis(path: &PathBuf) -> Option<Track> {
             "ALBUM" => {
                 album = Some(value);
             }
+            "ALBUMARTIST" => {
+                album_artist = Some(value);
+            }
+            "GENRE" => {
+                genres.push(value);
+            }
             "DISCNUMBER" => {
                 discnum = Some(
                     value
@@ -151,6 +179,8 @@ fn scan_vorbis(path: &PathBuf) -> Option<Track> {
         discnum,
         num,
         duration,
+        album_artist,
+        genres,
     };
 
     Some(Track {
@@ -217,15 +247,12 @@ fn get_vorbis_duration(path: &PathBuf) -> Option<u32> {
 fn scan_wav(path: &PathBuf) -> Track {
     let mut metadata = read_id3(path).unwrap_or_default();
 
-    if metadata.duration.is_none() {
-        let reader = hound::WavReader::open(path).unwrap();
-        let duration = Some(Duration::from_secs(
-            reader.duration() as u64 / reader.spec().sample_rate as u64,
+    // the header's more reliable than a TLEN tag:
+    if let Ok(reader) = hound::WavReader::open(path) {
+        metadata.duration = Some(audio::frames_to_duration(
+            reader.duration() as u64,
+            reader.spec().sample_rate,
         ));
-        metadata = Metadata {
-            duration,
-            ..metadata
-        };
     }
 
     Track {
@@ -246,9 +273,15 @@ fn read_id3(path: &PathBuf) -> Option<Metadata> {
                 .map(|v| v.into_iter().map(|s| s.to_owned()).collect())
                 .unwrap_or(vec![]);
             let album = tag.album().map(|s| s.to_owned());
+            let album_artist = tag.album_artist().map(|s| s.to_owned());
+            let genres = tag
+                .genres()
+                .map(|v| v.into_iter().map(|s| s.to_owned()).collect())
+                .unwrap_or(vec![]);
             let discnum = tag.disc().map(|n| n.try_into().unwrap_or(0));
             let num = tag.track().map(|n| n.try_into().unwrap_or(0));
-            let duration = tag.track().map(|s| Duration::from_secs(s as u64));
+            let duration =
+                tag.duration().map(|ms| Duration::from_millis(ms as u64));
 
             if title.is_none()
                 && artists.is_empty()
@@ -267,6 +300,8 @@ fn read_id3(path: &PathBuf) -> Option<Metadata> {
                 discnum,
                 num,
                 duration,
+                album_artist,
+                genres,
             })
         }
         Err(_) => None,
@@ -284,6 +319,13 @@ fn read_ape(path: &PathBuf) -> Option<Metadata> {
                 .unwrap_or(vec![]);
             let album =
                 tag.item("album").map(|i| i.to_owned().try_into().unwrap());
+            let album_artist = tag
+                .item("album artist")
+                .map(|i| i.to_owned().try_into().unwrap());
+            let genres = tag
+                .item("genre")
+                .map(|i| i.to_owned().try_into().unwrap())
+                .unwrap_or(vec![]);
             let num = tag.item("track").map(|i| {
                 <ape::Item as TryInto<String>>::try_into(i.to_owned())
                     .unwrap()
@@ -301,6 +343,8 @@ fn read_ape(path: &PathBuf) -> Option<Metadata> {
                 discnum: None,
                 num,
                 duration: None,
+                album_artist,
+                genres,
             })
         }
         Err(_) => None,
@@ -499,5 +543,42 @@ mod test {
         assert!(track.metadata.duration.is_some());
         let length = track.metadata.duration.unwrap().as_secs();
         assert_eq!(length, 121);
+
+        let exact = audio::duration::exact_duration(&track.path).unwrap();
+        assert!(audio::duration::agrees(track.metadata.duration, exact));
+        let off = exact + Duration::from_secs(1);
+        assert!(!audio::duration::agrees(Some(off), exact));
+    }
+
+    #[test]
+    fn roots_are_gathered_and_dropped() {
+        let tmp = std::env::temp_dir()
+            .join(format!("polypody-roots-{}", std::process::id()));
+        let roots = [tmp.join("a"), tmp.join("b")];
+        for root in &roots {
+            std::fs::create_dir_all(root).unwrap();
+            std::fs::copy(
+                "test/Minute_Waltz,\
+                 _by_Chopin_-_Performed_by_Sergej_Rachmaninoff.ogg",
+                root.join("waltz.ogg"),
+            )
+            .unwrap();
+        }
+
+        let lib = scan(&roots);
+        let top = lib.root_directory();
+        assert!(top.path.as_os_str().is_empty());
+        assert_eq!(top.subdirs.len(), 2);
+        for id in &top.subdirs {
+            assert_eq!(lib.get_directory(*id).unwrap().parent, lib.root_dir);
+        }
+        assert_eq!(lib.tracks().len(), 2);
+
+        let lib = partial_scan(&roots[..1], lib);
+        assert_eq!(lib.root_directory().path, roots[0]);
+        assert_eq!(lib.root_directory().parent, 0);
+        assert_eq!(lib.tracks().len(), 1);
+
+        std::fs::remove_dir_all(tmp).unwrap();
     }
 }
@@ -0,0 +1,309 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use super::*;
+
+/// POPM ratings corresponding to 0 - 5 stars, following the convention used by
+/// most players which read the frame.
+const POPM_RATINGS: [u8; 6] = [0, 1, 64, 128, 196, 255];
+const POPM_USER: &str = "polypody";
+const VENDOR: &str = "polypody";
+
+const FLAC_MAGIC: &[u8] = b"fLaC";
+const FLAC_VORBIS_COMMENT: u8 = 4;
+const VORBIS_COMMENT_HEADER: &[u8] = b"\x03vorbis";
+
+type Comments = Vec<(String, String)>;
+
+/// Writes a star rating (0 - 5, where 0 means unrated) into the track's file,
+/// as a POPM frame for ID3 tags, or as `RATING` (stars) and `FMPS_RATING`
+/// (0.0 - 1.0) Vorbis comments.
+pub fn write_rating(track: &Track, stars: u8) -> Result<(), Box<dyn Error>> {
+    let stars = stars.min(5);
+    match track.audio_type {
+        AudioType::Mp3 | AudioType::Wav => write_popm(&track.path, stars),
+        AudioType::Flac => {
+            write_flac_comments(&track.path, |c| set_rating_comments(c, stars))
+        }
+        AudioType::Vorbis => write_vorbis_comments(&track.path, |c| {
+            set_rating_comments(c, stars)
+        }),
+    }
+}
+
+fn write_popm(path: &Path, stars: u8) -> Result<(), Box<dyn Error>> {
+    use id3::{Tag, TagLike, Version, frame::Popularimeter};
+
+    let mut tag =
+        id3::no_tag_ok(Tag::read_from_path(path))?.unwrap_or_default();
+    let version = if tag.frames().next().is_some() {
+        tag.version()
+    } else {
+        Version::Id3v24
+    };
+
+    // keep the play counter, if we've written one before:
+    let counter = tag
+        .frames()
+        .filter_map(|f| f.content().popularimeter())
+        .find(|p| p.user == POPM_USER)
+        .map_or(0, |p| p.counter);
+    let others = tag
+        .remove("POPM")
+        .into_iter()
+        .filter(|f| {
+            f.content()
+                .popularimeter()
+                .is_some_and(|p| p.user != POPM_USER)
+        })
+        .collect::<Vec<_>>();
+    others.into_iter().for_each(|f| {
+        tag.add_frame(f);
+    });
+    if stars != 0 {
+        tag.add_frame(Popularimeter {
+            user: POPM_USER.to_owned(),
+            rating: POPM_RATINGS[stars as usize],
+            counter,
+        });
+    }
+
+    Ok(tag.write_to_path(path, version)?)
+}
+
+fn set_rating_comments(comments: &mut Comments, stars: u8) {
+    comments.retain(|(key, _)| {
+        !key.eq_ignore_ascii_case("RATING")
+            && !key.eq_ignore_ascii_case("FMPS_RATING")
+    });
+    if stars != 0 {
+        comments.push((String::from("RATING"), stars.to_string()));
+        comments.push((
+            String::from("FMPS_RATING"),
+            (stars as f32 / 5.0).to_string(),
+        ));
+    }
+}
+
+/// Parses the body of a Vorbis comment block (without the framing used by
+/// either Ogg or FLAC), returning the vendor string and the comments.
+fn parse_comments(data: &[u8]) -> Option<(String, Comments)> {
+    fn read_u32(data: &[u8], pos: &mut usize) -> Option<usize> {
+        let bytes = data.get(*pos..*pos + 4)?;
+        *pos += 4;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    }
+
+    fn read_str(data: &[u8], pos: &mut usize) -> Option<String> {
+        let len = read_u32(data, pos)?;
+        let bytes = data.get(*pos..*pos + len)?;
+        *pos += len;
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    let mut pos = 0;
+    let vendor = read_str(data, &mut pos)?;
+    let count = read_u32(data, &mut pos)?;
+    let mut comments = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        let comment = read_str(data, &mut pos)?;
+        let (key, value) = comment.split_once('=').unwrap_or((&comment, ""));
+        comments.push((key.to_owned(), value.to_owned()));
+    }
+    Some((vendor, comments))
+}
+
+fn serialize_comments(vendor: &str, comments: &Comments) -> Vec<u8> {
+    let mut data = vec![];
+    data.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    data.extend_from_slice(vendor.as_bytes());
+    data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for (key, value) in comments {
+        let comment = format!("{key}={value}");
+        data.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        data.extend_from_slice(comment.as_bytes());
+    }
+    data
+}
+
+/// Rewrites a file through a temporary file next to it, so that an error
+/// partway through doesn't leave the original truncated.
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let res = File::create(&tmp)
+        .map_err(|e| e.into())
+        .and_then(|f| {
+            let mut writer = BufWriter::new(f);
+            write(&mut writer)?;
+            Ok(writer.flush()?)
+        })
+        .and_then(|_| Ok(std::fs::rename(&tmp, path)?));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res
+}
+
+fn write_flac_comments(
+    path: &Path,
+    update: impl FnOnce(&mut Comments),
+) -> Result<(), Box<dyn Error>> {
+    let mut data = vec![];
+    File::open(path)?.read_to_end(&mut data)?;
+    if !data.starts_with(FLAC_MAGIC) {
+        return Err("not a FLAC file".into());
+    }
+
+    // (block type, block data):
+    let mut blocks = vec![];
+    let mut pos = FLAC_MAGIC.len();
+    loop {
+        let header = data.get(pos..pos + 4).ok_or("truncated FLAC header")?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let len =
+            u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        pos += 4;
+        let body = data.get(pos..pos + len).ok_or("truncated FLAC header")?;
+        blocks.push((block_type, body.to_vec()));
+        pos += len;
+        if last {
+            break;
+        }
+    }
+    let frames = &data[pos..];
+
+    let (vendor, mut comments) = blocks
+        .iter()
+        .find(|(block_type, _)| *block_type == FLAC_VORBIS_COMMENT)
+        .and_then(|(_, body)| parse_comments(body))
+        .unwrap_or_else(|| (String::from(VENDOR), vec![]));
+    update(&mut comments);
+    let body = serialize_comments(&vendor, &comments);
+    if body.len() >= 1 << 24 {
+        return Err("Vorbis comment block too large".into());
+    }
+
+    match blocks
+        .iter_mut()
+        .find(|(block_type, _)| *block_type == FLAC_VORBIS_COMMENT)
+    {
+        Some((_, old)) => *old = body,
+        // STREAMINFO always has to come first:
+        None => blocks.insert(1, (FLAC_VORBIS_COMMENT, body)),
+    }
+
+    replace_file(path, |f| {
+        f.write_all(FLAC_MAGIC)?;
+        let count = blocks.len();
+        for (i, (block_type, body)) in blocks.iter().enumerate() {
+            let last = if i == count - 1 { 0x80 } else { 0 };
+            let len = (body.len() as u32).to_be_bytes();
+            f.write_all(&[block_type | last, len[1], len[2], len[3]])?;
+            f.write_all(body)?;
+        }
+        Ok(f.write_all(frames)?)
+    })
+}
+
+fn write_vorbis_comments(
+    path: &Path,
+    update: impl FnOnce(&mut Comments),
+) -> Result<(), Box<dyn Error>> {
+    use ogg::{PacketReader, PacketWriteEndInfo, PacketWriter};
+
+    let mut reader = PacketReader::new(BufReader::new(File::open(path)?));
+    let mut update = Some(update);
+
+    replace_file(path, |f| {
+        let mut writer = PacketWriter::new(f);
+        // the comment header is the second packet of the (first) stream:
+        let mut serial = None;
+        let mut index = 0;
+        while let Some(packet) = reader.read_packet()? {
+            let stream = packet.stream_serial();
+            let serial = *serial.get_or_insert(stream);
+            let end = if packet.last_in_stream() {
+                PacketWriteEndInfo::EndStream
+            } else if packet.last_in_page() {
+                PacketWriteEndInfo::EndPage
+            } else {
+                PacketWriteEndInfo::NormalPacket
+            };
+            let absgp = packet.absgp_page();
+            let mut data = packet.data;
+            if stream == serial {
+                if index == 1 {
+                    let body = data
+                        .strip_prefix(VORBIS_COMMENT_HEADER)
+                        .ok_or("missing Vorbis comment header")?;
+                    let (vendor, mut comments) =
+                        parse_comments(body).ok_or("bad Vorbis comment")?;
+                    if let Some(update) = update.take() {
+                        update(&mut comments);
+                    }
+                    data = VORBIS_COMMENT_HEADER.to_vec();
+                    data.extend(serialize_comments(&vendor, &comments));
+                    data.push(1); // framing bit
+                }
+                index += 1;
+            }
+            writer.write_packet(data.into_boxed_slice(), stream, end, absgp)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vorbis_rating_round_trips() {
+        use lewton::inside_ogg::OggStreamReader;
+
+        let path = std::env::temp_dir().join("polypody_vorbis_rating.ogg");
+        std::fs::copy(
+            "test/Minute_Waltz,_by_Chopin_-_Performed_by_Sergej_Rachmaninoff.\
+             ogg",
+            &path,
+        )
+        .unwrap();
+        let track = Track {
+            path: path.clone(),
+            audio_type: AudioType::Vorbis,
+            metadata: Metadata::default(),
+        };
+
+        write_rating(&track, 4).unwrap();
+        let stream = OggStreamReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(stream.comment_hdr.vendor, "Xiph.Org libVorbis I 20050304");
+        let comments = stream.comment_hdr.comment_list;
+        assert!(comments.contains(&("RATING".into(), "4".into())));
+        assert!(comments.contains(&("FMPS_RATING".into(), "0.8".into())));
+
+        write_rating(&track, 0).unwrap();
+        let mut stream =
+            OggStreamReader::new(File::open(&path).unwrap()).unwrap();
+        assert!(
+            !stream
+                .comment_hdr
+                .comment_list
+                .iter()
+                .any(|(key, _)| key == "RATING")
+        );
+        // the audio itself should be untouched:
+        assert!(stream.read_dec_packet_itl().unwrap().is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! The protocol spoken over the daemon's control socket.
+//!
+//! Clients connect to the Unix socket at [`socket_path`] and send requests,
+//! one JSON object per line. Every request is answered with a single line
+//! containing a [`Response`]. See `doc/USER_GUIDE.md` for the full list of
+//! commands.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixStream,
+};
+
+use crate::app::{PlayStatus, RepeatStatus};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Clear,
+    /// Appends a track, or every track in a directory, to the queue, and
+    /// starts playing if nothing is.
+    Enqueue {
+        path: PathBuf,
+    },
+    /// Plays the track at the given index in the queue immediately, dropping
+    /// the tracks before it.
+    Jump {
+        index: usize,
+    },
+    Next,
+    Pause,
+    Play,
+    Quit,
+    Repeat {
+        repeat: RepeatStatus,
+    },
+    /// Seeks within the current track, to a position in seconds.
+    Seek {
+        position: f64,
+    },
+    /// Replaces the queue, without changing the current track.
+    SetQueue {
+        paths: Vec<PathBuf>,
+    },
+    Status,
+    Stop,
+    Toggle,
+    /// Sets the volume, from 0.0 - 1.0.
+    Volume {
+        volume: f32,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Response {
+    pub ok:     bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error:  Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn error(e: impl ToString) -> Self {
+        Self {
+            ok: false,
+            error: Some(e.to_string()),
+            ..Self::default()
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        Self {
+            ok: true,
+            status: Some(status),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Status {
+    pub state:    PlayStatus,
+    pub track:    Option<PathBuf>,
+    /// Position in the current track, in seconds.
+    pub position: f64,
+    /// Duration of the current track, in seconds.
+    pub duration: Option<f64>,
+    pub volume:   f32,
+    pub repeat:   RepeatStatus,
+    pub queue:    Vec<PathBuf>,
+}
+
+pub fn socket_path() -> std::io::Result<PathBuf> {
+    let mut path = crate::paths::data_dir()?;
+    path.push("polypody.sock");
+    Ok(path)
+}
+
+/// Sends a single request to the daemon and waits for its response.
+pub async fn request(request: &Request) -> std::io::Result<Response> {
+    let stream = UnixStream::connect(socket_path()?).await?;
+    let (read, mut write) = stream.into_split();
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(read).read_line(&mut line).await?;
+    Ok(serde_json::from_str(&line)?)
+}
@@ -2,7 +2,6 @@
 #![feature(new_range_api)]
 #![feature(seek_stream_len)]
 #![feature(slice_as_array)]
-#![feature(slice_swap_unchecked)]
 #![feature(stmt_expr_attributes)]
 #![deny(unused_imports)]
 #![deny(unused_import_braces)]
@@ -18,17 +17,94 @@ use internal::{
 use symphonia::core::{codecs::CodecRegistry, probe::Probe};
 
 pub mod app;
+pub mod cli;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(unix)]
+pub mod instance;
 pub mod internal;
+#[cfg(unix)]
+pub mod ipc;
+pub mod paths;
 
 const APP_ICON: &[u8] = include_bytes!("../res/img/icon.png");
 
 fn main() -> iced::Result {
-    let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
+    use clap::Parser;
+
+    let cli = cli::Cli::parse();
+    paths::migrate();
+    if let Some(path) = &cli.config {
+        app::config::Config::set_file_path(path.clone());
+    }
+    let options = cli.launch_options();
+
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, options) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // only one process should be playing, so a second launch hands its
+    // arguments to the first (an attached app doesn't play anything itself):
+    #[cfg(unix)]
+    let _lock = if cli.attach {
+        None
+    } else {
+        let socket = if cli.daemon {
+            ipc::socket_path()
+        } else {
+            instance::socket_path()
+        };
+        match socket.and_then(|socket| instance::acquire(&socket)) {
+            Ok(instance::Instance::Primary(_))
+                if cli.play_pause || cli.next =>
+            {
+                eprintln!("Polypody isn't running");
+                std::process::exit(1);
+            }
+            Ok(instance::Instance::Primary(lock)) => Some(lock),
+            Ok(instance::Instance::Secondary(_)) if cli.daemon => {
+                eprintln!("Polypody is already running");
+                std::process::exit(1);
+            }
+            Ok(instance::Instance::Secondary(socket)) => {
+                let requests = cli.forwarded_requests();
+                if requests.is_empty() {
+                    println!("Polypody is already running");
+                } else if let Err(e) = instance::forward(&socket, &requests) {
+                    eprintln!("{e}");
+                    std::process::exit(1);
+                }
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("Couldn't check for a running instance: {e}");
+                None
+            }
+        }
+    };
+
+    #[cfg(unix)]
+    if cli.daemon {
+        if let Err(e) = daemon::run(options) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    #[cfg(unix)]
+    let attach = cli.attach;
+    #[cfg(not(unix))]
+    let attach = false;
 
     iced::application("polypody", App::update, App::view)
         .font(app::ICON_FONT_BYTES)
         .theme(theme)
-        .subscription(App::progress_subscription)
+        .subscription(App::subscription)
+        .exit_on_close_request(false)
         .window(iced::window::Settings {
             min_size: Some([1200.0, 760.0].into()),
             icon: iced::window::icon::from_file_data(
@@ -38,13 +114,10 @@ fn main() -> iced::Result {
             .ok(),
             ..Default::default()
         })
-        .run_with(|| (App::new(stream_handle), iced::Task::none()))
-}
-
-fn exe_path() -> std::io::Result<PathBuf> {
-    let mut dir = std::env::current_exe()?;
-    dir.pop();
-    Ok(dir)
+        .run_with(move || match attach {
+            false => App::new(options),
+            true => App::attach(options),
+        })
 }
 
 fn theme(_state: &App) -> iced::Theme {
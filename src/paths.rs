@@ -0,0 +1,149 @@
+//! Where Polypody keeps its files. By default, these follow the platform's
+//! conventions -- on Linux, the XDG base directories:
+//!
+//! * the config in `$XDG_CONFIG_HOME/polypody/`,
+//! * playlists and other data in `$XDG_DATA_HOME/polypody/`,
+//! * the library cache in `$XDG_CACHE_HOME/polypody/`.
+//!
+//! If a file named `portable` sits next to the executable, everything is kept
+//! in the executable's directory instead, as it was before.
+
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const APP_DIR: &str = "polypody";
+const PORTABLE_MARKER: &str = "portable";
+/// Library caches in older formats, which would be rescanned rather than read.
+const OLD_CACHES: [&str; 2] = ["library", "library-2"];
+
+fn exe_dir() -> Result<PathBuf> {
+    let mut dir = std::env::current_exe()?;
+    dir.pop();
+    Ok(dir)
+}
+
+pub fn is_portable() -> bool {
+    static PORTABLE: OnceLock<bool> = OnceLock::new();
+    *PORTABLE.get_or_init(|| {
+        exe_dir().is_ok_and(|dir| dir.join(PORTABLE_MARKER).exists())
+    })
+}
+
+/// While set, everything's kept here as in portable mode, so that tests don't
+/// touch the real files.
+#[cfg(test)]
+pub static TEST_DIR: std::sync::Mutex<Option<PathBuf>> =
+    std::sync::Mutex::new(None);
+
+/// `base`/polypody, or `portable` under the executable's directory in portable
+/// mode, created if it doesn't exist yet.
+fn app_dir(base: Option<PathBuf>, portable: &str) -> Result<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = &*TEST_DIR.lock().unwrap() {
+        let path = dir.join(portable);
+        std::fs::create_dir_all(&path)?;
+        return Ok(path);
+    }
+    let path = if is_portable() {
+        exe_dir()?.join(portable)
+    } else {
+        base.ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, "couldn't find the home directory")
+        })?
+        .join(APP_DIR)
+    };
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+
+pub fn config_dir() -> Result<PathBuf> {
+    app_dir(dirs::config_dir(), "")
+}
+
+pub fn data_dir() -> Result<PathBuf> {
+    app_dir(dirs::data_dir(), "")
+}
+
+pub fn cache_dir() -> Result<PathBuf> {
+    app_dir(dirs::cache_dir(), ".cache/")
+}
+
+pub fn playlist_dir() -> Result<PathBuf> {
+    let path = data_dir()?.join("playlists/");
+    std::fs::create_dir_all(&path)?;
+    Ok(path)
+}
+
+/// Moves any files left in the executable's directory by older versions to
+/// where they're kept now, other than portable mode's, and deletes library
+/// caches that are out of date.
+pub fn migrate() {
+    if let Ok(dir) = cache_dir() {
+        for name in OLD_CACHES {
+            let _ = std::fs::remove_file(dir.join(name));
+        }
+    }
+    if is_portable() {
+        return;
+    }
+    let Ok(exe) = exe_dir() else {
+        return;
+    };
+    let files = [
+        (exe.join("config.toml"), config_dir(), "config.toml"),
+        (exe.join("session.toml"), data_dir(), "session.toml"),
+        (exe.join("history"), data_dir(), "history"),
+        (exe.join("userdata"), data_dir(), "userdata"),
+    ];
+    for (from, dir, name) in files {
+        if !from.exists() {
+            continue;
+        }
+        match dir {
+            Ok(dir) => move_file(&from, &dir.join(name)),
+            Err(e) => eprintln!("Couldn't move {}: {e}", from.display()),
+        }
+    }
+    let _ = std::fs::remove_file(exe.join(".cache/library"));
+    let _ = std::fs::remove_dir(exe.join(".cache/"));
+
+    let old_playlists = exe.join("playlists/");
+    let Ok(entries) = old_playlists.read_dir() else {
+        return;
+    };
+    match playlist_dir() {
+        Ok(dir) => {
+            for entry in entries.flatten() {
+                move_file(&entry.path(), &dir.join(entry.file_name()));
+            }
+            // only succeeds if everything was moved:
+            let _ = std::fs::remove_dir(&old_playlists);
+        }
+        Err(e) => {
+            eprintln!("Couldn't move {}: {e}", old_playlists.display())
+        }
+    }
+}
+
+fn move_file(from: &Path, to: &Path) {
+    if to.exists() {
+        eprintln!(
+            "Not moving {}, since {} already exists",
+            from.display(),
+            to.display()
+        );
+        return;
+    }
+    // renaming fails across filesystems, so fall back to copying:
+    let result = std::fs::rename(from, to).or_else(|_| {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)
+    });
+    match result {
+        Ok(()) => println!("Moved {} to {}", from.display(), to.display()),
+        Err(e) => eprintln!("Couldn't move {}: {e}", from.display()),
+    }
+}